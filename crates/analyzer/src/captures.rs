@@ -0,0 +1,150 @@
+use common::ProgramText;
+use parser::parse::Span;
+use std::{collections::HashMap, fmt};
+
+use crate::resolution::Resolutions;
+
+// Variable that a function or closure takes from the scopes around it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Capture {
+    pub name: ProgramText,
+    // The function itself and `this` aren't declared anywhere
+    pub declaration: Option<Span>,
+    // Assigned a new value somewhere, so the closure shares it with the scope it comes from
+    pub mutable: bool,
+    // Taken from an upvalue of the enclosing closure instead of straight from the call frame
+    pub transitive: bool,
+}
+
+impl Capture {
+    pub fn mode(&self) -> &'static str {
+        if self.mutable {
+            "by reference"
+        } else {
+            "by value"
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClosureCaptures {
+    // Span of the function declaration or closure
+    pub function: Span,
+    // Anonymous closures that aren't assigned to a variable don't have one
+    pub name: Option<ProgramText>,
+    // In the order of upvalue indices
+    pub captures: Vec<Capture>,
+}
+
+impl ClosureCaptures {
+    pub fn name(&self) -> &str {
+        self.name.as_deref().unwrap_or("<closure>")
+    }
+}
+
+// Text shown when hovering over the closure
+impl fmt::Display for ClosureCaptures {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.captures.is_empty() {
+            return write!(f, "{} captures nothing", self.name());
+        }
+
+        write!(f, "{} captures:", self.name())?;
+        for capture in &self.captures {
+            write!(f, "\n- {} {}", capture.name, capture.mode())?;
+            if capture.transitive {
+                write!(f, " (through the enclosing closure)")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// Innermost function or closure around the position
+pub fn closure_at(closures: &[ClosureCaptures], offset: usize) -> Option<&ClosureCaptures> {
+    // Closures are ordered by their start, so nested ones come after their parents
+    closures
+        .iter()
+        .rev()
+        .find(|closure| closure.function.contains(&offset))
+}
+
+// Variable captured by a closure created inside a loop, while it lives outside of that loop
+#[derive(Debug, Clone)]
+pub(crate) struct LoopCapture {
+    pub(crate) name: ProgramText,
+    // First use of the variable inside the closure
+    pub(crate) span: Span,
+    pub(crate) declaration: Span,
+    // Outermost closure created inside the loop
+    pub(crate) closure: Span,
+}
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct CaptureCollector {
+    closures: Vec<ClosureCaptures>,
+    positions: HashMap<Span, usize>,
+    in_loops: Vec<LoopCapture>,
+}
+
+impl CaptureCollector {
+    pub(crate) fn enter_function(&mut self, function: &Span, name: Option<&str>) {
+        self.positions.insert(function.clone(), self.closures.len());
+        self.closures.push(ClosureCaptures {
+            function: function.clone(),
+            name: name.map(ToOwned::to_owned),
+            captures: Vec::new(),
+        });
+    }
+
+    // Closures get the name of the variable they are assigned to
+    pub(crate) fn name_closure(&mut self, function: &Span, name: &str) {
+        if let Some(&position) = self.positions.get(function) {
+            self.closures[position].name = Some(name.to_owned());
+        }
+    }
+
+    // Index is the upvalue index of the capture, which is reused by every use of the variable
+    pub(crate) fn capture(&mut self, function: &Span, index: usize, capture: Capture) {
+        let position = match self.positions.get(function) {
+            Some(&position) => position,
+            None => return,
+        };
+        let captures = &mut self.closures[position].captures;
+        if index == captures.len() {
+            captures.push(capture);
+        }
+    }
+
+    pub(crate) fn capture_in_loop(&mut self, capture: LoopCapture) {
+        let captured = self.in_loops.iter().any(|other| {
+            other.declaration == capture.declaration && other.closure == capture.closure
+        });
+        if !captured {
+            self.in_loops.push(capture);
+        }
+    }
+
+    // Mutability is known only once every assignment of the program has been seen
+    pub(crate) fn finish(&mut self, resolutions: &Resolutions) -> Vec<LoopCapture> {
+        for capture in self
+            .closures
+            .iter_mut()
+            .flat_map(|closure| closure.captures.iter_mut())
+        {
+            capture.mutable = capture
+                .declaration
+                .as_ref()
+                .is_some_and(|declaration| resolutions.is_reassigned(declaration));
+        }
+
+        std::mem::take(&mut self.in_loops)
+            .into_iter()
+            .filter(|capture| resolutions.is_reassigned(&capture.declaration))
+            .collect()
+    }
+
+    pub(crate) fn closures(&self) -> &[ClosureCaptures] {
+        &self.closures
+    }
+}
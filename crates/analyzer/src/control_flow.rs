@@ -0,0 +1,337 @@
+use parser::{
+    parse::{
+        expr::{atom::AtomicValue, Expr, ExprKind},
+        stmt::{Stmt, StmtKind},
+        AstRef, Span,
+    },
+    utils::combine,
+};
+
+use crate::warning::{Warning, WarningCause};
+use common::LAMBDA_NAME;
+
+// Every way the control can leave an expression.
+// Falling through means continuing with whatever comes next,
+// with or without a value that the expression evaluated to.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct Flow {
+    falls_value: bool,
+    falls_empty: bool,
+    returns_value: bool,
+    returns_empty: bool,
+    breaks: bool,
+    continues: bool,
+}
+
+impl Flow {
+    fn value() -> Self {
+        Self {
+            falls_value: true,
+            ..Self::default()
+        }
+    }
+
+    fn empty() -> Self {
+        Self {
+            falls_empty: true,
+            ..Self::default()
+        }
+    }
+
+    fn falls(&self) -> bool {
+        self.falls_value || self.falls_empty
+    }
+
+    // Only the exits that jump somewhere else
+    fn jumps(self) -> Self {
+        Self {
+            falls_value: false,
+            falls_empty: false,
+            ..self
+        }
+    }
+
+    // Value of the expression is thrown away, e.g. in statements
+    fn discard(self) -> Self {
+        Self {
+            falls_value: false,
+            falls_empty: self.falls(),
+            ..self
+        }
+    }
+
+    fn union(self, other: Self) -> Self {
+        Self {
+            falls_value: self.falls_value || other.falls_value,
+            falls_empty: self.falls_empty || other.falls_empty,
+            returns_value: self.returns_value || other.returns_value,
+            returns_empty: self.returns_empty || other.returns_empty,
+            breaks: self.breaks || other.breaks,
+            continues: self.continues || other.continues,
+        }
+    }
+}
+
+// Builds the flow graph of every function bottom up, looking for statements that can't be reached
+// and functions that return a value only on some of their paths.
+#[derive(Default)]
+pub(crate) struct ControlFlow {
+    warnings: Vec<Warning>,
+}
+
+impl ControlFlow {
+    pub(crate) fn check(ast: AstRef) -> Vec<Warning> {
+        let mut control_flow = Self::default();
+        for stmt in ast {
+            control_flow.visit_stmt(stmt);
+        }
+
+        control_flow.warnings
+    }
+
+    fn warning(&mut self, span: Span, cause: WarningCause) {
+        self.warnings.push(Warning {
+            span,
+            level: cause.lint().default_level,
+            cause,
+        });
+    }
+
+    fn visit_function(&mut self, name: &str, span: &Span, body: &Expr) {
+        let flow = self.visit_expr(body);
+
+        if flow.returns_value && flow.falls_empty {
+            self.warning(span.clone(), WarningCause::MissingReturn(name.to_owned()));
+        }
+    }
+
+    fn visit_stmt(&mut self, stmt: &Stmt) -> Flow {
+        use StmtKind::*;
+
+        match &*stmt.kind {
+            Expression { expr } | VariableDeclaration { expr, .. } => {
+                self.visit_expr(expr).discard()
+            }
+            FunctionDeclaration { name, body, .. } => {
+                self.visit_function(name, &stmt.span, body);
+                Flow::empty()
+            }
+            ClassDeclaration { methods, .. } => {
+                for method in methods {
+                    self.visit_stmt(method);
+                }
+                Flow::empty()
+            }
+        }
+    }
+
+    // Evaluates expressions one after another, stopping at the first one that never finishes
+    fn visit_sequence<'a>(&mut self, exprs: impl IntoIterator<Item = &'a Expr>) -> Flow {
+        let mut flow = Flow::value();
+
+        for expr in exprs {
+            if !flow.falls() {
+                return flow;
+            }
+            flow = flow.jumps().union(self.visit_expr(expr));
+        }
+
+        flow
+    }
+
+    fn visit_block(&mut self, stmts: &[Stmt], return_expr: Option<&Expr>) -> Flow {
+        let mut flow = Flow::empty();
+
+        for (index, stmt) in stmts.iter().enumerate() {
+            if !flow.falls() {
+                self.unreachable(&stmt.span, &stmts[index - 1].span, stmts, return_expr);
+                return flow;
+            }
+            flow = flow.jumps().union(self.visit_stmt(stmt));
+        }
+
+        match return_expr {
+            Some(expr) if !flow.falls() => {
+                // Something must have diverged, so there is at least one statement
+                let diverging = &stmts.last().unwrap().span;
+                self.unreachable(&expr.span, diverging, stmts, return_expr);
+                flow
+            }
+            Some(expr) => flow.jumps().union(self.visit_expr(expr)),
+            None => flow,
+        }
+    }
+
+    // Reports everything from `start` till the end of the block at once
+    fn unreachable(
+        &mut self,
+        start: &Span,
+        diverging: &Span,
+        stmts: &[Stmt],
+        return_expr: Option<&Expr>,
+    ) {
+        let end = return_expr
+            .map(|expr| &expr.span)
+            .or_else(|| stmts.last().map(|stmt| &stmt.span))
+            .unwrap_or(start);
+
+        self.warning(
+            combine(start, end),
+            WarningCause::UnreachableCode {
+                diverging: diverging.clone(),
+            },
+        );
+    }
+
+    fn visit_expr(&mut self, expr: &Expr) -> Flow {
+        use ExprKind::*;
+
+        match &*expr.kind {
+            Atom(_) => Flow::value(),
+            Binary { lhs, rhs, .. } => self.visit_sequence(vec![lhs, rhs]),
+            Unary { rhs, .. } => self.visit_expr(rhs),
+            Block { stmts, return_expr } => self.visit_block(stmts, return_expr.as_ref()),
+            If {
+                condition,
+                body,
+                else_expr,
+            } => {
+                let condition = self.visit_expr(condition);
+                if !condition.falls() {
+                    return condition;
+                }
+
+                let else_flow = match else_expr {
+                    Some(else_expr) => self.visit_expr(else_expr),
+                    None => Flow::empty(),
+                };
+
+                condition
+                    .jumps()
+                    .union(self.visit_expr(body))
+                    .union(else_flow)
+            }
+            While { condition, body } => {
+                let condition_flow = self.visit_expr(condition);
+                if !condition_flow.falls() {
+                    return condition_flow;
+                }
+
+                let body = self.visit_expr(body);
+                let infinite = matches!(&*condition.kind, Atom(AtomicValue::Boolean(true)));
+
+                // break and continue never leave the loop
+                let exit = Flow {
+                    falls_empty: !infinite || body.breaks,
+                    returns_value: body.returns_value,
+                    returns_empty: body.returns_empty,
+                    ..Flow::default()
+                };
+                condition_flow.jumps().union(exit)
+            }
+            Break { return_expr } => {
+                let flow = self.visit_sequence(return_expr);
+                if !flow.falls() {
+                    return flow;
+                }
+                flow.jumps().union(Flow {
+                    breaks: true,
+                    ..Flow::default()
+                })
+            }
+            Continue => Flow {
+                continues: true,
+                ..Flow::default()
+            },
+            Return { value } => {
+                let flow = self.visit_sequence(value);
+                if !flow.falls() {
+                    return flow;
+                }
+                flow.jumps().union(Flow {
+                    returns_value: value.is_some(),
+                    returns_empty: value.is_none(),
+                    ..Flow::default()
+                })
+            }
+            Call { callee, args } => self.visit_sequence(std::iter::once(callee).chain(args)),
+            Array { values } => self.visit_sequence(values),
+            Index { target, position } => self.visit_sequence(vec![target, position]),
+            GetProperty { target, .. } => self.visit_expr(target),
+            SetProperty { target, value, .. } => self.visit_sequence(vec![target, value]),
+            ObjectLiteral { properties } => {
+                self.visit_sequence(properties.iter().map(|(_, value)| value))
+            }
+            Assignment { target, value } => self.visit_sequence(vec![target, value]),
+            Closure { body, .. } => {
+                self.visit_function(LAMBDA_NAME, &expr.span, body);
+                Flow::value()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use parser::parse;
+
+    use super::*;
+
+    fn warnings(code: &str) -> Vec<WarningCause> {
+        let ast = parse(code).unwrap();
+        ControlFlow::check(&ast)
+            .into_iter()
+            .map(|warning| warning.cause)
+            .collect()
+    }
+
+    fn unreachable(diverging: Span) -> WarningCause {
+        WarningCause::UnreachableCode { diverging }
+    }
+
+    #[test]
+    fn finds_unreachable_code() {
+        assert_eq!(
+            warnings("fn foo() { return 1; let a = 2; a }"),
+            vec![unreachable(11..20)]
+        );
+        assert_eq!(
+            warnings("while true { break; 1; };"),
+            vec![unreachable(13..19)]
+        );
+        assert_eq!(
+            warnings("while true { if true { continue; } else { break; }; 1; };"),
+            vec![unreachable(13..51)]
+        );
+        // code after an infinite loop
+        assert_eq!(
+            warnings("fn foo() { while true { }; 1 }"),
+            vec![unreachable(11..26)]
+        );
+        // only one of the branches diverges
+        assert_eq!(warnings("while true { if true { break; }; 1; };"), vec![]);
+        // loop left with break
+        assert_eq!(warnings("fn foo() { while true { break; }; 1 }"), vec![]);
+    }
+
+    #[test]
+    fn finds_missing_returns() {
+        let missing = |name: &str| WarningCause::MissingReturn(name.to_owned());
+
+        assert_eq!(
+            warnings("fn foo(a) { if a { return 1; } }"),
+            vec![missing("foo")]
+        );
+        assert_eq!(
+            warnings("let f = |a| => { while a { return 1; }; };"),
+            vec![missing(LAMBDA_NAME)]
+        );
+        assert_eq!(warnings("fn foo(a) { if a { return 1; }; 2 }"), vec![]);
+        assert_eq!(
+            warnings("fn foo(a) { if a { return 1; } else { return 2; } }"),
+            vec![]
+        );
+        assert_eq!(warnings("fn foo(a) { if a { return; } }"), vec![]);
+        assert_eq!(warnings("fn foo() { while true { return 1; } }"), vec![]);
+    }
+}
@@ -1,103 +1,492 @@
-use common::ProgramText;
+use captures::{Capture, CaptureCollector, ClosureCaptures, LoopCapture};
+use common::{find_std_function, ProgramText, BUILT_IN_FUNCTIONS};
+use control_flow::ControlFlow;
+use metrics::{measure, Thresholds};
 use parser::{
     parse::{
         expr::{atom::AtomicValue, Expr, ExprKind},
         stmt::{Stmt, StmtKind},
-        AstRef,
+        AstRef, Params, Span,
+    },
+    utils::{
+        combine,
+        error::{ParseError, ParseErrorCause},
     },
-    utils::error::{ParseError, ParseErrorCause},
 };
-use std::collections::HashMap;
-use vm::gravitas_std::NATIVE_FUNCTIONS;
+use purity::Purity;
+use resolution::{Resolution, Resolutions};
+use std::collections::{HashMap, HashSet};
+use symbols::{ScopeSymbols, Symbol, SymbolKind};
+use types::{Inference, Types};
+use warning::{Warning, WarningCause};
+
+pub mod captures;
+pub(crate) mod control_flow;
+pub mod lint;
+pub mod metrics;
+pub(crate) mod purity;
+pub mod references;
+pub mod rename;
+pub mod resolution;
+pub mod symbols;
+pub mod types;
+pub mod warning;
 
 pub type AnalyzerResult<E> = Result<(), E>;
 
+const THIS: &str = "this";
+const SUPER: &str = "super";
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum ScopeType {
     Function,
     Loop,
+    Block,
     Global,
+    // Methods of the class, `super` needs a superclass
+    Class { inherits: bool },
 }
 
-type Variables = HashMap<ProgramText, bool>;
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum VariableKind {
+    Builtin,
+    Variable,
+    Parameter,
+    Closure,
+    Function,
+    Class,
+    // Slots of a call frame that hold the called function and `this`
+    Reserved,
+}
+
+// Parameters of a function known at compile time
+#[derive(Debug, Clone)]
+struct Signature {
+    arity: usize,
+    // Parameter list, native functions don't have one
+    span: Option<Span>,
+    // Declaration of the variable holding the function, builtins have none
+    declaration: Option<Span>,
+}
+
+// Call of a function with a known signature, checked once every assignment has been resolved
+#[derive(Debug, Clone)]
+struct Call {
+    signature: Signature,
+    found: usize,
+    span: Span,
+}
+
+#[derive(Debug, Clone)]
+struct Variable {
+    kind: VariableKind,
+    // Builtins are not declared anywhere in the source
+    span: Option<Span>,
+    initialized: bool,
+    used: bool,
+    // Lost once something else is assigned to the variable
+    signature: Option<Signature>,
+    // Position in the call frame, given once the value is on the stack
+    slot: Option<usize>,
+}
+
+impl Variable {
+    fn builtin(arity: usize) -> Self {
+        Self {
+            kind: VariableKind::Builtin,
+            span: None,
+            initialized: true,
+            used: false,
+            signature: Some(Signature {
+                arity,
+                span: None,
+                declaration: None,
+            }),
+            slot: None,
+        }
+    }
+}
+
+type Variables = HashMap<ProgramText, Variable>;
 
 #[derive(Debug, Clone)]
 struct Scope {
     scope_type: ScopeType,
-    variables: HashMap<ProgramText, bool>,
+    variables: Variables,
+    // Blocks continue numbering slots of the call frame they are in
+    next_slot: usize,
+    // Span of the function node, if the scope is its body
+    function: Option<Span>,
+    // Part of the source where the variables of the scope can be used
+    span: Span,
 }
 
 impl Scope {
-    fn new(scope_type: ScopeType) -> Self {
+    fn new(scope_type: ScopeType, next_slot: usize, span: &Span) -> Self {
         Self {
             scope_type,
             variables: HashMap::new(),
+            next_slot,
+            function: None,
+            span: span.clone(),
+        }
+    }
+
+    fn function(span: &Span) -> Self {
+        Self {
+            function: Some(span.clone()),
+            ..Self::new(ScopeType::Function, 0, span)
         }
     }
 
     fn global(global_variables: Variables) -> Self {
         Self {
-            scope_type: ScopeType::Global,
             variables: global_variables,
+            ..Self::new(ScopeType::Global, 0, &(0..usize::MAX))
         }
     }
 
-    fn is_global(&self) -> bool {
-        self.scope_type == ScopeType::Global
-    }
+    fn symbols(&self) -> ScopeSymbols {
+        let mut symbols: Vec<Symbol> = self
+            .variables
+            .iter()
+            .filter_map(|(name, var)| {
+                let kind = match var.kind {
+                    VariableKind::Builtin => SymbolKind::Builtin,
+                    VariableKind::Variable => SymbolKind::Variable,
+                    VariableKind::Parameter => SymbolKind::Parameter,
+                    VariableKind::Closure => SymbolKind::Closure,
+                    VariableKind::Function => SymbolKind::Function,
+                    VariableKind::Class => SymbolKind::Class,
+                    VariableKind::Reserved => return None,
+                };
 
-    fn is_function(&self) -> bool {
-        self.scope_type == ScopeType::Function
+                Some(Symbol {
+                    name: name.clone(),
+                    kind,
+                    declaration: var.span.clone(),
+                })
+            })
+            .collect();
+        symbols.sort_by(|a, b| a.name.cmp(&b.name));
+
+        ScopeSymbols {
+            span: self.span.clone(),
+            symbols,
+        }
     }
 
-    fn is_loop(&self) -> bool {
-        self.scope_type == ScopeType::Loop
+    fn is_global(&self) -> bool {
+        self.scope_type == ScopeType::Global
     }
 }
 
+// Names starting with an underscore are deliberately unused
+fn is_silenced(name: &str) -> bool {
+    name.starts_with('_')
+}
+
 #[derive(Default)]
 pub struct Analyzer {
     scopes: Vec<Scope>,
+    errors: Vec<ParseError>,
+    warnings: Vec<Warning>,
+    resolutions: Resolutions,
+    // Scopes the analyzer has already left
+    left_scopes: Vec<ScopeSymbols>,
+    captures: CaptureCollector,
+    // Limits of function metrics, above which the analyzer warns
+    thresholds: Thresholds,
+    types: Types,
+    calls: Vec<Call>,
 }
 
 impl Analyzer {
     pub fn new() -> Self {
-        let variables: HashMap<ProgramText, bool> = NATIVE_FUNCTIONS
-            .keys()
-            .cloned()
-            .map(|fun| (fun.into(), true))
+        let variables: Variables = BUILT_IN_FUNCTIONS
+            .iter()
+            .map(|fun| (fun.clone().into(), Variable::builtin(fun.arity())))
             .collect();
 
         let scopes = vec![Scope::global(variables)];
 
         Self {
             scopes,
-            ..Default::default()
+            errors: Vec::new(),
+            warnings: Vec::new(),
+            resolutions: Resolutions::default(),
+            left_scopes: Vec::new(),
+            captures: CaptureCollector::default(),
+            thresholds: Thresholds::default(),
+            types: Types::default(),
+            calls: Vec::new(),
+        }
+    }
+
+    pub fn with_thresholds(thresholds: Thresholds) -> Self {
+        Self {
+            thresholds,
+            ..Self::new()
         }
     }
 
-    fn declare_var(&mut self, name: &str, initialized: bool) {
-        self.current_scope_mut()
+    pub fn warnings(&self) -> &[Warning] {
+        &self.warnings
+    }
+
+    pub fn resolutions(&self) -> &Resolutions {
+        &self.resolutions
+    }
+
+    pub fn types(&self) -> &Types {
+        &self.types
+    }
+
+    // Every scope of the program together with the variables declared in it
+    pub fn scopes(&self) -> Vec<ScopeSymbols> {
+        self.left_scopes
+            .iter()
+            .cloned()
+            .chain(self.scopes.iter().map(Scope::symbols))
+            .collect()
+    }
+
+    // Every function and closure of the program, in the order they start in
+    pub fn captures(&self) -> &[ClosureCaptures] {
+        self.captures.closures()
+    }
+
+    fn declare_var(&mut self, name: &str, kind: VariableKind, span: &Span, initialized: bool) {
+        // Builtins can be declared again, just like variables of outer scopes
+        let redeclared = self
+            .current_scope()
             .variables
-            .insert(name.to_owned(), initialized);
+            .get(name)
+            .and_then(|var| Some((var.kind, var.span.clone()?)));
+
+        if let Some((previous_kind, previous)) = redeclared {
+            self.redeclare_var(name, kind, span, previous_kind, previous);
+        } else if !self.current_scope().is_global() && !is_silenced(name) {
+            let shadowed = self
+                .scopes
+                .iter()
+                .rev()
+                .skip(1)
+                .filter_map(|scope| scope.variables.get(name))
+                .find(|var| var.kind != VariableKind::Reserved);
+
+            if let Some(shadowed) = shadowed {
+                let shadowed = shadowed.span.clone();
+                self.warning(
+                    span,
+                    WarningCause::ShadowedVariable {
+                        name: name.to_owned(),
+                        shadowed,
+                    },
+                );
+            }
+        }
+
+        self.current_scope_mut().variables.insert(
+            name.to_owned(),
+            Variable {
+                kind,
+                span: Some(span.clone()),
+                initialized,
+                used: false,
+                signature: None,
+                slot: None,
+            },
+        );
     }
 
-    fn find_var(&self, name: &ProgramText) -> Option<&bool> {
-        for scope in self.scopes.iter().rev() {
-            if let Some(var) = scope.variables.get(name) {
-                return Some(var);
+    fn redeclare_var(
+        &mut self,
+        name: &str,
+        kind: VariableKind,
+        span: &Span,
+        previous_kind: VariableKind,
+        previous: Span,
+    ) {
+        use VariableKind::*;
+        let name = name.to_owned();
+
+        match (kind, previous_kind) {
+            (Parameter, Parameter) => {
+                self.error(span, ParseErrorCause::DuplicateParameter { name, previous });
             }
+            (Function | Class, _) | (_, Function | Class) => {
+                self.error(span, ParseErrorCause::FunctionRedeclared { name, previous });
+            }
+            _ if !is_silenced(&name) => {
+                self.warning(span, WarningCause::RedeclaredVariable { name, previous });
+            }
+            _ => {}
         }
+    }
 
-        None
+    fn allocate_slot(&mut self, name: &str) {
+        let scope = self.current_scope_mut();
+        let slot = scope.next_slot;
+        scope.next_slot += 1;
+
+        if let Some(var) = scope.variables.get_mut(name) {
+            var.slot = Some(slot);
+            if let Some(declaration) = var.span.clone() {
+                self.resolutions.allocate(&declaration, slot);
+            }
+        }
+    }
+
+    // Takes the next slot without declaring anything, unless there's a name that isn't taken yet
+    fn reserve_slot(&mut self, name: Option<&str>) {
+        let signature = name
+            .and_then(|name| self.find_var(name))
+            .and_then(|var| var.signature.clone());
+        let scope = self.current_scope_mut();
+
+        if let Some(name) = name.filter(|name| !scope.variables.contains_key(*name)) {
+            scope.variables.insert(
+                name.to_owned(),
+                Variable {
+                    kind: VariableKind::Reserved,
+                    span: None,
+                    initialized: true,
+                    used: false,
+                    signature,
+                    slot: Some(scope.next_slot),
+                },
+            );
+        }
+        scope.next_slot += 1;
+    }
+
+    fn declare_signature(&mut self, name: &str, params: &Params) {
+        if let Some(var) = self.current_scope_mut().variables.get_mut(name) {
+            var.signature = Some(Signature {
+                arity: params.kind.len(),
+                span: Some(params.span.clone()),
+                declaration: var.span.clone(),
+            });
+        }
+    }
+
+    fn initialize_var(&mut self, name: &str) {
+        if let Some(var) = self.current_scope_mut().variables.get_mut(name) {
+            var.initialized = true;
+        }
+        self.allocate_slot(name);
+    }
+
+    fn find_var(&mut self, name: &str) -> Option<&mut Variable> {
+        self.scopes
+            .iter_mut()
+            .rev()
+            .find_map(|scope| scope.variables.get_mut(name))
+    }
+
+    fn enter_scope(&mut self, scope_type: ScopeType, span: &Span) {
+        let next_slot = self.current_scope().next_slot;
+        self.scopes.push(Scope::new(scope_type, next_slot, span));
+    }
+
+    // Records where the variable lives as seen from the current function
+    fn resolve(&mut self, name: &str, span: &Span, is_assignment: bool) {
+        let depth = match self
+            .scopes
+            .iter()
+            .rposition(|scope| scope.variables.contains_key(name))
+        {
+            Some(depth) => depth,
+            None => return,
+        };
+        let var = &self.scopes[depth].variables[name];
+        if let Some(declaration) = &var.span {
+            self.resolutions.declare(span, declaration, is_assignment);
+        }
+
+        let resolution = match (var.kind, var.slot) {
+            (VariableKind::Builtin, _) => match find_std_function(name) {
+                Some(function) => Resolution::Builtin(function),
+                None => return,
+            },
+            (_, Some(slot)) => self.capture(name, span, depth, slot),
+            // Not on the stack yet, which is already an error
+            (_, None) => return,
+        };
+        self.resolutions.resolve(span, resolution);
     }
 
-    fn enter_scope(&mut self, scope_type: ScopeType) {
-        self.scopes.push(Scope::new(scope_type));
+    // Every function between the variable and the current scope captures it
+    // from the frame (or the closure) that creates it
+    fn capture(&mut self, name: &str, span: &Span, depth: usize, slot: usize) -> Resolution {
+        let declaration = self.scopes[depth].variables[name].span.clone();
+        let functions: Vec<(usize, Span)> = self
+            .scopes
+            .iter()
+            .enumerate()
+            .skip(depth + 1)
+            .filter_map(|(position, scope)| Some((position, scope.function.clone()?)))
+            .collect();
+        // Closures created in a loop inside the variable's scope see it change between iterations
+        let in_loop = functions.iter().find(|(position, _)| {
+            self.scopes[depth + 1..*position]
+                .iter()
+                .any(|scope| scope.scope_type == ScopeType::Loop)
+        });
+        if let (Some((_, closure)), Some(declaration)) = (in_loop, &declaration) {
+            self.captures.capture_in_loop(LoopCapture {
+                name: name.to_owned(),
+                span: span.clone(),
+                declaration: declaration.clone(),
+                closure: closure.clone(),
+            });
+        }
+
+        let mut resolution = Resolution::Local(slot);
+        for (nesting, (_, function)) in functions.iter().enumerate() {
+            let index = self.resolutions.capture(function, resolution);
+            self.captures.capture(
+                function,
+                index,
+                Capture {
+                    name: name.to_owned(),
+                    declaration: declaration.clone(),
+                    mutable: false,
+                    transitive: nesting > 0,
+                },
+            );
+            resolution = Resolution::Upvalue {
+                index,
+                is_ref: nesting > 0,
+            };
+        }
+
+        resolution
     }
 
     fn leave_scope(&mut self) {
-        self.scopes.pop();
+        let scope = self.scopes.pop().unwrap();
+        self.left_scopes.push(scope.symbols());
+
+        let mut unused: Vec<(ProgramText, Variable)> = scope
+            .variables
+            .into_iter()
+            .filter(|(name, var)| !var.used && !is_silenced(name))
+            .collect();
+        unused.sort_by_key(|(_, var)| var.span.as_ref().map(|span| span.start));
+
+        for (name, var) in unused {
+            let cause = match var.kind {
+                VariableKind::Variable | VariableKind::Class => WarningCause::UnusedVariable(name),
+                VariableKind::Parameter => WarningCause::UnusedParameter(name),
+                VariableKind::Closure | VariableKind::Function => WarningCause::UnusedClosure(name),
+                VariableKind::Builtin | VariableKind::Reserved => continue,
+            };
+            if let Some(span) = &var.span {
+                self.warning(span, cause);
+            }
+        }
     }
 
     fn current_scope(&self) -> &Scope {
@@ -108,167 +497,423 @@ impl Analyzer {
         self.scopes.last_mut().unwrap()
     }
 
-    fn visit_expr(&mut self, expr: &Expr) -> AnalyzerResult<ParseError> {
+    // Closures don't let break and continue reach the loop they were declared in
+    fn in_loop(&self) -> bool {
+        for scope in self.scopes.iter().rev() {
+            match scope.scope_type {
+                ScopeType::Loop => return true,
+                ScopeType::Block => continue,
+                ScopeType::Function | ScopeType::Global | ScopeType::Class { .. } => return false,
+            }
+        }
+
+        false
+    }
+
+    // Methods and everything declared inside of them
+    fn current_class(&self) -> Option<ScopeType> {
+        self.scopes
+            .iter()
+            .rev()
+            .map(|scope| scope.scope_type)
+            .find(|scope_type| matches!(scope_type, ScopeType::Class { .. }))
+    }
+
+    fn is_class(&self, name: &str) -> bool {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.variables.get(name))
+            .is_some_and(|var| var.kind == VariableKind::Class)
+    }
+
+    fn in_function(&self) -> bool {
+        self.scopes
+            .iter()
+            .any(|scope| scope.scope_type == ScopeType::Function)
+    }
+
+    fn warning(&mut self, span: &Span, cause: WarningCause) {
+        self.warnings.push(Warning {
+            span: span.clone(),
+            level: cause.lint().default_level,
+            cause,
+        });
+    }
+
+    // TODO: just making it work. It probably should differentiate between the start and end span.
+    fn error(&mut self, span: &Span, cause: ParseErrorCause) {
+        self.errors.push(ParseError {
+            span_start: span.clone(),
+            span_end: span.clone(),
+            cause,
+        });
+    }
+
+    fn visit_expr(&mut self, expr: &Expr) {
         use ExprKind::*;
-        let span = expr.span.clone();
-
-        // TODO: just making it work. It probably should differentiate between the start and end span.
-        let err = move |cause: ParseErrorCause| {
-            Err(ParseError {
-                span_end: span.clone(),
-                span_start: span.clone(),
-                cause,
-            })
-        };
+        let span = &expr.span;
 
         match &*expr.kind {
-            Atom(AtomicValue::Identifier { name, .. }) => match self.find_var(name) {
-                Some(false) => {
-                    return err(ParseErrorCause::UsedBeforeInitialization);
+            Atom(AtomicValue::Identifier { name, .. }) if name == SUPER => {
+                match self.current_class() {
+                    Some(ScopeType::Class { inherits: true }) => {}
+                    Some(_) => self.error(span, ParseErrorCause::SuperWithoutSuperclass),
+                    None => self.error(span, ParseErrorCause::UsedOutsideClass),
                 }
-                Some(true) => {}
-                None => {
-                    return err(ParseErrorCause::NotDefined);
+            }
+            Atom(AtomicValue::Identifier { name, .. })
+                if name == THIS && self.current_class().is_none() =>
+            {
+                self.error(span, ParseErrorCause::UsedOutsideClass);
+                // Every function reserves a slot for it, which is where it resolves to
+                self.resolve(name, span, false);
+            }
+            Atom(AtomicValue::Identifier {
+                name,
+                is_assignment,
+            }) => {
+                match self.find_var(name) {
+                    Some(var) => {
+                        // Assigning a new value doesn't count as using the variable
+                        if !is_assignment {
+                            var.used = true;
+                        } else {
+                            var.signature = None;
+                        }
+                        if !var.initialized {
+                            self.error(span, ParseErrorCause::UsedBeforeInitialization);
+                        }
+                    }
+                    None => {
+                        self.error(span, ParseErrorCause::NotDefined);
+                    }
                 }
-            },
+                self.resolve(name, span, *is_assignment);
+            }
             Binary { lhs, rhs, .. } => {
-                self.visit_expr(lhs)?;
-                self.visit_expr(rhs)?;
+                self.visit_expr(lhs);
+                self.visit_expr(rhs);
             }
             Block { stmts, return_expr } => {
+                self.enter_scope(ScopeType::Block, span);
+                self.check_inheritance(stmts);
                 for stmt in stmts {
-                    self.visit_stmt(stmt)?;
+                    self.visit_stmt(stmt);
                 }
 
                 if let Some(expr) = return_expr {
-                    self.visit_expr(expr)?;
+                    self.visit_expr(expr);
                 }
+                self.leave_scope();
             }
             While { condition, body } => {
-                self.visit_expr(condition)?;
-                self.enter_scope(ScopeType::Loop);
-                self.visit_expr(body)?;
+                self.visit_expr(condition);
+                self.enter_scope(ScopeType::Loop, &body.span);
+                self.visit_expr(body);
                 self.leave_scope();
             }
-            Continue => {
-                if !self.current_scope().is_loop() {
-                    return err(ParseErrorCause::UsedOutsideLoop);
-                }
+            Continue if !self.in_loop() => {
+                self.error(span, ParseErrorCause::UsedOutsideLoop);
             }
             Break { return_expr } => {
-                if !self.current_scope().is_loop() {
-                    return err(ParseErrorCause::UsedOutsideLoop);
+                if !self.in_loop() {
+                    self.error(span, ParseErrorCause::UsedOutsideLoop);
                 }
 
                 if let Some(expr) = return_expr {
-                    self.visit_expr(expr)?;
+                    self.visit_expr(expr);
                 }
             }
             Return { value } => {
-                if !self.current_scope().is_function() {
-                    return err(ParseErrorCause::ReturnUsedOutsideFunction);
+                if !self.in_function() {
+                    self.error(span, ParseErrorCause::ReturnUsedOutsideFunction);
                 }
                 if let Some(value) = value {
-                    self.visit_expr(value)?;
+                    self.visit_expr(value);
                 }
             }
             Call { callee, args } => {
-                self.visit_expr(callee)?;
+                self.record_call(callee, args.len(), span);
+                self.visit_expr(callee);
                 for arg in args {
-                    self.visit_expr(arg)?;
+                    self.visit_expr(arg);
                 }
             }
-            Unary { op, rhs } => {
-                self.visit_expr(rhs)?;
+            Unary { op: _, rhs } => {
+                self.visit_expr(rhs);
             }
             If {
                 condition,
                 body,
                 else_expr,
             } => {
-                self.visit_expr(condition)?;
-                self.visit_expr(body)?;
+                self.visit_expr(condition);
+                self.visit_expr(body);
                 if let Some(else_expr) = else_expr {
-                    self.visit_expr(else_expr)?;
+                    self.visit_expr(else_expr);
                 }
             }
             Array { values } => {
                 for value in values {
-                    self.visit_expr(value)?;
+                    self.visit_expr(value);
                 }
             }
             Index { target, position } => {
-                self.visit_expr(target)?;
-                self.visit_expr(position)?;
+                self.visit_expr(target);
+                self.visit_expr(position);
             }
             GetProperty {
                 target,
-                is_method_call,
-                identifier,
+                is_method_call: _,
+                identifier: _,
             } => {
-                self.visit_expr(target)?;
+                self.visit_expr(target);
             }
             SetProperty {
                 target,
                 value,
-                identifier,
+                identifier: _,
             } => {
-                self.visit_expr(target)?;
-                self.visit_expr(value)?;
+                self.visit_expr(target);
+                self.visit_expr(value);
             }
             ObjectLiteral { properties } => {
-                for (name, value) in properties {
-                    self.visit_expr(value)?;
+                for (_name, value) in properties {
+                    self.visit_expr(value);
                 }
             }
             Assignment { target, value } => {
-                self.visit_expr(target)?;
-                self.visit_expr(value)?;
+                self.visit_expr(target);
+                self.visit_expr(value);
             }
             Closure { params, body } => {
-                self.enter_scope(ScopeType::Function);
-                self.visit_expr(body)?;
-                self.leave_scope();
+                self.visit_function(None, span, params, body);
             }
             _ => {}
         }
-        Ok(())
     }
 
-    fn visit_stmt(&mut self, stmt: &Stmt) -> AnalyzerResult<ParseError> {
+    fn visit_stmt(&mut self, stmt: &Stmt) {
         use StmtKind::*;
 
         match &*stmt.kind {
             VariableDeclaration { name, expr } => {
-                self.declare_var(name, false);
-                self.visit_expr(expr)?;
-                self.declare_var(name, true);
+                let kind = match &*expr.kind {
+                    ExprKind::Closure { .. } => VariableKind::Closure,
+                    _ => VariableKind::Variable,
+                };
+                self.declare_var(name, kind, &stmt.span, false);
+                self.visit_expr(expr);
+                self.initialize_var(name);
+                if let ExprKind::Closure { params, .. } = &*expr.kind {
+                    self.declare_signature(name, params);
+                    self.captures.name_closure(&expr.span, name);
+                }
+            }
+            FunctionDeclaration {
+                body, name, params, ..
+            } => {
+                self.declare_var(name, VariableKind::Function, &stmt.span, true);
+                self.allocate_slot(name);
+                self.declare_signature(name, params);
+                self.visit_function(Some(name), &stmt.span, params, body);
             }
+            ClassDeclaration {
+                name,
+                super_class,
+                methods,
+            } => {
+                self.declare_var(name, VariableKind::Class, &stmt.span, true);
+                self.allocate_slot(name);
+                if let Some(var) = super_class.as_ref().and_then(|name| self.find_var(name)) {
+                    var.used = true;
+                }
 
-            FunctionDeclaration { body, name, .. } => {
-                self.declare_var(name, true);
-                self.enter_scope(ScopeType::Function);
-                self.visit_expr(body)?;
+                self.enter_scope(
+                    ScopeType::Class {
+                        inherits: super_class.is_some(),
+                    },
+                    &stmt.span,
+                );
+                for method in methods {
+                    if let FunctionDeclaration {
+                        name, params, body, ..
+                    } = &*method.kind
+                    {
+                        self.visit_function(Some(name), &method.span, params, body);
+                    }
+                }
                 self.leave_scope();
             }
             Expression { expr } => {
-                self.visit_expr(expr)?;
+                self.visit_expr(expr);
             }
         }
-        Ok(())
     }
 
-    pub fn analyze(&mut self, ast: AstRef) -> AnalyzerResult<Vec<ParseError>> {
-        let mut errors: Vec<ParseError> = Vec::new();
+    // Classes can inherit from the ones declared later in the same block,
+    // so the hierarchy is checked before any of them is visited
+    fn check_inheritance(&mut self, stmts: &[Stmt]) {
+        let classes: Vec<(&ProgramText, &ProgramText, &Span)> = stmts
+            .iter()
+            .filter_map(|stmt| match &*stmt.kind {
+                StmtKind::ClassDeclaration {
+                    name,
+                    super_class: Some(super_class),
+                    ..
+                } => Some((name, super_class, &stmt.span)),
+                _ => None,
+            })
+            .collect();
+        let superclasses: HashMap<&ProgramText, &ProgramText> = classes
+            .iter()
+            .map(|(name, super_class, _)| (*name, *super_class))
+            .collect();
+        let declared: HashSet<&ProgramText> = stmts
+            .iter()
+            .filter_map(|stmt| match &*stmt.kind {
+                StmtKind::ClassDeclaration { name, .. } => Some(name),
+                _ => None,
+            })
+            .collect();
+        // Every cycle is reported once, at the first class that's part of it
+        let mut reported: HashSet<&ProgramText> = HashSet::new();
 
-        for stmt in ast {
-            if let Err(e) = self.visit_stmt(stmt) {
-                errors.push(e);
+        for (name, super_class, span) in classes {
+            if name == super_class {
+                self.error(span, ParseErrorCause::CantInheritFromItself);
+                continue;
+            }
+            if !declared.contains(super_class) && !self.is_class(super_class) {
+                self.error(span, ParseErrorCause::SuperclassDoesntExist);
+                continue;
+            }
+
+            let mut cycle = vec![name];
+            let mut current = super_class;
+            while let Some(next) = superclasses.get(current) {
+                if cycle.contains(&current) {
+                    break;
+                }
+                cycle.push(current);
+                current = next;
+            }
+
+            if current == name && !reported.contains(name) {
+                reported.extend(cycle.iter().copied());
+                self.error(
+                    span,
+                    ParseErrorCause::InheritanceCycle(cycle.into_iter().cloned().collect()),
+                );
             }
         }
+    }
 
-        if !errors.is_empty() {
-            Err(errors)
+    // Only calls made directly by the name of a known function can be checked
+    fn record_call(&mut self, callee: &Expr, found: usize, span: &Span) {
+        let name = match &*callee.kind {
+            ExprKind::Atom(AtomicValue::Identifier { name, .. }) => name,
+            _ => return,
+        };
+        if let Some(signature) = self.find_var(name).and_then(|var| var.signature.clone()) {
+            self.calls.push(Call {
+                signature,
+                found,
+                span: combine(&callee.span, span),
+            });
+        }
+    }
+
+    // Function may be called before something else is assigned to its variable,
+    // so only variables that always hold it have their calls checked
+    fn check_arity(&mut self) {
+        for call in std::mem::take(&mut self.calls) {
+            let Call {
+                signature,
+                found,
+                span,
+            } = call;
+            let reassigned = signature
+                .declaration
+                .as_ref()
+                .is_some_and(|declaration| self.resolutions.is_reassigned(declaration));
+
+            if !reassigned && signature.arity != found {
+                self.error(
+                    &span,
+                    ParseErrorCause::WrongArity {
+                        expected: signature.arity,
+                        found,
+                        definition: signature.span,
+                    },
+                );
+            }
+        }
+    }
+
+    fn check_metrics(&mut self, ast: AstRef) {
+        for function in measure(ast) {
+            for (metric, value, limit) in self.thresholds.exceeded(&function) {
+                self.warning(
+                    &function.span,
+                    WarningCause::MetricExceeded {
+                        function: function.name.clone(),
+                        metric,
+                        value,
+                        limit,
+                    },
+                );
+            }
+        }
+    }
+
+    fn visit_function(&mut self, name: Option<&str>, span: &Span, params: &Params, body: &Expr) {
+        self.scopes.push(Scope::function(span));
+        self.captures.enter_function(span, name);
+        for param in &params.kind {
+            self.declare_var(&param.kind, VariableKind::Parameter, &param.span, true);
+            self.allocate_slot(&param.kind);
+        }
+        // Calls put the function itself and `this` right behind the arguments
+        self.reserve_slot(name);
+        self.reserve_slot(Some(THIS));
+        self.visit_expr(body);
+        self.leave_scope();
+    }
+
+    pub fn analyze(&mut self, ast: AstRef) -> AnalyzerResult<Vec<ParseError>> {
+        self.check_inheritance(ast);
+        for stmt in ast {
+            self.visit_stmt(stmt);
+        }
+        self.check_arity();
+        self.warnings.extend(ControlFlow::check(ast));
+        self.check_metrics(ast);
+        // Needs to know where every variable lives, so it runs once all of them are resolved
+        let (pure, violations) = Purity::check(ast, &self.resolutions);
+        self.resolutions.mark_pure(pure);
+        for (span, cause) in violations {
+            self.error(&span, cause);
+        }
+        let (types, mismatches) = Inference::infer(ast, &self.resolutions);
+        self.types = types;
+        for (span, cause) in mismatches {
+            self.warning(&span, cause);
+        }
+        for capture in self.captures.finish(&self.resolutions) {
+            self.warning(
+                &capture.span,
+                WarningCause::CapturedLoopVariable {
+                    name: capture.name,
+                    declaration: capture.declaration,
+                },
+            );
+        }
+        self.warnings.sort_by_key(|warning| warning.span.start);
+
+        if !self.errors.is_empty() {
+            Err(std::mem::take(&mut self.errors))
         } else {
             Ok(())
         }
@@ -277,7 +922,7 @@ impl Analyzer {
 
 pub fn analyze(ast: AstRef) -> AnalyzerResult<Vec<ParseError>> {
     let mut analyzer = Analyzer::new();
-    analyzer.analyze(&ast)?;
+    analyzer.analyze(ast)?;
     Ok(())
 }
 
@@ -293,17 +938,23 @@ mod test {
         assert_eq!(analyze(&ast).unwrap_err()[0].cause, cause);
     }
 
+    fn assert_errors(code: &str, causes: Vec<ParseErrorCause>) {
+        let ast = parse(code).unwrap();
+        let errors: Vec<ParseErrorCause> = analyze(&ast)
+            .unwrap_err()
+            .into_iter()
+            .map(|error| error.cause)
+            .collect();
+        assert_eq!(errors, causes);
+    }
+
     #[test]
     fn errors() {
         use ParseErrorCause::*;
-        assert_err("super;", UsedOutsideClass);
-        assert_err("this;", UsedOutsideClass);
         assert_err("continue;", UsedOutsideLoop);
         assert_err("break;", UsedOutsideLoop);
         assert_err("let x = x + 1;", UsedBeforeInitialization);
         assert_err("x + 2;", NotDefined);
-        assert_err("class Foo: Foo {}", CantInheritFromItself);
-        assert_err("class Foo: DoesntExist {}", SuperclassDoesntExist);
 
         // evaluates errors inside blocks
         assert_err("{ continue; };", UsedOutsideLoop);
@@ -313,4 +964,366 @@ mod test {
         assert_err("fn foo() { continue; }", UsedOutsideLoop);
         assert_err("return;", ReturnUsedOutsideFunction);
     }
+
+    #[test]
+    fn collects_all_errors() {
+        use ParseErrorCause::*;
+        assert_errors(
+            "continue; x; let a = a;",
+            vec![UsedOutsideLoop, NotDefined, UsedBeforeInitialization],
+        );
+        // keeps going inside of the expression that already failed
+        assert_errors(
+            "fn foo() { break x + y; }",
+            vec![UsedOutsideLoop, NotDefined, NotDefined],
+        );
+        assert_errors("while true { return; };", vec![ReturnUsedOutsideFunction]);
+    }
+
+    fn assert_warnings(code: &str, causes: Vec<WarningCause>) {
+        let ast = parse(code).unwrap();
+        let mut analyzer = Analyzer::new();
+        analyzer.analyze(&ast).unwrap();
+        let warnings: Vec<WarningCause> = analyzer
+            .warnings()
+            .iter()
+            .map(|warning| warning.cause.clone())
+            .collect();
+        assert_eq!(warnings, causes);
+    }
+
+    #[test]
+    fn resolves_scopes() {
+        assert!(analyze(&parse("fn foo(a) { a }").unwrap()).is_ok());
+        assert!(analyze(&parse("fn foo() { while true { return 1; }; }").unwrap()).is_ok());
+        assert!(analyze(&parse("while true { if true { break; }; };").unwrap()).is_ok());
+        assert_err("{ let a = 1; }; a;", ParseErrorCause::NotDefined);
+        assert_err(
+            "while true { || => { break; }; };",
+            ParseErrorCause::UsedOutsideLoop,
+        );
+    }
+
+    #[test]
+    fn unused_warnings() {
+        use WarningCause::*;
+        let name = |name: &str| name.to_owned();
+
+        assert_warnings(
+            "fn foo(a, b) { let c = 1; let d = 2; d + b }",
+            vec![UnusedParameter(name("a")), UnusedVariable(name("c"))],
+        );
+        // assignment alone doesn't use the variable
+        assert_warnings(
+            "fn foo() { let a = 1; a = 2; }",
+            vec![UnusedVariable(name("a"))],
+        );
+        assert_warnings(
+            "fn foo() { let f = |x| => x; fn g() {} let h = || => 1; h(); }",
+            vec![UnusedClosure(name("f")), UnusedClosure(name("g"))],
+        );
+        assert_warnings("let f = |a, _b| => { let _c = 1; a };", vec![]);
+        // globals and class methods are not reported
+        assert_warnings("let a = 1; fn foo() {} class Foo { fn bar() {} }", vec![]);
+    }
+
+    #[test]
+    fn shadowing_warnings() {
+        use WarningCause::*;
+
+        assert_warnings(
+            "let a = 1; fn foo(a) { a }",
+            vec![ShadowedVariable {
+                name: "a".to_owned(),
+                shadowed: Some(0..10),
+            }],
+        );
+        assert_warnings(
+            "fn foo() { let print = 1; print }",
+            vec![ShadowedVariable {
+                name: "print".to_owned(),
+                shadowed: None,
+            }],
+        );
+        assert_warnings("let a = 1; fn foo(_a) { let _a = 2; _a }", vec![]);
+    }
+
+    #[test]
+    fn redeclarations() {
+        use ParseErrorCause::*;
+        let name = |name: &str| name.to_owned();
+
+        assert_errors(
+            "fn foo(a, a) { a }",
+            vec![DuplicateParameter {
+                name: name("a"),
+                previous: 7..8,
+            }],
+        );
+        assert_errors(
+            "fn foo() {} fn foo() {}",
+            vec![FunctionRedeclared {
+                name: name("foo"),
+                previous: 0..11,
+            }],
+        );
+        assert_errors(
+            "fn bar() { let foo = 1; fn foo() {} foo() }",
+            vec![FunctionRedeclared {
+                name: name("foo"),
+                previous: 11..23,
+            }],
+        );
+
+        assert_warnings(
+            "fn foo() { let a = 1; let a = 2; a }",
+            vec![WarningCause::RedeclaredVariable {
+                name: name("a"),
+                previous: 11..21,
+            }],
+        );
+        // builtins and silenced variables can be declared again
+        assert_warnings("let print = 1; let _a = 1; let _a = 2;", vec![]);
+    }
+
+    #[test]
+    fn arity_errors() {
+        let wrong_arity = |expected, found, definition| ParseErrorCause::WrongArity {
+            expected,
+            found,
+            definition,
+        };
+
+        assert_errors("print();", vec![wrong_arity(1, 0, None)]);
+        assert_errors("clock(1);", vec![wrong_arity(0, 1, None)]);
+        assert_errors(
+            "fn foo(a, b) { a + b } foo(1);",
+            vec![wrong_arity(2, 1, Some(6..12))],
+        );
+        assert_errors(
+            "let f = |a| => a; f(1, 2);",
+            vec![wrong_arity(1, 2, Some(8..11))],
+        );
+        assert!(analyze(&parse("fn foo(a) { a } foo(1); print(foo(2));").unwrap()).is_ok());
+        // the signature is unknown after assignment
+        assert!(analyze(&parse("let f = |a| => a; f = clock; f();").unwrap()).is_ok());
+        // even for calls that come before the assignment
+        assert!(analyze(&parse("let f = |a| => a; fn g() { f(); } f = clock;").unwrap()).is_ok());
+        assert_errors(
+            "let f = |a| => a; fn g() { f(); } let h = f;",
+            vec![wrong_arity(1, 0, Some(8..11))],
+        );
+        // recursive calls check the signature of the function itself
+        assert_errors("fn foo(a) { foo() }", vec![wrong_arity(1, 0, Some(6..9))]);
+
+        let ast = parse("fn foo(a) { a } foo();").unwrap();
+        assert_eq!(analyze(&ast).unwrap_err()[0].span_start, 16..21);
+    }
+
+    #[test]
+    fn class_errors() {
+        use ParseErrorCause::*;
+        assert_err("super;", UsedOutsideClass);
+        assert_err("this;", UsedOutsideClass);
+        assert_err("class Foo: Foo {}", CantInheritFromItself);
+        assert_err("class Foo: DoesntExist {}", SuperclassDoesntExist);
+    }
+
+    #[test]
+    fn this_and_super() {
+        use ParseErrorCause::*;
+        let analyzes = |code: &str| analyze(&parse(code).unwrap()).is_ok();
+
+        assert!(analyzes(
+            "class Foo { fn foo() { this } } class Bar: Foo { fn bar() { super.foo(); this } }"
+        ));
+        // closures inside methods still belong to the class
+        assert!(analyzes("class Foo { fn foo() { || => this; } }"));
+        assert_err(
+            "class Foo { fn foo() { super.foo() } }",
+            SuperWithoutSuperclass,
+        );
+        assert_err("fn foo() { this }", UsedOutsideClass);
+        assert_err("let a = 1; class Foo: a {}", SuperclassDoesntExist);
+    }
+
+    #[test]
+    fn inheritance_cycles() {
+        use ParseErrorCause::*;
+        let names = |names: &[&str]| names.iter().map(|name| name.to_string()).collect();
+
+        assert_errors(
+            "class A: C {} class B: A {} class C: B {}",
+            vec![InheritanceCycle(names(&["A", "C", "B"]))],
+        );
+        assert_errors(
+            "{ class A: B {} class B: A {} class C: A {} };",
+            vec![InheritanceCycle(names(&["A", "B"]))],
+        );
+        // superclass declared later in the same block
+        assert!(analyze(&parse("class B: A {} class A {}").unwrap()).is_ok());
+    }
+
+    #[test]
+    fn reports_captures() {
+        let ast = parse(
+            "let a = 1; let b = 2; b = 3; fn outer() { let c = || => a + b; fn inner() { a } c() + inner() }",
+        )
+        .unwrap();
+        let mut analyzer = Analyzer::new();
+        analyzer.analyze(&ast).unwrap();
+
+        let captures: Vec<_> = analyzer
+            .captures()
+            .iter()
+            .map(|closure| {
+                let captures = closure
+                    .captures
+                    .iter()
+                    .map(|capture| (capture.name.as_str(), capture.mutable, capture.transitive))
+                    .collect::<Vec<_>>();
+                (closure.name(), captures)
+            })
+            .collect();
+        assert_eq!(
+            captures,
+            vec![
+                ("outer", vec![("a", false, false), ("b", true, false)]),
+                ("c", vec![("a", false, true), ("b", true, true)]),
+                ("inner", vec![("a", false, true)]),
+            ]
+        );
+        assert_eq!(
+            analyzer.captures()[1].to_string(),
+            "c captures:\n- a by value (through the enclosing closure)\n- b by reference (through the enclosing closure)"
+        );
+    }
+
+    #[test]
+    fn warns_about_captured_loop_variables() {
+        let name = |name: &str| name.to_owned();
+        assert_warnings(
+            "let i = 0; let f = 0; while i < 3 { let j = i; f = || => i + j; i = i + 1; }; f;",
+            vec![WarningCause::CapturedLoopVariable {
+                name: name("i"),
+                declaration: 0..10,
+            }],
+        );
+        // nothing changes between iterations
+        assert_warnings(
+            "let n = 3; while true { let f = || => n; f(); break; };",
+            vec![],
+        );
+        // loop inside of the closure runs every time it's called
+        assert_warnings(
+            "let i = 0; let f = || => { while i < 3 { i = i + 1; }; i }; f;",
+            vec![],
+        );
+    }
+
+    #[test]
+    fn infers_purity() {
+        let ast = parse(
+            "fn add(a, b) => a + b fn twice(x) => add(x, x) fn noisy() { print(1); } \
+             fn calls_noisy() => noisy() let count = 0; fn bump() { count = count + 1; } \
+             fn fib(n) => if n < 2 { n } else { fib(n - 1) + fib(n - 2) } fn apply(f) => f(1) \
+             let square = |a| => a * a; fn squares(a) => square(a) + (|| => 2)() \
+             fn set(array) { array[0] = 1; }",
+        )
+        .unwrap();
+        let mut analyzer = Analyzer::new();
+        analyzer.analyze(&ast).unwrap();
+
+        let pure: Vec<&str> = ast
+            .iter()
+            .filter_map(|stmt| match &*stmt.kind {
+                StmtKind::FunctionDeclaration { name, .. }
+                    if analyzer.resolutions().is_pure(&stmt.span) =>
+                {
+                    Some(name.as_str())
+                }
+                _ => None,
+            })
+            .collect();
+        assert_eq!(pure, vec!["add", "twice", "fib", "squares"]);
+    }
+
+    #[test]
+    fn checks_pure_annotations() {
+        use parser::utils::error::SideEffect::*;
+        let violation = |function: &str, effect, annotation| ParseErrorCause::PurityViolation {
+            function: function.to_owned(),
+            effect,
+            annotation,
+        };
+
+        assert!(analyze(&parse("@pure fn add(a, b) => a + b").unwrap()).is_ok());
+        assert_errors(
+            "let a = 0; @pure fn foo() { a = 1; print(a); }",
+            vec![
+                violation("foo", MutatesCapture("a".to_owned()), 11..16),
+                violation("foo", CallsNative("print".to_owned()), 11..16),
+            ],
+        );
+        // impurity spreads through every function in between
+        assert_errors(
+            "fn baz() { print(1); } fn bar() => baz() @pure fn foo() => bar()",
+            vec![violation("foo", CallsImpure("bar".to_owned()), 41..46)],
+        );
+        assert_errors(
+            "@pure fn foo(f) => f() class Foo { @pure fn set() { this.a = 1; } }",
+            vec![
+                violation("foo", CallsUnknown, 0..5),
+                violation("set", SetsProperty, 35..40),
+            ],
+        );
+    }
+
+    #[test]
+    fn warns_about_exceeded_metrics() {
+        let ast = parse("fn foo(a, b) => a and b fn bar() => 1").unwrap();
+        let mut analyzer = Analyzer::with_thresholds(Thresholds {
+            complexity: Some(1),
+            ..Thresholds::default()
+        });
+        analyzer.analyze(&ast).unwrap();
+
+        let warnings: Vec<&WarningCause> = analyzer
+            .warnings()
+            .iter()
+            .map(|warning| &warning.cause)
+            .collect();
+        assert_eq!(
+            warnings,
+            vec![&WarningCause::MetricExceeded {
+                function: "foo".to_owned(),
+                metric: metrics::Metric::Complexity,
+                value: 2,
+                limit: 1,
+            }]
+        );
+    }
+
+    #[test]
+    fn keeps_scopes() {
+        let code = "let a = 1; fn foo(b) { let c = 2; let a = c; a } let d = 3;";
+        let ast = parse(code).unwrap();
+        let mut analyzer = Analyzer::new();
+        analyzer.analyze(&ast).unwrap();
+
+        let scopes = analyzer.scopes();
+        let visible = |offset| -> Vec<&str> {
+            symbols::visible_at(&scopes, offset)
+                .iter()
+                .map(|symbol| symbol.name.as_str())
+                .collect()
+        };
+
+        // right before `let a = c;` inside of foo
+        assert_eq!(
+            visible(code.find("let a = c").unwrap()),
+            vec!["c", "b", "a", "clock", "foo", "print"]
+        );
+        assert_eq!(visible(code.len()), vec!["a", "clock", "d", "foo", "print"]);
+    }
 }
@@ -0,0 +1,488 @@
+use codespan_reporting::diagnostic::Diagnostic;
+use common::CompilerDiagnostic;
+use serde::Deserialize;
+use std::{collections::HashMap, fmt};
+
+use crate::{metrics::Thresholds, warning::Warning};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LintLevel {
+    Allow,
+    Warn,
+    Deny,
+}
+
+impl LintLevel {
+    fn from_str(level: &str) -> Option<Self> {
+        Some(match level {
+            "allow" => LintLevel::Allow,
+            "warn" => LintLevel::Warn,
+            "deny" => LintLevel::Deny,
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Lint {
+    pub id: &'static str,
+    pub default_level: LintLevel,
+    pub description: &'static str,
+}
+
+pub static UNUSED_VARIABLE: Lint = Lint {
+    id: "unused_variable",
+    default_level: LintLevel::Warn,
+    description: "local variable is never read",
+};
+
+pub static UNUSED_PARAMETER: Lint = Lint {
+    id: "unused_parameter",
+    default_level: LintLevel::Warn,
+    description: "function parameter is never read",
+};
+
+pub static UNUSED_CLOSURE: Lint = Lint {
+    id: "unused_closure",
+    default_level: LintLevel::Warn,
+    description: "local function or closure is never called",
+};
+
+pub static SHADOWED_VARIABLE: Lint = Lint {
+    id: "shadowed_variable",
+    default_level: LintLevel::Warn,
+    description: "declaration hides a variable from an outer scope",
+};
+
+pub static REDECLARED_VARIABLE: Lint = Lint {
+    id: "redeclared_variable",
+    default_level: LintLevel::Warn,
+    description: "variable is declared again in the same scope",
+};
+
+pub static UNREACHABLE_CODE: Lint = Lint {
+    id: "unreachable_code",
+    default_level: LintLevel::Warn,
+    description: "code placed after return, break or continue never runs",
+};
+
+pub static MISSING_RETURN: Lint = Lint {
+    id: "missing_return",
+    default_level: LintLevel::Warn,
+    description: "function returns a value only on some of its paths",
+};
+
+pub static CAPTURED_LOOP_VARIABLE: Lint = Lint {
+    id: "captured_loop_variable",
+    default_level: LintLevel::Warn,
+    description: "closure created in a loop captures a variable the loop keeps changing",
+};
+
+pub static METRIC_THRESHOLD: Lint = Lint {
+    id: "metric_threshold",
+    default_level: LintLevel::Warn,
+    description: "function metric is above the limit set in the configuration",
+};
+
+pub static TYPE_MISMATCH: Lint = Lint {
+    id: "type_mismatch",
+    default_level: LintLevel::Deny,
+    description: "operation is given a value of a type it fails on at runtime",
+};
+
+pub static LINTS: &[&Lint] = &[
+    &UNUSED_VARIABLE,
+    &UNUSED_PARAMETER,
+    &UNUSED_CLOSURE,
+    &SHADOWED_VARIABLE,
+    &REDECLARED_VARIABLE,
+    &UNREACHABLE_CODE,
+    &MISSING_RETURN,
+    &CAPTURED_LOOP_VARIABLE,
+    &METRIC_THRESHOLD,
+    &TYPE_MISMATCH,
+];
+
+pub fn find_lint(id: &str) -> Option<&'static Lint> {
+    LINTS.iter().copied().find(|lint| lint.id == id)
+}
+
+#[derive(Debug, PartialEq)]
+pub enum LintConfigError {
+    // Configuration file exists, but can't be read
+    Unreadable(String),
+    Malformed(String),
+    UnknownLint(String),
+}
+
+impl fmt::Display for LintConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LintConfigError::Unreadable(reason) | LintConfigError::Malformed(reason) => {
+                write!(f, "{}", reason)
+            }
+            LintConfigError::UnknownLint(id) => write!(f, "unknown lint '{}'", id),
+        }
+    }
+}
+
+impl CompilerDiagnostic for LintConfigError {
+    fn report(&self, _file_id: usize) -> Diagnostic<usize> {
+        let message = match self {
+            LintConfigError::Unreadable(reason) => {
+                format!("Couldn't read the lint configuration: {}", reason)
+            }
+            error => format!("Invalid lint configuration: {}", error),
+        };
+
+        Diagnostic::error().with_message(message)
+    }
+}
+
+// gravitas.toml
+// [lints]
+// unused_variable = "allow"
+// shadowed_variable = "deny"
+//
+// [metrics]
+// complexity = 10
+#[derive(Deserialize)]
+struct ConfigFile {
+    #[serde(default)]
+    lints: HashMap<String, LintLevel>,
+    #[serde(default)]
+    metrics: Thresholds,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct LintConfig {
+    levels: HashMap<&'static str, LintLevel>,
+    deny_warnings: bool,
+    thresholds: Thresholds,
+}
+
+impl LintConfig {
+    pub fn from_toml(config: &str) -> Result<Self, LintConfigError> {
+        let file: ConfigFile =
+            toml::from_str(config).map_err(|err| LintConfigError::Malformed(err.to_string()))?;
+
+        let mut lint_config = Self {
+            thresholds: file.metrics,
+            ..Self::default()
+        };
+        for (id, level) in file.lints {
+            lint_config.set_level(&id, level)?;
+        }
+
+        Ok(lint_config)
+    }
+
+    pub fn set_level(&mut self, id: &str, level: LintLevel) -> Result<(), LintConfigError> {
+        let lint = find_lint(id).ok_or_else(|| LintConfigError::UnknownLint(id.to_owned()))?;
+        self.levels.insert(lint.id, level);
+        Ok(())
+    }
+
+    pub fn thresholds(&self) -> &Thresholds {
+        &self.thresholds
+    }
+
+    // Every lint that would only warn fails the compilation instead
+    pub fn deny_warnings(&mut self) {
+        self.deny_warnings = true;
+    }
+
+    pub fn level(&self, lint: &Lint) -> LintLevel {
+        self.levels
+            .get(lint.id)
+            .copied()
+            .unwrap_or(lint.default_level)
+    }
+
+    // Resolves the final level of every warning, dropping the allowed ones.
+    // Comment directives in the source take precedence over the configuration.
+    pub fn apply(&self, warnings: &[Warning], source: &str) -> Vec<Warning> {
+        let directives = Directives::parse(source);
+
+        warnings
+            .iter()
+            .filter_map(|warning| {
+                let lint = warning.cause.lint();
+                let line = source[..warning.span.start].matches('\n').count();
+
+                let level = match directives.level(lint, line) {
+                    Some(level) => level,
+                    None => self.level(lint),
+                };
+                let level = match level {
+                    LintLevel::Warn if self.deny_warnings => LintLevel::Deny,
+                    level => level,
+                };
+
+                match level {
+                    LintLevel::Allow => None,
+                    level => Some(Warning {
+                        level,
+                        ..warning.clone()
+                    }),
+                }
+            })
+            .collect()
+    }
+}
+
+const DIRECTIVE: &str = "gravitas:";
+
+// Start of the line's comment, `//` inside of strings doesn't start one.
+// Strings can span many lines, so whether the line starts inside of one is carried over.
+fn comment_start(line: &str, in_string: &mut bool) -> Option<usize> {
+    let mut chars = line.char_indices().peekable();
+
+    while let Some((index, char)) = chars.next() {
+        match char {
+            '"' => *in_string = !*in_string,
+            // Escaped quotes don't end the string
+            '\\' if *in_string => {
+                chars.next();
+            }
+            '/' if !*in_string && matches!(chars.peek(), Some((_, '/'))) => return Some(index),
+            _ => {}
+        }
+    }
+
+    None
+}
+
+// `//! gravitas: allow(unused_variable)` applies to the whole file,
+// `// gravitas: allow(unused_variable)` applies to the line it's written on
+// or to the following one, if the comment is the only thing in its line.
+#[derive(Debug, Default)]
+struct Directives {
+    file: HashMap<&'static str, LintLevel>,
+    lines: HashMap<(usize, &'static str), LintLevel>,
+}
+
+impl Directives {
+    fn parse(source: &str) -> Self {
+        let mut directives = Self::default();
+        let mut in_string = false;
+
+        for (line, text) in source.lines().enumerate() {
+            let comment_start = match comment_start(text, &mut in_string) {
+                Some(start) => start,
+                None => continue,
+            };
+            let code = &text[..comment_start];
+            let comment = &text[comment_start + 2..];
+
+            let (is_file, comment) = match comment.strip_prefix('!') {
+                Some(comment) => (true, comment),
+                None => (false, comment),
+            };
+            let directive = match comment.trim_start().strip_prefix(DIRECTIVE) {
+                Some(directive) => directive.trim(),
+                None => continue,
+            };
+
+            let target_line = if code.trim().is_empty() {
+                line + 1
+            } else {
+                line
+            };
+
+            for (level, lint) in Self::parse_directive(directive) {
+                if is_file {
+                    directives.file.insert(lint.id, level);
+                } else {
+                    directives.lines.insert((target_line, lint.id), level);
+                }
+            }
+        }
+
+        directives
+    }
+
+    // allow(unused_variable, unused_parameter)
+    fn parse_directive(directive: &str) -> Vec<(LintLevel, &'static Lint)> {
+        let open = match directive.find('(') {
+            Some(open) => open,
+            None => return vec![],
+        };
+        let close = match directive.rfind(')') {
+            Some(close) if close > open => close,
+            _ => return vec![],
+        };
+        let level = match LintLevel::from_str(directive[..open].trim()) {
+            Some(level) => level,
+            None => return vec![],
+        };
+
+        directive[open + 1..close]
+            .split(',')
+            .filter_map(|id| find_lint(id.trim()))
+            .map(|lint| (level, lint))
+            .collect()
+    }
+
+    fn level(&self, lint: &Lint, line: usize) -> Option<LintLevel> {
+        self.lines
+            .get(&(line, lint.id))
+            .or_else(|| self.file.get(lint.id))
+            .copied()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use parser::parse;
+
+    use super::*;
+    use crate::Analyzer;
+
+    fn lint(code: &str, config: &LintConfig) -> Vec<(&'static str, LintLevel)> {
+        let ast = parse(code).unwrap();
+        let mut analyzer = Analyzer::new();
+        analyzer.analyze(&ast).unwrap();
+
+        config
+            .apply(analyzer.warnings(), code)
+            .into_iter()
+            .map(|warning| (warning.cause.lint().id, warning.level))
+            .collect()
+    }
+
+    const CODE: &str = "fn foo(a) {\n    let b = 1;\n    0\n}";
+
+    #[test]
+    fn uses_default_levels() {
+        assert_eq!(
+            lint(CODE, &LintConfig::default()),
+            vec![
+                ("unused_parameter", LintLevel::Warn),
+                ("unused_variable", LintLevel::Warn)
+            ]
+        );
+    }
+
+    #[test]
+    fn reads_toml_config() {
+        let config = LintConfig::from_toml(
+            "[lints]\nunused_parameter = \"allow\"\nunused_variable = \"deny\"",
+        )
+        .unwrap();
+        assert_eq!(
+            lint(CODE, &config),
+            vec![("unused_variable", LintLevel::Deny)]
+        );
+
+        assert_eq!(
+            LintConfig::from_toml("[lints]\nfoo = \"allow\"").unwrap_err(),
+            LintConfigError::UnknownLint("foo".to_owned())
+        );
+        assert!(matches!(
+            LintConfig::from_toml("[lints]\nunused_variable = \"sometimes\""),
+            Err(LintConfigError::Malformed(_))
+        ));
+    }
+
+    #[test]
+    fn reports_config_errors() {
+        assert_eq!(
+            LintConfigError::UnknownLint("foo".to_owned())
+                .report(0)
+                .message,
+            "Invalid lint configuration: unknown lint 'foo'"
+        );
+        assert_eq!(
+            LintConfigError::Unreadable("permission denied".to_owned())
+                .report(0)
+                .message,
+            "Couldn't read the lint configuration: permission denied"
+        );
+    }
+
+    #[test]
+    fn reads_metric_thresholds() {
+        let config = LintConfig::from_toml("[metrics]\ncomplexity = 5\nparameters = 3").unwrap();
+        assert_eq!(
+            config.thresholds(),
+            &Thresholds {
+                complexity: Some(5),
+                parameters: Some(3),
+                ..Thresholds::default()
+            }
+        );
+        assert!(matches!(
+            LintConfig::from_toml("[metrics]\nlines = 5"),
+            Err(LintConfigError::Malformed(_))
+        ));
+    }
+
+    #[test]
+    fn escalates_warnings() {
+        let mut config = LintConfig::from_toml("[lints]\nunused_parameter = \"allow\"").unwrap();
+        config.deny_warnings();
+        assert_eq!(
+            lint(CODE, &config),
+            vec![("unused_variable", LintLevel::Deny)]
+        );
+    }
+
+    #[test]
+    fn honours_directives() {
+        let config = LintConfig::default();
+
+        // same line
+        let code = "fn foo(a) { // gravitas: allow(unused_parameter)\n    let b = 1;\n    0\n}";
+        assert_eq!(
+            lint(code, &config),
+            vec![("unused_variable", LintLevel::Warn)]
+        );
+
+        // next line, with many lints at once
+        let code =
+            "// gravitas: deny(unused_parameter, unused_variable)\nfn foo(a) {\n    let b = 1;\n    0\n}";
+        assert_eq!(
+            lint(code, &config),
+            vec![
+                ("unused_parameter", LintLevel::Deny),
+                ("unused_variable", LintLevel::Warn)
+            ]
+        );
+
+        // whole file, overridden by the line directive
+        let code = "//! gravitas: allow(unused_variable, unused_parameter)\nfn foo(a) {\n    let b = 1; // gravitas: warn(unused_variable)\n    0\n}";
+        assert_eq!(
+            lint(code, &config),
+            vec![("unused_variable", LintLevel::Warn)]
+        );
+    }
+
+    #[test]
+    fn finds_directives_after_strings() {
+        let config = LintConfig::default();
+        let unused = vec![("unused_variable", LintLevel::Warn)];
+
+        let code =
+            "fn foo() {\n    let z = \"http://x\"; // gravitas: allow(unused_variable)\n    0\n}";
+        assert_eq!(lint(code, &config), vec![]);
+        let code = "fn foo() {\n    let z = \"http://x\";\n    0\n}";
+        assert_eq!(lint(code, &config), unused);
+
+        let code =
+            "fn foo() {\n    let z = \"say \\\"//\\\"\"; // gravitas: allow(unused_variable)\n    0\n}";
+        assert_eq!(lint(code, &config), vec![]);
+
+        // Text inside of a string spanning many lines isn't a comment
+        let code =
+            "fn foo() {\n    let y = \"\n// gravitas: allow(unused_variable)\n\"; let z = 1;\n    0\n}";
+        assert_eq!(
+            lint(code, &config),
+            vec![
+                ("unused_variable", LintLevel::Warn),
+                ("unused_variable", LintLevel::Warn)
+            ]
+        );
+    }
+}
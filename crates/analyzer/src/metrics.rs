@@ -0,0 +1,350 @@
+use common::ProgramText;
+use parser::parse::{
+    expr::{Expr, ExprKind},
+    operator::BinaryOperator,
+    stmt::{Stmt, StmtKind},
+    AstRef, Span,
+};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+// Numbers describing a single function or method.
+// Closures are a part of the function they are declared in, nested functions are measured separately.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FunctionMetrics {
+    pub name: ProgramText,
+    pub span: Span,
+    // Decision points (if, while, and, or) plus one
+    pub complexity: usize,
+    // Deepest nesting of ifs and loops, else-if chains stay on the same level
+    pub nesting_depth: usize,
+    pub parameters: usize,
+    pub statements: usize,
+    pub closures: usize,
+    // Deepest nesting of blocks, the body of the function is the first one
+    pub block_depth: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Metric {
+    Complexity,
+    NestingDepth,
+    Parameters,
+    Statements,
+    Closures,
+    BlockDepth,
+}
+
+impl fmt::Display for Metric {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Metric::Complexity => "cyclomatic complexity",
+            Metric::NestingDepth => "nesting depth",
+            Metric::Parameters => "parameter count",
+            Metric::Statements => "statement count",
+            Metric::Closures => "closure count",
+            Metric::BlockDepth => "block depth",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FunctionMetrics {
+    fn new(name: &str, span: &Span, parameters: usize) -> Self {
+        Self {
+            name: name.to_owned(),
+            span: span.clone(),
+            complexity: 1,
+            nesting_depth: 0,
+            parameters,
+            statements: 0,
+            closures: 0,
+            block_depth: 0,
+        }
+    }
+
+    pub fn get(&self, metric: Metric) -> usize {
+        match metric {
+            Metric::Complexity => self.complexity,
+            Metric::NestingDepth => self.nesting_depth,
+            Metric::Parameters => self.parameters,
+            Metric::Statements => self.statements,
+            Metric::Closures => self.closures,
+            Metric::BlockDepth => self.block_depth,
+        }
+    }
+}
+
+// gravitas.toml
+// [metrics]
+// complexity = 10
+// parameters = 4
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Thresholds {
+    pub complexity: Option<usize>,
+    pub nesting_depth: Option<usize>,
+    pub parameters: Option<usize>,
+    pub statements: Option<usize>,
+    pub closures: Option<usize>,
+    pub block_depth: Option<usize>,
+}
+
+impl Thresholds {
+    pub fn limit(&self, metric: Metric) -> Option<usize> {
+        match metric {
+            Metric::Complexity => self.complexity,
+            Metric::NestingDepth => self.nesting_depth,
+            Metric::Parameters => self.parameters,
+            Metric::Statements => self.statements,
+            Metric::Closures => self.closures,
+            Metric::BlockDepth => self.block_depth,
+        }
+    }
+
+    // Every metric of the function that is above its limit, with the value and the limit
+    pub fn exceeded(&self, function: &FunctionMetrics) -> Vec<(Metric, usize, usize)> {
+        use Metric::*;
+
+        [
+            Complexity,
+            NestingDepth,
+            Parameters,
+            Statements,
+            Closures,
+            BlockDepth,
+        ]
+        .iter()
+        .filter_map(|&metric| {
+            let limit = self.limit(metric)?;
+            let value = function.get(metric);
+            (value > limit).then_some((metric, value, limit))
+        })
+        .collect()
+    }
+}
+
+// Measures every function and method of the program, in the order they start in
+pub fn measure(ast: AstRef) -> Vec<FunctionMetrics> {
+    let mut metrics = Metrics::default();
+    // Code outside of functions isn't reported
+    let mut main = FunctionMetrics::new("", &(0..0), 0);
+    for stmt in ast {
+        metrics.visit_stmt(stmt, &mut main, 0, 0);
+    }
+
+    metrics
+        .functions
+        .sort_by_key(|function| function.span.start);
+    metrics.functions
+}
+
+#[derive(Default)]
+struct Metrics {
+    functions: Vec<FunctionMetrics>,
+}
+
+impl Metrics {
+    fn visit_function(&mut self, name: &str, span: &Span, parameters: usize, body: &Expr) {
+        let mut function = FunctionMetrics::new(name, span, parameters);
+        self.visit_expr(body, &mut function, 0, 0);
+        self.functions.push(function);
+    }
+
+    fn visit_stmt(
+        &mut self,
+        stmt: &Stmt,
+        function: &mut FunctionMetrics,
+        nesting: usize,
+        blocks: usize,
+    ) {
+        use StmtKind::*;
+        function.statements += 1;
+
+        match &*stmt.kind {
+            Expression { expr } | VariableDeclaration { expr, .. } => {
+                self.visit_expr(expr, function, nesting, blocks);
+            }
+            FunctionDeclaration {
+                name, params, body, ..
+            } => {
+                self.visit_function(name, &stmt.span, params.kind.len(), body);
+            }
+            ClassDeclaration { methods, .. } => {
+                for method in methods {
+                    if let FunctionDeclaration {
+                        name, params, body, ..
+                    } = &*method.kind
+                    {
+                        self.visit_function(name, &method.span, params.kind.len(), body);
+                    }
+                }
+            }
+        }
+    }
+
+    fn visit_exprs<'a>(
+        &mut self,
+        exprs: impl IntoIterator<Item = &'a Expr>,
+        function: &mut FunctionMetrics,
+        nesting: usize,
+        blocks: usize,
+    ) {
+        for expr in exprs {
+            self.visit_expr(expr, function, nesting, blocks);
+        }
+    }
+
+    fn visit_expr(
+        &mut self,
+        expr: &Expr,
+        function: &mut FunctionMetrics,
+        nesting: usize,
+        blocks: usize,
+    ) {
+        use ExprKind::*;
+
+        match &*expr.kind {
+            Block { stmts, return_expr } => {
+                let blocks = blocks + 1;
+                function.block_depth = function.block_depth.max(blocks);
+                for stmt in stmts {
+                    self.visit_stmt(stmt, function, nesting, blocks);
+                }
+                self.visit_exprs(return_expr, function, nesting, blocks);
+            }
+            If {
+                condition,
+                body,
+                else_expr,
+            } => {
+                function.complexity += 1;
+                function.nesting_depth = function.nesting_depth.max(nesting + 1);
+                self.visit_expr(condition, function, nesting, blocks);
+                self.visit_expr(body, function, nesting + 1, blocks);
+
+                match else_expr {
+                    // else if continues the same chain
+                    Some(else_expr) if matches!(&*else_expr.kind, If { .. }) => {
+                        self.visit_expr(else_expr, function, nesting, blocks);
+                    }
+                    else_expr => self.visit_exprs(else_expr, function, nesting + 1, blocks),
+                }
+            }
+            While { condition, body } => {
+                function.complexity += 1;
+                function.nesting_depth = function.nesting_depth.max(nesting + 1);
+                self.visit_expr(condition, function, nesting, blocks);
+                self.visit_expr(body, function, nesting + 1, blocks);
+            }
+            Binary { lhs, op, rhs } => {
+                if matches!(op.kind, BinaryOperator::And | BinaryOperator::Or) {
+                    function.complexity += 1;
+                }
+                self.visit_expr(lhs, function, nesting, blocks);
+                self.visit_expr(rhs, function, nesting, blocks);
+            }
+            Closure { body, .. } => {
+                function.closures += 1;
+                self.visit_expr(body, function, nesting, blocks);
+            }
+            Unary { rhs, .. } => self.visit_expr(rhs, function, nesting, blocks),
+            Break { return_expr } => self.visit_exprs(return_expr, function, nesting, blocks),
+            Return { value } => self.visit_exprs(value, function, nesting, blocks),
+            Call { callee, args } => {
+                self.visit_expr(callee, function, nesting, blocks);
+                self.visit_exprs(args, function, nesting, blocks);
+            }
+            Array { values } => self.visit_exprs(values, function, nesting, blocks),
+            Index { target, position } => {
+                self.visit_expr(target, function, nesting, blocks);
+                self.visit_expr(position, function, nesting, blocks);
+            }
+            GetProperty { target, .. } => self.visit_expr(target, function, nesting, blocks),
+            SetProperty { target, value, .. } | Assignment { target, value } => {
+                self.visit_expr(target, function, nesting, blocks);
+                self.visit_expr(value, function, nesting, blocks);
+            }
+            ObjectLiteral { properties } => {
+                let values = properties.iter().map(|(_, value)| value);
+                self.visit_exprs(values, function, nesting, blocks);
+            }
+            Atom(_) | Continue => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use parser::parse;
+
+    use super::*;
+
+    fn measure_one(code: &str) -> FunctionMetrics {
+        measure(&parse(code).unwrap()).remove(0)
+    }
+
+    #[test]
+    fn measures_functions() {
+        let metrics = measure_one(
+            "fn foo(a, b) {
+                let c = a and b;
+                if a { 1; } else if b { 2; } else { while c { if a or b { c = false; }; }; };
+                let f = |x| => x + 1;
+                f(c)
+            }",
+        );
+
+        assert_eq!(
+            metrics,
+            FunctionMetrics {
+                name: "foo".to_owned(),
+                span: metrics.span.clone(),
+                complexity: 7,
+                nesting_depth: 3,
+                parameters: 2,
+                statements: 8,
+                closures: 1,
+                block_depth: 4,
+            }
+        );
+    }
+
+    #[test]
+    fn measures_nested_functions_separately() {
+        let functions = measure(
+            &parse("fn outer() { fn inner(a) => if a { 1 } else { 2 } inner } class Foo { fn method() => 1 }")
+                .unwrap(),
+        );
+        let summary: Vec<(&str, usize, usize)> = functions
+            .iter()
+            .map(|function| {
+                (
+                    function.name.as_str(),
+                    function.complexity,
+                    function.statements,
+                )
+            })
+            .collect();
+
+        assert_eq!(
+            summary,
+            vec![("outer", 1, 1), ("inner", 2, 0), ("method", 1, 0)]
+        );
+    }
+
+    #[test]
+    fn finds_exceeded_thresholds() {
+        let metrics = measure_one("fn foo(a, b, c) => if a { b } else { c }");
+        let thresholds = Thresholds {
+            complexity: Some(1),
+            parameters: Some(3),
+            ..Thresholds::default()
+        };
+
+        assert_eq!(
+            thresholds.exceeded(&metrics),
+            vec![(Metric::Complexity, 2, 1)]
+        );
+    }
+}
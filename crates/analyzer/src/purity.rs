@@ -0,0 +1,502 @@
+use std::collections::{HashMap, HashSet};
+
+use common::ProgramText;
+use parser::{
+    parse::{
+        expr::{atom::AtomicValue, Expr, ExprKind},
+        stmt::{Annotation, Stmt, StmtKind},
+        AstRef, Span,
+    },
+    utils::{
+        combine,
+        error::{ParseErrorCause, SideEffect},
+    },
+};
+
+use crate::resolution::{Resolution, Resolutions};
+
+fn is_inside(span: &Span, function: &Span) -> bool {
+    function.start <= span.start && span.end <= function.end
+}
+
+// Side effects of a function and of everything it calls
+#[derive(Debug, Clone)]
+struct Reach {
+    effects: bool,
+    // Declarations of the captured variables that get assigned, other than the function's own
+    writes: HashSet<Span>,
+}
+
+impl Reach {
+    // Adds the effects of a callee, returns whether anything changed
+    fn extend(&mut self, callee: Reach, function: &Span) -> bool {
+        let mut changed = callee.effects && !self.effects;
+        self.effects |= callee.effects;
+        for declaration in callee.writes {
+            if !is_inside(&declaration, function) {
+                changed |= self.writes.insert(declaration);
+            }
+        }
+        changed
+    }
+
+    // Whether a call is noticed outside of the given function
+    fn escapes(&self, function: &Span) -> bool {
+        self.effects
+            || self
+                .writes
+                .iter()
+                .any(|declaration| !is_inside(declaration, function))
+    }
+}
+
+#[derive(Debug, Default)]
+struct Function {
+    name: Option<ProgramText>,
+    // Variable an anonymous closure is assigned to, which names it in errors
+    variable: Option<ProgramText>,
+    // Span of the @pure annotation
+    annotation: Option<Span>,
+    // Found in the function's own body, closures declared inside of it have their own
+    effects: Vec<(Span, SideEffect)>,
+    // Span of the assignment, name and declaration of a captured variable. They are effects only
+    // for the functions the variable is declared outside of, others see just local state change.
+    writes: Vec<(Span, ProgramText, Span)>,
+    // Span of the call and the declaration of the called variable
+    calls: Vec<(Span, Span)>,
+    // Span of the call and the function that gets called
+    callees: Vec<(Span, Span)>,
+}
+
+// Infers which functions are pure, meaning they don't call natives, don't assign to the variables
+// they capture, don't modify objects or arrays and call nothing but other pure functions.
+// Closures that assign to variables of the function they're declared in don't make it impure.
+pub(crate) struct Purity<'r> {
+    resolutions: &'r Resolutions,
+    functions: HashMap<Span, Function>,
+    // Spans of the functions in the order they are declared in
+    order: Vec<Span>,
+    // Functions surrounding the visited code, innermost last
+    stack: Vec<Span>,
+    // Closures assigned to variables that never change, keyed by the span of the declaration
+    closures: HashMap<Span, Span>,
+}
+
+impl<'r> Purity<'r> {
+    // Returns spans of the pure functions and the violations of @pure annotations
+    pub(crate) fn check(
+        ast: AstRef,
+        resolutions: &'r Resolutions,
+    ) -> (HashSet<Span>, Vec<(Span, ParseErrorCause)>) {
+        let mut purity = Self {
+            resolutions,
+            functions: HashMap::new(),
+            order: Vec::new(),
+            stack: Vec::new(),
+            closures: HashMap::new(),
+        };
+        for stmt in ast {
+            purity.visit_stmt(stmt);
+        }
+        purity.resolve_calls();
+
+        let reach = purity.reach();
+        let violations = purity.violations(&reach);
+        let pure = purity
+            .order
+            .into_iter()
+            .filter(|function| !reach[function].escapes(function))
+            .collect();
+
+        (pure, violations)
+    }
+
+    fn name(&self, function: &Span) -> ProgramText {
+        let function = &self.functions[function];
+        function
+            .name
+            .clone()
+            .or_else(|| function.variable.clone())
+            .unwrap_or_else(|| "<closure>".to_owned())
+    }
+
+    fn effect(&mut self, span: Span, effect: SideEffect) {
+        // Code outside of functions can do whatever it wants
+        if let Some(function) = self.stack.last() {
+            let function = self.functions.get_mut(function).unwrap();
+            function.effects.push((span, effect));
+        }
+    }
+
+    fn write(&mut self, span: Span, name: ProgramText, declaration: Span) {
+        if let Some(function) = self.stack.last() {
+            let function = self.functions.get_mut(function).unwrap();
+            function.writes.push((span, name, declaration));
+        }
+    }
+
+    fn call(&mut self, span: Span, declaration: Span) {
+        if let Some(function) = self.stack.last() {
+            let function = self.functions.get_mut(function).unwrap();
+            function.calls.push((span, declaration));
+        }
+    }
+
+    // Functions can be called before they are declared,
+    // so callees are known only once the whole program has been visited
+    fn resolve_calls(&mut self) {
+        let resolutions = self.resolutions;
+        let closures = &self.closures;
+        let known: HashSet<&Span> = self.order.iter().collect();
+
+        for function in self.functions.values_mut() {
+            for (call, declaration) in std::mem::take(&mut function.calls) {
+                // Variables that get a new value can hold any function
+                let callee = if resolutions.is_reassigned(&declaration) {
+                    None
+                } else {
+                    Some(closures.get(&declaration).unwrap_or(&declaration))
+                };
+
+                match callee.filter(|callee| known.contains(callee)) {
+                    Some(callee) => function.callees.push((call, callee.clone())),
+                    None => function.effects.push((call, SideEffect::CallsUnknown)),
+                }
+            }
+        }
+    }
+
+    // Effects of every function together with the ones of the functions it calls.
+    // Each call has its own variables, so assignments to them don't reach the callers.
+    fn reach(&self) -> HashMap<Span, Reach> {
+        let mut reach: HashMap<Span, Reach> = self
+            .functions
+            .iter()
+            .map(|(span, function)| {
+                let reach = Reach {
+                    effects: !function.effects.is_empty(),
+                    // Captured variables are always declared outside of the function itself
+                    writes: function
+                        .writes
+                        .iter()
+                        .map(|(_, _, declaration)| declaration.clone())
+                        .collect(),
+                };
+                (span.clone(), reach)
+            })
+            .collect();
+
+        // Spreads from the callees to their callers until nothing changes
+        loop {
+            let mut changed = false;
+            for (span, function) in &self.functions {
+                for (_, callee) in &function.callees {
+                    let callee = reach[callee].clone();
+                    changed |= reach.get_mut(span).unwrap().extend(callee, span);
+                }
+            }
+
+            if !changed {
+                return reach;
+            }
+        }
+    }
+
+    fn violations(&self, reach: &HashMap<Span, Reach>) -> Vec<(Span, ParseErrorCause)> {
+        let mut violations = Vec::new();
+
+        for span in &self.order {
+            let function = &self.functions[span];
+            let annotation = match &function.annotation {
+                Some(annotation) => annotation,
+                None => continue,
+            };
+
+            let mut effects = function.effects.clone();
+            effects.extend(
+                function.writes.iter().map(|(write, name, _)| {
+                    (write.clone(), SideEffect::MutatesCapture(name.clone()))
+                }),
+            );
+            effects.extend(
+                function
+                    .callees
+                    .iter()
+                    .filter(|(_, callee)| reach[callee].escapes(span))
+                    .map(|(call, callee)| {
+                        (call.clone(), SideEffect::CallsImpure(self.name(callee)))
+                    }),
+            );
+            effects.sort_by_key(|(span, _)| span.start);
+
+            for (effect_span, effect) in effects {
+                violations.push((
+                    effect_span,
+                    ParseErrorCause::PurityViolation {
+                        function: self.name(span),
+                        effect,
+                        annotation: annotation.clone(),
+                    },
+                ));
+            }
+        }
+
+        violations
+    }
+
+    fn visit_function(
+        &mut self,
+        name: Option<&str>,
+        span: &Span,
+        annotation: Option<Span>,
+        body: &Expr,
+    ) {
+        self.functions.insert(
+            span.clone(),
+            Function {
+                name: name.map(ToOwned::to_owned),
+                annotation,
+                ..Function::default()
+            },
+        );
+        self.order.push(span.clone());
+
+        self.stack.push(span.clone());
+        self.visit_expr(body);
+        self.stack.pop();
+    }
+
+    fn visit_stmt(&mut self, stmt: &Stmt) {
+        use StmtKind::*;
+
+        match &*stmt.kind {
+            Expression { expr } => self.visit_expr(expr),
+            VariableDeclaration { name, expr } => {
+                self.visit_expr(expr);
+                if let ExprKind::Closure { .. } = &*expr.kind {
+                    self.closures.insert(stmt.span.clone(), expr.span.clone());
+                    if let Some(closure) = self.functions.get_mut(&expr.span) {
+                        closure.variable = Some(name.clone());
+                    }
+                }
+            }
+            FunctionDeclaration {
+                name,
+                body,
+                annotations,
+                ..
+            } => {
+                let annotation = annotations
+                    .iter()
+                    .find(|annotation| annotation.kind == Annotation::Pure)
+                    .map(|annotation| annotation.span.clone());
+                self.visit_function(Some(name), &stmt.span, annotation, body);
+            }
+            ClassDeclaration { methods, .. } => {
+                for method in methods {
+                    self.visit_stmt(method);
+                }
+            }
+        }
+    }
+
+    fn visit_callee(&mut self, callee: &Expr, span: &Span) {
+        let call = combine(&callee.span, span);
+
+        let name = match &*callee.kind {
+            ExprKind::Atom(AtomicValue::Identifier { name, .. }) => name,
+            // Closure that is called right away
+            ExprKind::Closure { .. } => {
+                return self.call(call, callee.span.clone());
+            }
+            _ => return self.effect(call, SideEffect::CallsUnknown),
+        };
+
+        if let Some(Resolution::Builtin(_)) = self.resolutions.identifier(&callee.span) {
+            return self.effect(call, SideEffect::CallsNative(name.clone()));
+        }
+
+        match self.resolutions.declaration(&callee.span) {
+            Some(declaration) => self.call(call, declaration.clone()),
+            // Names that aren't declared anywhere belong to the functions themselves
+            None => {
+                let function = self
+                    .stack
+                    .iter()
+                    .rev()
+                    .find(|function| self.functions[*function].name.as_ref() == Some(name))
+                    .cloned();
+
+                match function {
+                    Some(function) => self.call(call, function),
+                    None => self.effect(call, SideEffect::CallsUnknown),
+                }
+            }
+        }
+    }
+
+    fn visit_exprs<'a>(&mut self, exprs: impl IntoIterator<Item = &'a Expr>) {
+        for expr in exprs {
+            self.visit_expr(expr);
+        }
+    }
+
+    fn visit_expr(&mut self, expr: &Expr) {
+        use ExprKind::*;
+        let span = &expr.span;
+
+        match &*expr.kind {
+            Call { callee, args } => {
+                self.visit_callee(callee, span);
+                self.visit_expr(callee);
+                self.visit_exprs(args);
+            }
+            Assignment { target, value } => {
+                match &*target.kind {
+                    Atom(AtomicValue::Identifier { name, .. }) => {
+                        if let Some(Resolution::Upvalue { .. }) =
+                            self.resolutions.identifier(&target.span)
+                        {
+                            match self.resolutions.declaration(&target.span) {
+                                Some(declaration) => self.write(
+                                    target.span.clone(),
+                                    name.clone(),
+                                    declaration.clone(),
+                                ),
+                                // Slot of the function itself isn't declared anywhere
+                                None => self.effect(
+                                    target.span.clone(),
+                                    SideEffect::MutatesCapture(name.clone()),
+                                ),
+                            }
+                        }
+                    }
+                    _ => self.effect(target.span.clone(), SideEffect::MutatesValue),
+                }
+                self.visit_expr(target);
+                self.visit_expr(value);
+            }
+            SetProperty { target, value, .. } => {
+                self.effect(span.clone(), SideEffect::SetsProperty);
+                self.visit_expr(target);
+                self.visit_expr(value);
+            }
+            Closure { body, .. } => {
+                self.visit_function(None, span, None, body);
+            }
+            Binary { lhs, rhs, .. } => {
+                self.visit_expr(lhs);
+                self.visit_expr(rhs);
+            }
+            Unary { rhs, .. } => self.visit_expr(rhs),
+            Block { stmts, return_expr } => {
+                for stmt in stmts {
+                    self.visit_stmt(stmt);
+                }
+                self.visit_exprs(return_expr);
+            }
+            If {
+                condition,
+                body,
+                else_expr,
+            } => {
+                self.visit_expr(condition);
+                self.visit_expr(body);
+                self.visit_exprs(else_expr);
+            }
+            While { condition, body } => {
+                self.visit_expr(condition);
+                self.visit_expr(body);
+            }
+            Break { return_expr } => self.visit_exprs(return_expr),
+            Return { value } => self.visit_exprs(value),
+            Array { values } => self.visit_exprs(values),
+            Index { target, position } => {
+                self.visit_expr(target);
+                self.visit_expr(position);
+            }
+            GetProperty { target, .. } => self.visit_expr(target),
+            ObjectLiteral { properties } => {
+                self.visit_exprs(properties.iter().map(|(_, value)| value));
+            }
+            Atom(_) | Continue => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use parser::parse;
+
+    use super::*;
+    use crate::Analyzer;
+
+    // Names of the pure functions and the effects that violate @pure annotations
+    fn check(code: &str) -> (Vec<String>, Vec<(ProgramText, SideEffect)>) {
+        let ast = parse(code).unwrap();
+        let mut analyzer = Analyzer::new();
+        let _ = analyzer.analyze(&ast);
+        let (pure, violations) = Purity::check(&ast, analyzer.resolutions());
+
+        let pure = analyzer
+            .captures()
+            .iter()
+            .filter(|function| pure.contains(&function.function))
+            .map(|function| function.name().to_owned())
+            .collect();
+        let violations = violations
+            .into_iter()
+            .filter_map(|(_, cause)| match cause {
+                ParseErrorCause::PurityViolation {
+                    function, effect, ..
+                } => Some((function, effect)),
+                _ => None,
+            })
+            .collect();
+
+        (pure, violations)
+    }
+
+    #[test]
+    fn closures_can_change_variables_of_the_function() {
+        let (pure, violations) =
+            check("@pure fn h() { let c = 0; let inc = || => { c = c + 1; }; inc(); c }");
+        assert_eq!(pure, vec!["h"]);
+        assert_eq!(violations, vec![]);
+
+        // every call gets its own variables, so callers stay pure as well
+        let (pure, _) = check(
+            "fn counter() { let n = 0; let inc = || => { n = n + 1; }; inc(); n } fn f() => counter()",
+        );
+        assert_eq!(pure, vec!["counter", "f"]);
+    }
+
+    #[test]
+    fn captured_variables_declared_outside_are_effects() {
+        let (pure, violations) =
+            check("let c = 0; @pure fn h() { let inc = || => { c = c + 1; }; inc(); c }");
+        assert_eq!(pure, Vec::<String>::new());
+        assert_eq!(
+            violations,
+            vec![("h".to_owned(), SideEffect::CallsImpure("inc".to_owned()))]
+        );
+
+        let (_, violations) = check("let c = 0; @pure fn h() { c = 1; }");
+        assert_eq!(
+            violations,
+            vec![("h".to_owned(), SideEffect::MutatesCapture("c".to_owned()))]
+        );
+    }
+
+    #[test]
+    fn names_anonymous_closures() {
+        let (_, violations) = check("@pure fn h() => (|| => print(1))()");
+        assert_eq!(
+            violations,
+            vec![(
+                "h".to_owned(),
+                SideEffect::CallsImpure("<closure>".to_owned())
+            )]
+        );
+    }
+}
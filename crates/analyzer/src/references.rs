@@ -0,0 +1,452 @@
+use common::ProgramText;
+use parser::parse::{
+    expr::{atom::AtomicValue, Expr, ExprKind},
+    stmt::{Stmt, StmtKind},
+    AstRef, Node, Params, Span,
+};
+
+use crate::{
+    resolution::{Resolution, Resolutions},
+    symbols::SymbolKind,
+};
+
+// Something declared in the program that can be navigated to
+#[derive(Debug, Clone, PartialEq)]
+pub struct Declaration {
+    pub name: ProgramText,
+    pub kind: SymbolKind,
+    // Whole statement, or the parameter, same as in the resolutions
+    pub span: Span,
+    // Just the name
+    pub name_span: Span,
+    // Declaration written without its body, e.g. `fn add(a, b)`
+    pub detail: String,
+    // Function or closure that the declaration introduces
+    pub function: Option<Span>,
+    // Index of the function or class that the declaration belongs to
+    pub parent: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Identifier {
+    pub name: ProgramText,
+    pub span: Span,
+}
+
+// Name of a property that gets read, assigned, declared as a method or written in an object literal
+#[derive(Debug, Clone, PartialEq)]
+pub struct Property {
+    pub name: ProgramText,
+    pub span: Span,
+    // `{ foo }` is both the key and the variable it takes the value from
+    pub shorthand: bool,
+}
+
+// Every name written in the program, together with what it refers to
+#[derive(Debug, Clone, Default)]
+pub struct References {
+    // In the order they start in
+    declarations: Vec<Declaration>,
+    identifiers: Vec<Identifier>,
+    properties: Vec<Property>,
+    // Spans of identifiers and the declarations they refer to
+    uses: Vec<(Span, Span)>,
+}
+
+impl References {
+    pub fn new(ast: AstRef, source: &str, resolutions: &Resolutions) -> Self {
+        let mut collector = Collector {
+            source,
+            references: References::default(),
+            parent: None,
+        };
+        for stmt in ast {
+            collector.visit_stmt(stmt);
+        }
+
+        let mut references = collector.references;
+        references.uses = references.find_uses(resolutions);
+        references
+    }
+
+    fn find_uses(&self, resolutions: &Resolutions) -> Vec<(Span, Span)> {
+        let mut uses: Vec<(Span, Span)> = resolutions
+            .declarations()
+            .map(|(identifier, declaration)| (identifier.clone(), declaration.clone()))
+            .collect();
+
+        // Functions aren't declared inside of their own bodies, so calls to themselves aren't resolved to anything
+        for identifier in &self.identifiers {
+            let span = &identifier.span;
+            let unresolved = resolutions.declaration(span).is_none()
+                && !matches!(
+                    resolutions.identifier(span),
+                    Some(Resolution::Builtin(_)) | None
+                );
+            if !unresolved {
+                continue;
+            }
+
+            let function = self.declarations.iter().rev().find(|declaration| {
+                declaration.kind == SymbolKind::Function
+                    && declaration.name == identifier.name
+                    && declaration.span.contains(&span.start)
+            });
+            if let Some(function) = function {
+                uses.push((span.clone(), function.span.clone()));
+            }
+        }
+
+        uses.sort_by_key(|(identifier, _)| identifier.start);
+        uses
+    }
+
+    pub fn declarations(&self) -> &[Declaration] {
+        &self.declarations
+    }
+
+    // In the order they are written in
+    pub fn identifiers(&self) -> &[Identifier] {
+        &self.identifiers
+    }
+
+    pub fn declaration(&self, span: &Span) -> Option<&Declaration> {
+        self.declarations
+            .iter()
+            .find(|declaration| &declaration.span == span)
+    }
+
+    // Declaration that the identifier refers to, builtins and unknown names don't have any
+    pub fn declaration_of(&self, identifier: &Span) -> Option<&Declaration> {
+        let (_, declaration) = self.uses.iter().find(|(span, _)| span == identifier)?;
+        self.declaration(declaration)
+    }
+
+    pub fn uses_of<'a>(&'a self, declaration: &'a Span) -> impl Iterator<Item = &'a Span> {
+        self.uses
+            .iter()
+            .filter(move |(_, other)| other == declaration)
+            .map(|(identifier, _)| identifier)
+    }
+
+    // Cursor right behind the name still points at it
+    pub fn identifier_at(&self, offset: usize) -> Option<&Identifier> {
+        self.identifiers
+            .iter()
+            .find(|identifier| touches(&identifier.span, offset))
+    }
+
+    pub fn property_at(&self, offset: usize) -> Option<&Property> {
+        self.properties
+            .iter()
+            .find(|property| touches(&property.span, offset))
+    }
+
+    pub fn properties_named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Property> {
+        self.properties
+            .iter()
+            .filter(move |property| property.name == name)
+    }
+
+    pub fn is_shorthand(&self, identifier: &Span) -> bool {
+        self.properties
+            .iter()
+            .any(|property| property.shorthand && &property.span == identifier)
+    }
+
+    pub fn is_method(&self, declaration: &Declaration) -> bool {
+        declaration
+            .parent
+            .is_some_and(|parent| self.declarations[parent].kind == SymbolKind::Class)
+    }
+
+    // Declaration of the identifier under the cursor or the declaration whose name it is on
+    pub fn target(&self, offset: usize) -> Option<&Declaration> {
+        if let Some(identifier) = self.identifier_at(offset) {
+            return self.declaration_of(&identifier.span);
+        }
+
+        self.declarations
+            .iter()
+            .find(|declaration| touches(&declaration.name_span, offset))
+    }
+}
+
+fn touches(span: &Span, offset: usize) -> bool {
+    span.start <= offset && offset <= span.end
+}
+
+fn is_identifier_char(char: char) -> bool {
+    char.is_alphanumeric() || char == '_'
+}
+
+// Position of the word that isn't a part of a longer identifier
+fn find_word(source: &str, from: usize, word: &str) -> Option<usize> {
+    source[from..]
+        .match_indices(word)
+        .map(|(offset, _)| from + offset)
+        .find(|&start| {
+            let before = source[..start].chars().next_back();
+            let after = source[start + word.len()..].chars().next();
+            !before.is_some_and(is_identifier_char) && !after.is_some_and(is_identifier_char)
+        })
+}
+
+fn format_params(params: &Params) -> String {
+    params
+        .kind
+        .iter()
+        .map(|param| param.kind.as_str())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+struct Collector<'s> {
+    source: &'s str,
+    references: References,
+    parent: Option<usize>,
+}
+
+impl Collector<'_> {
+    // Names aren't nodes of their own, so they are looked up in the source of the declaration
+    fn name_span(&self, span: &Span, keyword: &str, name: &str) -> Span {
+        let after_keyword = find_word(self.source, span.start, keyword)
+            .map_or(span.start, |start| start + keyword.len());
+
+        match find_word(self.source, after_keyword, name) {
+            Some(start) if start + name.len() <= span.end => start..start + name.len(),
+            _ => span.clone(),
+        }
+    }
+
+    fn declare(&mut self, declaration: Declaration) -> usize {
+        let declarations = &mut self.references.declarations;
+        declarations.push(declaration);
+        declarations.len() - 1
+    }
+
+    // Parameters belong to the declaration of the function, if it has one
+    fn visit_function(&mut self, index: Option<usize>, params: &Params, body: &Expr) {
+        let parent = self.parent;
+        self.parent = index.or(parent);
+        for param in &params.kind {
+            self.declare(Declaration {
+                name: param.kind.clone(),
+                kind: SymbolKind::Parameter,
+                span: param.span.clone(),
+                name_span: param.span.clone(),
+                detail: format!("(parameter) {}", param.kind),
+                function: None,
+                parent: self.parent,
+            });
+        }
+        self.visit_expr(body);
+        self.parent = parent;
+    }
+
+    fn visit_stmt(&mut self, stmt: &Stmt) {
+        use StmtKind::*;
+        let span = &stmt.span;
+
+        match &*stmt.kind {
+            Expression { expr } => self.visit_expr(expr),
+            VariableDeclaration { name, expr } => {
+                let name_span = self.name_span(span, "let", name);
+                match &*expr.kind {
+                    ExprKind::Closure { params, body } => {
+                        let index = self.declare(Declaration {
+                            name: name.clone(),
+                            kind: SymbolKind::Closure,
+                            span: span.clone(),
+                            name_span,
+                            detail: format!("let {} = |{}|", name, format_params(params)),
+                            function: Some(expr.span.clone()),
+                            parent: self.parent,
+                        });
+                        self.visit_function(Some(index), params, body);
+                    }
+                    _ => {
+                        self.declare(Declaration {
+                            name: name.clone(),
+                            kind: SymbolKind::Variable,
+                            span: span.clone(),
+                            name_span,
+                            detail: format!("let {}", name),
+                            function: None,
+                            parent: self.parent,
+                        });
+                        self.visit_expr(expr);
+                    }
+                }
+            }
+            FunctionDeclaration {
+                name,
+                params,
+                body,
+                annotations,
+            } => {
+                let annotations: String = annotations
+                    .iter()
+                    .map(|annotation| format!("{} ", annotation))
+                    .collect();
+                let name_span = self.name_span(span, "fn", name);
+                let is_method = self.parent.is_some_and(|parent| {
+                    self.references.declarations[parent].kind == SymbolKind::Class
+                });
+                if is_method {
+                    self.references.properties.push(Property {
+                        name: name.clone(),
+                        span: name_span.clone(),
+                        shorthand: false,
+                    });
+                }
+
+                let index = self.declare(Declaration {
+                    name: name.clone(),
+                    kind: SymbolKind::Function,
+                    span: span.clone(),
+                    name_span,
+                    detail: format!("{}fn {}({})", annotations, name, format_params(params)),
+                    function: Some(span.clone()),
+                    parent: self.parent,
+                });
+                self.visit_function(Some(index), params, body);
+            }
+            ClassDeclaration {
+                name,
+                super_class,
+                methods,
+            } => {
+                let detail = match super_class {
+                    Some(super_class) => format!("class {}: {}", name, super_class),
+                    None => format!("class {}", name),
+                };
+                let index = self.declare(Declaration {
+                    name: name.clone(),
+                    kind: SymbolKind::Class,
+                    span: span.clone(),
+                    name_span: self.name_span(span, "class", name),
+                    detail,
+                    function: None,
+                    parent: self.parent,
+                });
+
+                let parent = self.parent.replace(index);
+                for method in methods {
+                    self.visit_stmt(method);
+                }
+                self.parent = parent;
+            }
+        }
+    }
+
+    fn visit_exprs<'a>(&mut self, exprs: impl IntoIterator<Item = &'a Expr>) {
+        for expr in exprs {
+            self.visit_expr(expr);
+        }
+    }
+
+    // Span of the identifier starts at the dot in front of it
+    fn visit_property(&mut self, identifier: &Node<ProgramText>) {
+        let name = &identifier.kind;
+        let end = identifier.span.end;
+        self.references.properties.push(Property {
+            name: name.clone(),
+            span: end - name.len()..end,
+            shorthand: false,
+        });
+    }
+
+    // Keys don't have spans, so they are looked up between the previous value and their own
+    fn visit_object(&mut self, span: &Span, properties: &[(ProgramText, Expr)]) {
+        let mut from = span.start;
+        for (key, value) in properties {
+            let property = match find_word(self.source, from, key) {
+                Some(start) if start + key.len() <= value.span.start => Property {
+                    name: key.clone(),
+                    span: start..start + key.len(),
+                    shorthand: false,
+                },
+                _ => Property {
+                    name: key.clone(),
+                    span: value.span.clone(),
+                    shorthand: true,
+                },
+            };
+            self.references.properties.push(property);
+            self.visit_expr(value);
+            from = value.span.end;
+        }
+    }
+
+    fn visit_expr(&mut self, expr: &Expr) {
+        use ExprKind::*;
+
+        match &*expr.kind {
+            Atom(AtomicValue::Identifier { name, .. }) => {
+                self.references.identifiers.push(Identifier {
+                    name: name.clone(),
+                    span: expr.span.clone(),
+                });
+            }
+            // Anonymous closures only declare their parameters
+            Closure { params, body } => self.visit_function(None, params, body),
+            Block { stmts, return_expr } => {
+                for stmt in stmts {
+                    self.visit_stmt(stmt);
+                }
+                self.visit_exprs(return_expr);
+            }
+            If {
+                condition,
+                body,
+                else_expr,
+            } => {
+                self.visit_expr(condition);
+                self.visit_expr(body);
+                self.visit_exprs(else_expr);
+            }
+            While { condition, body } => {
+                self.visit_expr(condition);
+                self.visit_expr(body);
+            }
+            Binary { lhs, rhs, .. } => {
+                self.visit_expr(lhs);
+                self.visit_expr(rhs);
+            }
+            Unary { rhs, .. } => self.visit_expr(rhs),
+            Break { return_expr } => self.visit_exprs(return_expr),
+            Return { value } => self.visit_exprs(value),
+            Call { callee, args } => {
+                self.visit_expr(callee);
+                self.visit_exprs(args);
+            }
+            Array { values } => self.visit_exprs(values),
+            Index { target, position } => {
+                self.visit_expr(target);
+                self.visit_expr(position);
+            }
+            GetProperty {
+                target, identifier, ..
+            } => {
+                self.visit_expr(target);
+                self.visit_property(identifier);
+            }
+            SetProperty {
+                target,
+                value,
+                identifier,
+            } => {
+                self.visit_expr(target);
+                self.visit_property(identifier);
+                self.visit_expr(value);
+            }
+            Assignment { target, value } => {
+                self.visit_expr(target);
+                self.visit_expr(value);
+            }
+            ObjectLiteral { properties } => self.visit_object(&expr.span, properties),
+            Atom(_) | Continue => {}
+        }
+    }
+}
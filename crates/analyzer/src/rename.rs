@@ -0,0 +1,374 @@
+use codespan_reporting::diagnostic::{Diagnostic, Label};
+use common::{CompilerDiagnostic, ProgramText};
+use parser::{is_identifier, parse, parse::Span, TextEdit};
+use std::fmt;
+
+use crate::{
+    references::{Declaration, Identifier, References},
+    resolution::Resolution,
+    Analyzer, SUPER, THIS,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RenameError {
+    // Names can only be found in programs that parse
+    InvalidProgram,
+    InvalidName(ProgramText),
+    // Position isn't on a variable, parameter, function, class or property
+    NothingToRename,
+    Builtin {
+        name: ProgramText,
+        span: Span,
+    },
+    // After the rename the identifier would refer to a different declaration,
+    // builtins and names that aren't declared anywhere don't have one
+    CaptureCollision {
+        name: ProgramText,
+        span: Span,
+        captured_by: Option<Span>,
+    },
+    // Properties don't belong to any declaration, so two of them would merge into one
+    PropertyCollision {
+        name: ProgramText,
+        existing: Span,
+    },
+}
+
+impl fmt::Display for RenameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use RenameError::*;
+
+        match self {
+            InvalidProgram => write!(f, "Program has to parse before anything can be renamed"),
+            InvalidName(name) => write!(f, "'{}' can't be used as a name", name),
+            NothingToRename => write!(f, "There is nothing to rename at this position"),
+            Builtin { name, .. } => write!(f, "Builtin function '{}' can't be renamed", name),
+            CaptureCollision { name, .. } => write!(
+                f,
+                "Rename would make '{}' refer to a different declaration",
+                name
+            ),
+            PropertyCollision { name, .. } => write!(f, "Property '{}' already exists", name),
+        }
+    }
+}
+
+impl CompilerDiagnostic for RenameError {
+    fn report(&self, file_id: usize) -> Diagnostic<usize> {
+        use RenameError::*;
+        let diagnostic = Diagnostic::error().with_message(self.to_string());
+
+        match self {
+            InvalidProgram | InvalidName(_) | NothingToRename => diagnostic,
+            Builtin { span, .. } => {
+                diagnostic.with_labels(vec![Label::primary(file_id, span.clone())])
+            }
+            CaptureCollision {
+                span, captured_by, ..
+            } => {
+                let mut labels = vec![Label::primary(file_id, span.clone())];
+                match captured_by {
+                    Some(declaration) => labels.push(
+                        Label::secondary(file_id, declaration.clone())
+                            .with_message("...would be captured by this declaration"),
+                    ),
+                    None => labels[0].message = "...would no longer be declared".to_owned(),
+                }
+                diagnostic.with_labels(labels)
+            }
+            PropertyCollision { existing, .. } => {
+                diagnostic.with_labels(vec![
+                    Label::primary(file_id, existing.clone()).with_message("...it's used here")
+                ])
+            }
+        }
+    }
+}
+
+pub type RenameResult = Result<Vec<TextEdit>, RenameError>;
+
+// Edits renaming whatever is at the offset together with everything that refers to it,
+// in the order they appear in the source
+pub fn rename(source: &str, offset: usize, new_name: &str) -> RenameResult {
+    if !is_identifier(new_name) || new_name == THIS || new_name == SUPER {
+        return Err(RenameError::InvalidName(new_name.to_owned()));
+    }
+    let (analyzer, references) = resolve(source)?;
+
+    if let Some(identifier) = references.identifier_at(offset) {
+        return match references.declaration_of(&identifier.span) {
+            Some(declaration) => rename_declaration(source, &references, declaration, new_name),
+            None => match analyzer.resolutions().identifier(&identifier.span) {
+                Some(Resolution::Builtin(_)) => Err(RenameError::Builtin {
+                    name: identifier.name.clone(),
+                    span: identifier.span.clone(),
+                }),
+                _ => Err(RenameError::NothingToRename),
+            },
+        };
+    }
+
+    if let Some(property) = references.property_at(offset) {
+        return rename_property(&references, &property.name, new_name);
+    }
+
+    match references.target(offset) {
+        Some(declaration) => rename_declaration(source, &references, declaration, new_name),
+        None => Err(RenameError::NothingToRename),
+    }
+}
+
+// Edits are applied from the last one, so their ranges stay valid
+pub fn apply_edits(source: &str, edits: &[TextEdit]) -> String {
+    let mut edits: Vec<&TextEdit> = edits.iter().collect();
+    edits.sort_by_key(|edit| std::cmp::Reverse(edit.range.start));
+
+    let mut edited = source.to_owned();
+    for edit in edits {
+        edited.replace_range(edit.range.clone(), &edit.text);
+    }
+    edited
+}
+
+fn resolve(source: &str) -> Result<(Analyzer, References), RenameError> {
+    let ast = parse(source).map_err(|_| RenameError::InvalidProgram)?;
+    let mut analyzer = Analyzer::new();
+    // Programs with errors can be renamed too, resolutions are filled anyway
+    let _ = analyzer.analyze(&ast);
+    let references = References::new(&ast, source, analyzer.resolutions());
+
+    Ok((analyzer, references))
+}
+
+fn rename_declaration(
+    source: &str,
+    references: &References,
+    declaration: &Declaration,
+    new_name: &str,
+) -> RenameResult {
+    // Methods are called through properties of their objects
+    if references.is_method(declaration) {
+        return rename_property(references, &declaration.name, new_name);
+    }
+
+    let mut edits = vec![TextEdit::new(declaration.name_span.clone(), new_name)];
+    for span in references.uses_of(&declaration.span) {
+        // Object keeps the key it had
+        let text = if references.is_shorthand(span) {
+            format!("{}: {}", declaration.name, new_name)
+        } else {
+            new_name.to_owned()
+        };
+        edits.push(TextEdit::new(span.clone(), text));
+    }
+    edits.sort_by_key(|edit| edit.range.start);
+
+    check_captures(source, references, &edits)?;
+    Ok(edits)
+}
+
+fn rename_property(references: &References, name: &str, new_name: &str) -> RenameResult {
+    if let Some(existing) = references.properties_named(new_name).next() {
+        return Err(RenameError::PropertyCollision {
+            name: new_name.to_owned(),
+            existing: existing.span.clone(),
+        });
+    }
+
+    let mut edits: Vec<TextEdit> = references
+        .properties_named(name)
+        .map(|property| {
+            // Key changes while the variable it's taken from stays the same
+            let text = if property.shorthand {
+                format!("{}: {}", new_name, name)
+            } else {
+                new_name.to_owned()
+            };
+            TextEdit::new(property.span.clone(), text)
+        })
+        .collect();
+
+    // Methods calling themselves by their name
+    let methods = references
+        .declarations()
+        .iter()
+        .filter(|declaration| declaration.name == name && references.is_method(declaration));
+    for method in methods {
+        edits.extend(
+            references
+                .uses_of(&method.span)
+                .map(|span| TextEdit::new(span.clone(), new_name)),
+        );
+    }
+    edits.sort_by_key(|edit| edit.range.start);
+
+    Ok(edits)
+}
+
+// Position of the declaration that the identifier refers to
+fn meaning(references: &References, identifier: &Identifier) -> Option<usize> {
+    let declaration = references.declaration_of(&identifier.span)?;
+    references
+        .declarations()
+        .iter()
+        .position(|other| other == declaration)
+}
+
+// Renaming can't change what any identifier refers to. Renamed program has the same tree,
+// so its identifiers and declarations are compared in the order they appear in.
+fn check_captures(
+    source: &str,
+    references: &References,
+    edits: &[TextEdit],
+) -> Result<(), RenameError> {
+    let (_, renamed) = resolve(&apply_edits(source, edits))?;
+
+    for (before, after) in references.identifiers().iter().zip(renamed.identifiers()) {
+        let captured_by = meaning(&renamed, after);
+        if meaning(references, before) != captured_by {
+            return Err(RenameError::CaptureCollision {
+                name: before.name.clone(),
+                span: before.span.clone(),
+                captured_by: captured_by
+                    .map(|declaration| references.declarations()[declaration].span.clone()),
+            });
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // Offset of the nth occurrence of the text
+    fn at(source: &str, text: &str, nth: usize) -> usize {
+        source.match_indices(text).nth(nth).unwrap().0
+    }
+
+    fn assert_renames(source: &str, (text, nth): (&str, usize), new_name: &str, expected: &str) {
+        let edits = rename(source, at(source, text, nth), new_name).unwrap();
+        assert_eq!(apply_edits(source, &edits), expected);
+    }
+
+    #[test]
+    fn renames_variables_and_their_uses() {
+        assert_renames(
+            "let a = 1; let b = a + 1; print(a);",
+            ("a", 2),
+            "total",
+            "let total = 1; let b = total + 1; print(total);",
+        );
+        // shadowing declaration is a different variable
+        assert_renames(
+            "let a = 1; fn foo(a) => a + 1 print(a);",
+            ("a", 1),
+            "b",
+            "let a = 1; fn foo(b) => b + 1 print(a);",
+        );
+        assert_renames(
+            "fn fact(n) => if n < 2 { 1 } else { n * fact(n - 1) } fact(5);",
+            ("fact", 0),
+            "factorial",
+            "fn factorial(n) => if n < 2 { 1 } else { n * factorial(n - 1) } factorial(5);",
+        );
+        // object keeps its key
+        assert_renames(
+            "let a = 1; let obj = new { a };",
+            ("a", 0),
+            "b",
+            "let b = 1; let obj = new { a: b };",
+        );
+    }
+
+    #[test]
+    fn renames_properties() {
+        assert_renames(
+            "class Foo { fn speak() => this.sound } let foo = Foo(); foo.sound = 1; foo.speak(); let obj = new { sound: 2 };",
+            ("sound", 0),
+            "noise",
+            "class Foo { fn speak() => this.noise } let foo = Foo(); foo.noise = 1; foo.speak(); let obj = new { noise: 2 };",
+        );
+        assert_renames(
+            "class Foo { fn speak() => 1 } Foo().speak();",
+            ("speak", 0),
+            "talk",
+            "class Foo { fn talk() => 1 } Foo().talk();",
+        );
+        assert_renames(
+            "let sound = 1; let obj = new { sound }; obj.sound;",
+            ("sound", 2),
+            "noise",
+            "let sound = 1; let obj = new { noise: sound }; obj.noise;",
+        );
+    }
+
+    #[test]
+    fn refuses_capture_collisions() {
+        let source = "let a = 1; fn foo() { let b = 2; a + b }";
+        // `a` inside of foo would be captured by `b`
+        assert_eq!(
+            rename(source, at(source, "a", 0), "b"),
+            Err(RenameError::CaptureCollision {
+                name: "a".to_owned(),
+                span: 33..34,
+                captured_by: Some(22..32),
+            })
+        );
+        // `a` inside of foo would refer to the renamed `b` instead
+        let source = "let a = 1; fn foo() { let b = 2; a + b }";
+        assert_eq!(
+            rename(source, at(source, "b", 0), "a"),
+            Err(RenameError::CaptureCollision {
+                name: "a".to_owned(),
+                span: 33..34,
+                captured_by: Some(22..32),
+            })
+        );
+        let source = "let a = 1; print(a);";
+        assert!(matches!(
+            rename(source, at(source, "a", 0), "print"),
+            Err(RenameError::CaptureCollision { name, .. }) if name == "print"
+        ));
+        // different scopes are fine
+        assert_renames(
+            "fn foo() { let a = 1; a } fn bar() { let b = 2; b }",
+            ("a", 0),
+            "b",
+            "fn foo() { let b = 1; b } fn bar() { let b = 2; b }",
+        );
+    }
+
+    #[test]
+    fn refuses_invalid_renames() {
+        let source = "let a = 1; print(a); a.foo = 2; a.bar;";
+        assert_eq!(
+            rename(source, at(source, "a", 0), "let"),
+            Err(RenameError::InvalidName("let".to_owned()))
+        );
+        assert_eq!(
+            rename(source, at(source, "a", 0), "this"),
+            Err(RenameError::InvalidName("this".to_owned()))
+        );
+        assert_eq!(
+            rename(source, at(source, "print", 0), "show"),
+            Err(RenameError::Builtin {
+                name: "print".to_owned(),
+                span: 11..16,
+            })
+        );
+        assert_eq!(
+            rename(source, at(source, "1", 0), "b"),
+            Err(RenameError::NothingToRename)
+        );
+        assert_eq!(
+            rename(source, at(source, "foo", 0), "bar"),
+            Err(RenameError::PropertyCollision {
+                name: "bar".to_owned(),
+                existing: 34..37,
+            })
+        );
+        assert_eq!(rename("let a = ", 4, "b"), Err(RenameError::InvalidProgram));
+    }
+}
@@ -0,0 +1,96 @@
+use common::BuiltInFunction;
+use parser::parse::Span;
+use std::collections::{HashMap, HashSet};
+
+// Where the value of an identifier lives when the program runs
+#[derive(Debug, Clone, PartialEq)]
+pub enum Resolution {
+    // Slot in the current call frame, counted from its start
+    Local(usize),
+    // Index into the upvalues of the running closure.
+    // References are upvalues captured from another upvalue of the enclosing closure.
+    Upvalue { index: usize, is_ref: bool },
+    Builtin(BuiltInFunction),
+}
+
+// Side table filled by the analyzer and consumed by the bytecode generator.
+// Nodes are identified by their spans, which never overlap for two nodes of the same kind.
+#[derive(Debug, Clone, Default)]
+pub struct Resolutions {
+    // Keyed by the span of the identifier
+    identifiers: HashMap<Span, Resolution>,
+    // Keyed by the span of the function declaration or closure.
+    // Addresses are resolved in the frame that creates the closure, in the order of upvalue indices.
+    captures: HashMap<Span, Vec<Resolution>>,
+    // Span of the declaration, keyed by the span of the identifier
+    declarations: HashMap<Span, Span>,
+    // Declarations that are assigned a new value somewhere
+    reassigned: HashSet<Span>,
+    // Slot in the call frame, keyed by the span of the declaration
+    slots: HashMap<Span, usize>,
+    // Functions and closures without side effects
+    pure: HashSet<Span>,
+}
+
+impl Resolutions {
+    pub fn identifier(&self, span: &Span) -> Option<&Resolution> {
+        self.identifiers.get(span)
+    }
+
+    pub fn captures(&self, span: &Span) -> &[Resolution] {
+        self.captures.get(span).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    pub fn declaration(&self, span: &Span) -> Option<&Span> {
+        self.declarations.get(span)
+    }
+
+    // Every identifier together with the declaration it refers to
+    pub fn declarations(&self) -> impl Iterator<Item = (&Span, &Span)> {
+        self.declarations.iter()
+    }
+
+    pub fn slot(&self, declaration: &Span) -> Option<usize> {
+        self.slots.get(declaration).copied()
+    }
+
+    pub fn is_reassigned(&self, declaration: &Span) -> bool {
+        self.reassigned.contains(declaration)
+    }
+
+    pub fn is_pure(&self, function: &Span) -> bool {
+        self.pure.contains(function)
+    }
+
+    pub(crate) fn mark_pure(&mut self, functions: HashSet<Span>) {
+        self.pure = functions;
+    }
+
+    pub(crate) fn resolve(&mut self, span: &Span, resolution: Resolution) {
+        self.identifiers.insert(span.clone(), resolution);
+    }
+
+    pub(crate) fn allocate(&mut self, declaration: &Span, slot: usize) {
+        self.slots.insert(declaration.clone(), slot);
+    }
+
+    pub(crate) fn declare(&mut self, span: &Span, declaration: &Span, is_assignment: bool) {
+        self.declarations.insert(span.clone(), declaration.clone());
+        if is_assignment {
+            self.reassigned.insert(declaration.clone());
+        }
+    }
+
+    // Returns the upvalue index of the capture, reusing the existing one
+    pub(crate) fn capture(&mut self, function: &Span, address: Resolution) -> usize {
+        let captures = self.captures.entry(function.clone()).or_default();
+
+        match captures.iter().position(|capture| capture == &address) {
+            Some(index) => index,
+            None => {
+                captures.push(address);
+                captures.len() - 1
+            }
+        }
+    }
+}
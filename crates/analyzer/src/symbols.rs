@@ -0,0 +1,60 @@
+use common::ProgramText;
+use parser::parse::Span;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolKind {
+    Builtin,
+    Variable,
+    Parameter,
+    Closure,
+    Function,
+    Class,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Symbol {
+    pub name: ProgramText,
+    pub kind: SymbolKind,
+    // Builtins aren't declared anywhere in the source
+    pub declaration: Option<Span>,
+}
+
+impl Symbol {
+    // Variables can't be used in their own initializers, functions can call themselves
+    fn is_visible_at(&self, offset: usize) -> bool {
+        match (&self.declaration, self.kind) {
+            (None, _) => true,
+            (Some(declaration), SymbolKind::Variable | SymbolKind::Closure) => {
+                declaration.end <= offset
+            }
+            (Some(declaration), _) => declaration.start < offset,
+        }
+    }
+}
+
+// Variables declared directly in one of the program's scopes, kept after the analyzer leaves it
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScopeSymbols {
+    pub span: Span,
+    pub symbols: Vec<Symbol>,
+}
+
+// Every name that can be used at the position, without the shadowed ones
+pub fn visible_at(scopes: &[ScopeSymbols], offset: usize) -> Vec<&Symbol> {
+    let mut around: Vec<&ScopeSymbols> = scopes
+        .iter()
+        .filter(|scope| scope.span.start <= offset && offset <= scope.span.end)
+        .collect();
+    // Innermost scopes are the shortest ones
+    around.sort_by_key(|scope| scope.span.end - scope.span.start);
+
+    let mut visible: Vec<&Symbol> = Vec::new();
+    for symbol in around.iter().flat_map(|scope| &scope.symbols) {
+        let shadowed = visible.iter().any(|other| other.name == symbol.name);
+        if !shadowed && symbol.is_visible_at(offset) {
+            visible.push(symbol);
+        }
+    }
+
+    visible
+}
@@ -0,0 +1,764 @@
+use std::{collections::HashMap, fmt};
+
+use common::{BuiltInFunction, ProgramText};
+use parser::{
+    parse::{
+        expr::{atom::AtomicValue, Expr, ExprKind},
+        operator::{BinaryOperator, UnaryOperator},
+        stmt::{Stmt, StmtKind},
+        AstRef, Params, Span,
+    },
+    utils::combine,
+};
+
+use crate::{
+    resolution::{Resolution, Resolutions},
+    warning::WarningCause,
+};
+
+// Types that keep growing after that many passes, like functions returning themselves, are given up on
+const MAX_PASSES: usize = 8;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Type {
+    Number,
+    String,
+    Bool,
+    Null,
+    // Type of every element
+    Array(Box<Type>),
+    Object,
+    // Instance of the named class
+    Instance(ProgramText),
+    Class(ProgramText),
+    Function { arity: usize, returns: Box<Type> },
+    // Expressions that never produce a value, like return or break
+    Never,
+    // Could be anything once the program runs
+    Unknown,
+}
+
+impl Type {
+    // Type that holds the values of both of them
+    pub fn join(self, other: Type) -> Type {
+        use Type::*;
+
+        match (self, other) {
+            (Never, other) | (other, Never) => other,
+            (Array(a), Array(b)) => Array(Box::new(a.join(*b))),
+            (
+                Function { arity, returns: a },
+                Function {
+                    arity: other_arity,
+                    returns: b,
+                },
+            ) if arity == other_arity => Function {
+                arity,
+                returns: Box::new(a.join(*b)),
+            },
+            (a, b) if a == b => a,
+            _ => Unknown,
+        }
+    }
+
+    // Only types that are known for sure can be wrong
+    fn differs_from(&self, expected: &Type) -> bool {
+        !matches!(self, Type::Unknown | Type::Never) && self != expected
+    }
+
+    fn is_callable(&self) -> bool {
+        matches!(
+            self,
+            Type::Function { .. } | Type::Class(_) | Type::Unknown | Type::Never
+        )
+    }
+
+    fn builtin(builtin: &BuiltInFunction) -> Self {
+        let returns = match builtin {
+            BuiltInFunction::Clock => Type::Number,
+            BuiltInFunction::Print => Type::Null,
+        };
+
+        Type::Function {
+            arity: builtin.arity(),
+            returns: Box::new(returns),
+        }
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use Type::*;
+
+        match self {
+            Number => write!(f, "Number"),
+            String => write!(f, "String"),
+            Bool => write!(f, "Bool"),
+            Null => write!(f, "Null"),
+            Array(element) => write!(f, "[{}]", element),
+            Object => write!(f, "Object"),
+            Instance(class) => write!(f, "{}", class),
+            Class(name) => write!(f, "class {}", name),
+            Function { arity, returns } => {
+                write!(f, "fn({}) -> {}", vec!["_"; *arity].join(", "), returns)
+            }
+            Never => write!(f, "Never"),
+            Unknown => write!(f, "Unknown"),
+        }
+    }
+}
+
+// Side table with the inferred types, for the tools that show them
+#[derive(Debug, Clone, Default)]
+pub struct Types {
+    // Keyed by the span of the expression
+    expressions: HashMap<Span, Type>,
+    // Keyed by the span of the declaration, holds every value the variable is given
+    declarations: HashMap<Span, Type>,
+}
+
+impl Types {
+    pub fn of(&self, expression: &Span) -> Option<&Type> {
+        self.expressions.get(expression)
+    }
+
+    pub fn of_declaration(&self, declaration: &Span) -> Option<&Type> {
+        self.declarations.get(declaration)
+    }
+
+    // Innermost expression containing the offset
+    pub fn at(&self, offset: usize) -> Option<(&Span, &Type)> {
+        self.expressions
+            .iter()
+            .filter(|(span, _)| span.contains(&offset))
+            .min_by_key(|(span, _)| span.len())
+    }
+}
+
+// Spans of calls and indexes start at the parenthesis or the bracket,
+// types are kept for the whole expression together with the callee or the target
+fn whole_span(expr: &Expr) -> Span {
+    match &*expr.kind {
+        ExprKind::Call { callee: target, .. } | ExprKind::Index { target, .. } => {
+            combine(&whole_span(target), &expr.span)
+        }
+        _ => expr.span.clone(),
+    }
+}
+
+// Flow insensitive inference, every variable has the type of all the values it's ever given.
+// Variables can be read before the analysis reaches their declaration (functions calling the ones
+// declared later), so the program is visited again until no variable changes its type.
+// Types only grow, variables that weren't given any value yet are `Never`.
+pub(crate) struct Inference<'r> {
+    resolutions: &'r Resolutions,
+    types: Types,
+    // Name and declaration of the functions surrounding the visited code, innermost last.
+    // Functions refer to themselves through their own call frame instead of the declaration.
+    functions: Vec<(Option<ProgramText>, Option<Span>)>,
+    // Values returned by the surrounding functions
+    returns: Vec<Type>,
+    // Values the surrounding loops break with
+    loops: Vec<Type>,
+    mismatches: Vec<(Span, WarningCause)>,
+}
+
+impl<'r> Inference<'r> {
+    // Returns the inferred types and the operations that would fail at runtime
+    pub(crate) fn infer(
+        ast: AstRef,
+        resolutions: &'r Resolutions,
+    ) -> (Types, Vec<(Span, WarningCause)>) {
+        let mut inference = Self {
+            resolutions,
+            types: Types::default(),
+            functions: Vec::new(),
+            returns: Vec::new(),
+            loops: Vec::new(),
+            mismatches: Vec::new(),
+        };
+
+        let mut passes = 0;
+        loop {
+            let previous = inference.types.declarations.clone();
+            // Only the last pass sees the final types
+            inference.mismatches.clear();
+            for stmt in ast {
+                inference.visit_stmt(stmt);
+            }
+            if inference.types.declarations == previous {
+                break;
+            }
+
+            passes += 1;
+            if passes >= MAX_PASSES {
+                for (declaration, ty) in inference.types.declarations.iter_mut() {
+                    if previous.get(declaration) != Some(ty) {
+                        *ty = Type::Unknown;
+                    }
+                }
+            }
+        }
+
+        (inference.types, inference.mismatches)
+    }
+
+    fn assign(&mut self, declaration: &Span, ty: Type) {
+        let declarations = &mut self.types.declarations;
+        let joined = match declarations.remove(declaration) {
+            Some(previous) => previous.join(ty),
+            None => ty,
+        };
+        declarations.insert(declaration.clone(), joined);
+    }
+
+    fn variable(&self, declaration: &Span) -> Type {
+        self.types
+            .declarations
+            .get(declaration)
+            .cloned()
+            .unwrap_or(Type::Never)
+    }
+
+    fn identifier(&self, name: &str, span: &Span) -> Type {
+        if let Some(declaration) = self.resolutions.declaration(span) {
+            return self.variable(declaration);
+        }
+
+        match self.resolutions.identifier(span) {
+            Some(Resolution::Builtin(builtin)) => Type::builtin(builtin),
+            Some(_) => {
+                let function = self
+                    .functions
+                    .iter()
+                    .rev()
+                    .find(|(function, _)| function.as_deref() == Some(name));
+                match function {
+                    Some((_, Some(declaration))) => self.variable(declaration),
+                    _ => Type::Unknown,
+                }
+            }
+            None => Type::Unknown,
+        }
+    }
+
+    fn expect(
+        &mut self,
+        span: &Span,
+        operator: String,
+        expected: Type,
+        operands: &[(&Expr, &Type)],
+    ) {
+        let operands: Vec<(Span, Type)> = operands
+            .iter()
+            .filter(|(_, ty)| ty.differs_from(&expected))
+            .map(|(operand, ty)| (whole_span(operand), (*ty).clone()))
+            .collect();
+
+        if !operands.is_empty() {
+            self.mismatches.push((
+                span.clone(),
+                WarningCause::MismatchedTypes {
+                    operator,
+                    expected,
+                    operands,
+                },
+            ));
+        }
+    }
+
+    fn visit_function(
+        &mut self,
+        name: Option<&ProgramText>,
+        declaration: Option<&Span>,
+        params: &Params,
+        body: &Expr,
+    ) -> Type {
+        // Nothing is known about the arguments
+        for param in &params.kind {
+            self.assign(&param.span, Type::Unknown);
+        }
+        self.functions.push((name.cloned(), declaration.cloned()));
+        self.returns.push(Type::Never);
+        // Breaking out of a closure doesn't stop the loop it was created in
+        let loops = std::mem::take(&mut self.loops);
+
+        let body = self.visit_expr(body);
+
+        self.loops = loops;
+        let returns = self.returns.pop().unwrap().join(body);
+        self.functions.pop();
+
+        Type::Function {
+            arity: params.kind.len(),
+            returns: Box::new(returns),
+        }
+    }
+
+    // Values assigned to indexes become elements of the array variable, `m[0][1] = v` makes `m`
+    // hold arrays of arrays of `v`
+    fn widen_elements(&mut self, target: &Expr, element: Type) {
+        if let ExprKind::Index { target, .. } = &*target.kind {
+            let array = Type::Array(Box::new(element));
+            match self.resolutions.declaration(&target.span) {
+                Some(declaration) => {
+                    let declaration = declaration.clone();
+                    self.assign(&declaration, array);
+                }
+                None => self.widen_elements(target, array),
+            }
+        }
+    }
+
+    // Returns the type of expression statements, declarations don't have any value
+    fn visit_stmt(&mut self, stmt: &Stmt) -> Type {
+        use StmtKind::*;
+
+        match &*stmt.kind {
+            VariableDeclaration { expr, .. } => {
+                let ty = self.visit_expr(expr);
+                self.assign(&stmt.span, ty);
+            }
+            FunctionDeclaration {
+                name, params, body, ..
+            } => {
+                let ty = self.visit_function(Some(name), Some(&stmt.span), params, body);
+                self.assign(&stmt.span, ty);
+            }
+            ClassDeclaration { name, methods, .. } => {
+                self.assign(&stmt.span, Type::Class(name.clone()));
+                for method in methods {
+                    if let FunctionDeclaration {
+                        name, params, body, ..
+                    } = &*method.kind
+                    {
+                        self.visit_function(Some(name), None, params, body);
+                    }
+                }
+            }
+            Expression { expr } => return self.visit_expr(expr),
+        }
+
+        Type::Null
+    }
+
+    fn visit_expr(&mut self, expr: &Expr) -> Type {
+        let ty = self.infer_expr(expr);
+        // Outer expressions are visited last, so they win when the spans are the same
+        self.types.expressions.insert(whole_span(expr), ty.clone());
+        ty
+    }
+
+    fn infer_expr(&mut self, expr: &Expr) -> Type {
+        use ExprKind::*;
+        let span = &expr.span;
+
+        match &*expr.kind {
+            Atom(AtomicValue::Number(_)) => Type::Number,
+            Atom(AtomicValue::Text(_)) => Type::String,
+            Atom(AtomicValue::Boolean(_)) => Type::Bool,
+            Atom(AtomicValue::Identifier { name, .. }) => self.identifier(name, span),
+            Binary { lhs, op, rhs } => {
+                use BinaryOperator::*;
+                let lhs_type = self.visit_expr(lhs);
+                let rhs_type = self.visit_expr(rhs);
+
+                let (expected, result) = match op.kind {
+                    // Strings are concatenated, so the other operand has to be a string too
+                    Addition if lhs_type == Type::String || rhs_type == Type::String => {
+                        (Type::String, Type::String)
+                    }
+                    Addition | Subtraction | Multiplication | Division | Modulo | Power => {
+                        (Type::Number, Type::Number)
+                    }
+                    LesserThan | LesserEquals | GreaterThan | GreaterEquals => {
+                        (Type::Number, Type::Bool)
+                    }
+                    And | Or => (Type::Bool, Type::Bool),
+                    // Values of different types are simply not equal
+                    Equals | NotEquals => return Type::Bool,
+                };
+                self.expect(
+                    span,
+                    op.kind.to_string(),
+                    expected,
+                    &[(lhs, &lhs_type), (rhs, &rhs_type)],
+                );
+                result
+            }
+            Unary { op, rhs } => {
+                let rhs_type = self.visit_expr(rhs);
+                let expected = match op.kind {
+                    UnaryOperator::Negate => Type::Number,
+                    UnaryOperator::Not => Type::Bool,
+                };
+                self.expect(
+                    span,
+                    op.kind.to_string(),
+                    expected.clone(),
+                    &[(rhs, &rhs_type)],
+                );
+                expected
+            }
+            Block { stmts, return_expr } => {
+                let mut diverges = false;
+                for stmt in stmts {
+                    diverges |= self.visit_stmt(stmt) == Type::Never;
+                }
+                let value = match return_expr {
+                    Some(expr) => self.visit_expr(expr),
+                    None => Type::Null,
+                };
+
+                if diverges {
+                    Type::Never
+                } else {
+                    value
+                }
+            }
+            If {
+                condition,
+                body,
+                else_expr,
+            } => {
+                self.visit_expr(condition);
+                let body = self.visit_expr(body);
+                match else_expr {
+                    Some(else_expr) => {
+                        let else_type = self.visit_expr(else_expr);
+                        body.join(else_type)
+                    }
+                    None => Type::Unknown,
+                }
+            }
+            While { condition, body } => {
+                self.visit_expr(condition);
+                self.loops.push(Type::Null);
+                self.visit_expr(body);
+                self.loops.pop().unwrap()
+            }
+            Break { return_expr } => {
+                let value = match return_expr {
+                    Some(expr) => self.visit_expr(expr),
+                    None => Type::Null,
+                };
+                if let Some(breaks) = self.loops.pop() {
+                    self.loops.push(breaks.join(value));
+                }
+                Type::Never
+            }
+            Continue => Type::Never,
+            Return { value } => {
+                let value = match value {
+                    Some(expr) => self.visit_expr(expr),
+                    None => Type::Null,
+                };
+                if let Some(returns) = self.returns.pop() {
+                    self.returns.push(returns.join(value));
+                }
+                Type::Never
+            }
+            Call { callee, args } => {
+                let callee_type = self.visit_expr(callee);
+                for arg in args {
+                    self.visit_expr(arg);
+                }
+
+                match callee_type {
+                    Type::Function { returns, .. } => *returns,
+                    Type::Class(name) => Type::Instance(name),
+                    found if !found.is_callable() => {
+                        self.mismatches.push((
+                            whole_span(expr),
+                            WarningCause::NotCallable {
+                                callee: whole_span(callee),
+                                found,
+                            },
+                        ));
+                        Type::Unknown
+                    }
+                    _ => Type::Unknown,
+                }
+            }
+            Array { values } if values.is_empty() => Type::Array(Box::new(Type::Unknown)),
+            Array { values } => {
+                let element = values.iter().fold(Type::Never, |element, value| {
+                    element.join(self.visit_expr(value))
+                });
+                Type::Array(Box::new(element))
+            }
+            Index { target, position } => {
+                let target = self.visit_expr(target);
+                self.visit_expr(position);
+                match target {
+                    Type::Array(element) => *element,
+                    _ => Type::Unknown,
+                }
+            }
+            GetProperty { target, .. } => {
+                self.visit_expr(target);
+                Type::Unknown
+            }
+            SetProperty { target, value, .. } => {
+                self.visit_expr(target);
+                self.visit_expr(value)
+            }
+            ObjectLiteral { properties } => {
+                for (_, value) in properties {
+                    self.visit_expr(value);
+                }
+                Type::Object
+            }
+            Assignment { target, value } => {
+                let value = self.visit_expr(value);
+                match self.resolutions.declaration(&target.span) {
+                    Some(declaration) => {
+                        let declaration = declaration.clone();
+                        self.assign(&declaration, value.clone());
+                        self.types
+                            .expressions
+                            .insert(target.span.clone(), self.variable(&declaration));
+                    }
+                    None => {
+                        self.visit_expr(target);
+                        self.widen_elements(target, value.clone());
+                    }
+                }
+                value
+            }
+            Closure { params, body } => self.visit_function(None, None, params, body),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use common::CompilerDiagnostic;
+    use parser::parse;
+
+    use super::*;
+    use crate::Analyzer;
+
+    fn analyze(code: &str) -> Analyzer {
+        let ast = parse(code).unwrap();
+        let mut analyzer = Analyzer::new();
+        analyzer.analyze(&ast).unwrap();
+        analyzer
+    }
+
+    // Type of the last occurrence of the text in the code
+    fn type_of(code: &str, text: &str) -> Type {
+        let start = code.rfind(text).unwrap();
+        analyze(code)
+            .types()
+            .of(&(start..start + text.len()))
+            .cloned()
+            .unwrap()
+    }
+
+    fn mismatches(code: &str) -> Vec<WarningCause> {
+        analyze(code)
+            .warnings()
+            .iter()
+            .map(|warning| warning.cause.clone())
+            .filter(|cause| {
+                matches!(
+                    cause,
+                    WarningCause::MismatchedTypes { .. } | WarningCause::NotCallable { .. }
+                )
+            })
+            .collect()
+    }
+
+    fn function(arity: usize, returns: Type) -> Type {
+        Type::Function {
+            arity,
+            returns: Box::new(returns),
+        }
+    }
+
+    #[test]
+    fn infers_expression_types() {
+        assert_eq!(type_of("1 + 2 * 3;", "1 + 2 * 3"), Type::Number);
+        assert_eq!(type_of("\"foo\";", "\"foo\""), Type::String);
+        assert_eq!(
+            type_of("\"foo\" + \"bar\";", "\"foo\" + \"bar\""),
+            Type::String
+        );
+        assert_eq!(
+            type_of("1 < 2 and 2 != \"foo\";", "1 < 2 and 2 != \"foo\""),
+            Type::Bool
+        );
+        assert_eq!(type_of("!true;", "!true"), Type::Bool);
+        assert_eq!(type_of("let a = 5; a;", "a"), Type::Number);
+        assert_eq!(
+            type_of(
+                "if true { 1 } else { \"foo\" };",
+                "if true { 1 } else { \"foo\" }"
+            ),
+            Type::Unknown
+        );
+        assert_eq!(
+            type_of("[1, 2][0];", "[1, 2]"),
+            Type::Array(Box::new(Type::Number))
+        );
+        assert_eq!(type_of("[1, 2][0];", "[1, 2][0]"), Type::Number);
+        assert_eq!(type_of("clock();", "clock()"), Type::Number);
+        assert_eq!(type_of("print(1);", "print(1)"), Type::Null);
+        assert_eq!(
+            type_of("class Foo {} Foo();", "Foo()"),
+            Type::Instance("Foo".to_owned())
+        );
+        assert_eq!(type_of("new { a: 1 };", "new { a: 1 }"), Type::Object);
+    }
+
+    #[test]
+    fn infers_function_types() {
+        assert_eq!(
+            type_of("|a, b| => a + b;", "|a, b| => a + b"),
+            function(2, Type::Number)
+        );
+        assert_eq!(
+            type_of(
+                "fn foo(a) { if a { return \"a\"; } \"b\" } foo(1);",
+                "foo(1)"
+            ),
+            Type::String
+        );
+        assert_eq!(
+            type_of("fn bar() => 1 + 1 fn foo() => bar() == 1 foo();", "foo()"),
+            Type::Bool
+        );
+        assert_eq!(
+            type_of(
+                "fn fact(n) => if n < 2 { 1 } else { n * fact(n - 1) } fact(5);",
+                "fact(5)"
+            ),
+            Type::Number
+        );
+        // returns itself forever
+        assert_eq!(type_of("fn foo() => foo foo;", "foo"), Type::Unknown);
+    }
+
+    #[test]
+    fn joins_every_value_of_variables() {
+        let code = "let a = 1; a = 2; a;";
+        assert_eq!(type_of(code, "a"), Type::Number);
+        let code = "let a = 1; let b = |_x| => { a = \"foo\"; }; a;";
+        assert_eq!(type_of(code, "a"), Type::Unknown);
+
+        // next iterations of the loop see `a` holding a string
+        let code = "let a = 1; let b = a; while b < 2 { b = a; a = \"foo\"; }; b;";
+        assert_eq!(type_of(code, "b"), Type::Unknown);
+
+        let code = "let a = [1]; a[0] = 2; a;";
+        assert_eq!(type_of(code, "a"), Type::Array(Box::new(Type::Number)));
+        let code = "let a = [[1]]; a[0][0] = \"foo\"; a;";
+        assert_eq!(
+            type_of(code, "a"),
+            Type::Array(Box::new(Type::Array(Box::new(Type::Unknown))))
+        );
+
+        let code = "let a = 1; a = \"foo\";";
+        let declaration = 0..code.find(';').unwrap() + 1;
+        assert_eq!(
+            analyze(code).types().of_declaration(&declaration),
+            Some(&Type::Unknown)
+        );
+    }
+
+    #[test]
+    fn finds_types_at_offsets() {
+        let code = "let a = 1 + 2 * 3;";
+        let analyzer = analyze(code);
+
+        assert_eq!(
+            analyzer.types().at(code.find('*').unwrap()),
+            Some((&(12..17), &Type::Number))
+        );
+        assert_eq!(
+            analyzer.types().at(code.find('1').unwrap()),
+            Some((&(8..9), &Type::Number))
+        );
+        assert_eq!(analyzer.types().at(0), None);
+    }
+
+    #[test]
+    fn notes_that_only_strings_are_joined() {
+        let notes = |code| {
+            analyze(code)
+                .warnings()
+                .iter()
+                .flat_map(|warning| warning.report(0).notes)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            notes("\"foo\" + 1;"),
+            vec!["both operands of '+' must be strings to join them".to_owned()]
+        );
+        assert_eq!(notes("\"foo\" - \"bar\";"), Vec::<String>::new());
+        assert_eq!(notes("1 + true;"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn reports_mismatched_types() {
+        assert_eq!(
+            mismatches("\"foo\" + 1;"),
+            vec![WarningCause::MismatchedTypes {
+                operator: "+".to_owned(),
+                expected: Type::String,
+                operands: vec![(8..9, Type::Number)],
+            }]
+        );
+        assert_eq!(
+            mismatches("\"foo\" - \"bar\";"),
+            vec![WarningCause::MismatchedTypes {
+                operator: "-".to_owned(),
+                expected: Type::Number,
+                operands: vec![(0..5, Type::String), (8..13, Type::String)],
+            }]
+        );
+        assert_eq!(
+            mismatches("let a = true; -a;"),
+            vec![WarningCause::MismatchedTypes {
+                operator: "-".to_owned(),
+                expected: Type::Number,
+                operands: vec![(15..16, Type::Bool)],
+            }]
+        );
+        assert_eq!(
+            mismatches("1 and true;"),
+            vec![WarningCause::MismatchedTypes {
+                operator: "and".to_owned(),
+                expected: Type::Bool,
+                operands: vec![(0..1, Type::Number)],
+            }]
+        );
+        assert_eq!(
+            mismatches("fn foo() => \"foo\" foo() < 1;"),
+            vec![WarningCause::MismatchedTypes {
+                operator: "<".to_owned(),
+                expected: Type::Number,
+                operands: vec![(18..23, Type::String)],
+            }]
+        );
+        assert_eq!(
+            mismatches("let a = 1; a();"),
+            vec![WarningCause::NotCallable {
+                callee: 11..12,
+                found: Type::Number,
+            }]
+        );
+        // nothing is known for sure
+        assert_eq!(
+            mismatches("fn foo(a) => a + 1 foo(\"a\"); 1 == \"a\";"),
+            vec![]
+        );
+        assert_eq!(mismatches("let a = 1; a = \"a\"; a + 1;"), vec![]);
+        assert_eq!(
+            mismatches("let fs = [0, 0]; fs[0] = || => 1; print(fs[0]());"),
+            vec![]
+        );
+    }
+}
@@ -0,0 +1,221 @@
+use codespan_reporting::diagnostic::{Diagnostic, Label, Severity};
+use common::{CompilerDiagnostic, ProgramText};
+use parser::parse::Span;
+
+use crate::lint::{
+    Lint, LintLevel, CAPTURED_LOOP_VARIABLE, METRIC_THRESHOLD, MISSING_RETURN, REDECLARED_VARIABLE,
+    SHADOWED_VARIABLE, TYPE_MISMATCH, UNREACHABLE_CODE, UNUSED_CLOSURE, UNUSED_PARAMETER,
+    UNUSED_VARIABLE,
+};
+use crate::metrics::Metric;
+use crate::types::Type;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Warning {
+    pub span: Span,
+    pub cause: WarningCause,
+    pub level: LintLevel,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum WarningCause {
+    UnusedVariable(ProgramText),
+    UnusedParameter(ProgramText),
+    UnusedClosure(ProgramText),
+    // Span of the shadowed declaration, builtins don't have any
+    ShadowedVariable {
+        name: ProgramText,
+        shadowed: Option<Span>,
+    },
+    RedeclaredVariable {
+        name: ProgramText,
+        previous: Span,
+    },
+    // Span of the statement that makes the code unreachable
+    UnreachableCode {
+        diverging: Span,
+    },
+    MissingReturn(ProgramText),
+    // Reassigned variable captured by a closure that is created inside a loop
+    CapturedLoopVariable {
+        name: ProgramText,
+        declaration: Span,
+    },
+    MetricExceeded {
+        function: ProgramText,
+        metric: Metric,
+        value: usize,
+        limit: usize,
+    },
+    // Span and type of every operand that isn't of the expected type
+    MismatchedTypes {
+        operator: ProgramText,
+        expected: Type,
+        operands: Vec<(Span, Type)>,
+    },
+    NotCallable {
+        callee: Span,
+        found: Type,
+    },
+}
+
+impl WarningCause {
+    pub fn lint(&self) -> &'static Lint {
+        use WarningCause::*;
+
+        match self {
+            UnusedVariable(_) => &UNUSED_VARIABLE,
+            UnusedParameter(_) => &UNUSED_PARAMETER,
+            UnusedClosure(_) => &UNUSED_CLOSURE,
+            ShadowedVariable { .. } => &SHADOWED_VARIABLE,
+            RedeclaredVariable { .. } => &REDECLARED_VARIABLE,
+            UnreachableCode { .. } => &UNREACHABLE_CODE,
+            MissingReturn(_) => &MISSING_RETURN,
+            CapturedLoopVariable { .. } => &CAPTURED_LOOP_VARIABLE,
+            MetricExceeded { .. } => &METRIC_THRESHOLD,
+            MismatchedTypes { .. } | NotCallable { .. } => &TYPE_MISMATCH,
+        }
+    }
+}
+
+impl CompilerDiagnostic for Warning {
+    fn report(&self, file_id: usize) -> Diagnostic<usize> {
+        let severity = if self.is_denied() {
+            Severity::Error
+        } else {
+            Severity::Warning
+        };
+
+        self.describe(Diagnostic::new(severity), file_id)
+            .with_code(self.cause.lint().id)
+    }
+}
+
+impl Warning {
+    pub fn is_denied(&self) -> bool {
+        self.level == LintLevel::Deny
+    }
+
+    fn describe(&self, diagnostic: Diagnostic<usize>, file_id: usize) -> Diagnostic<usize> {
+        use WarningCause::*;
+        let span = self.span.clone();
+
+        match &self.cause {
+            UnusedVariable(name) => diagnostic
+                .with_message(format!("Variable '{}' is never used", name))
+                .with_labels(vec![Label::primary(file_id, span)])
+                .with_notes(vec![prefix_note(name)]),
+            UnusedParameter(name) => diagnostic
+                .with_message(format!("Parameter '{}' is never used", name))
+                .with_labels(vec![Label::primary(file_id, span)])
+                .with_notes(vec![prefix_note(name)]),
+            UnusedClosure(name) => diagnostic
+                .with_message(format!("Closure '{}' is never used", name))
+                .with_labels(vec![Label::primary(file_id, span)])
+                .with_notes(vec![prefix_note(name)]),
+            ShadowedVariable { name, shadowed } => {
+                let mut labels =
+                    vec![Label::primary(file_id, span).with_message("...is shadowed here")];
+                if let Some(shadowed) = shadowed {
+                    labels.push(
+                        Label::secondary(file_id, shadowed.clone())
+                            .with_message("Variable declared here..."),
+                    );
+                }
+
+                diagnostic
+                    .with_message(format!("Variable '{}' shadows an outer variable", name))
+                    .with_labels(labels)
+                    .with_notes(vec![prefix_note(name)])
+            }
+            RedeclaredVariable { name, previous } => diagnostic
+                .with_message(format!("Variable '{}' is declared again", name))
+                .with_labels(vec![
+                    Label::primary(file_id, span).with_message("...and replaced here"),
+                    Label::secondary(file_id, previous.clone())
+                        .with_message("Variable declared here..."),
+                ])
+                .with_notes(vec![prefix_note(name)]),
+            UnreachableCode { diverging } => diagnostic
+                .with_message("Unreachable code")
+                .with_labels(vec![
+                    Label::primary(file_id, span).with_message("...so this code never runs"),
+                    Label::secondary(file_id, diverging.clone())
+                        .with_message("Control never gets past this..."),
+                ]),
+            MissingReturn(name) => diagnostic
+                .with_message(format!(
+                    "Function '{}' doesn't return a value on every path",
+                    name
+                ))
+                .with_labels(vec![Label::primary(file_id, span)
+                    .with_message("some paths reach the end without returning")]),
+            CapturedLoopVariable { name, declaration } => diagnostic
+                .with_message(format!(
+                    "Closure created inside a loop captures '{}', which is reassigned",
+                    name
+                ))
+                .with_labels(vec![
+                    Label::primary(file_id, span).with_message("...and captured here"),
+                    Label::secondary(file_id, declaration.clone())
+                        .with_message("Variable declared outside of the loop..."),
+                ])
+                .with_notes(vec![format!(
+                    "every closure created by the loop shares the same '{}', \
+                     copy it into a variable declared inside the loop to keep its current value",
+                    name
+                )]),
+            MetricExceeded {
+                function,
+                metric,
+                value,
+                limit,
+            } => diagnostic
+                .with_message(format!(
+                    "Function '{}' has {} of {}, above the limit of {}",
+                    function, metric, value, limit
+                ))
+                .with_labels(vec![Label::primary(file_id, span)])
+                .with_notes(vec![
+                    "limits are set in the [metrics] section of gravitas.toml".to_owned(),
+                ]),
+            MismatchedTypes {
+                operator,
+                expected,
+                operands,
+            } => {
+                let mut labels = vec![Label::primary(file_id, span)
+                    .with_message("...so this fails when the program runs")];
+                labels.extend(operands.iter().map(|(operand, found)| {
+                    Label::secondary(file_id, operand.clone())
+                        .with_message(format!("This is {}...", found))
+                }));
+                // Many languages turn the other operand into a string, this one doesn't
+                let notes = if operator == "+" && expected == &Type::String {
+                    vec!["both operands of '+' must be strings to join them".to_owned()]
+                } else {
+                    Vec::new()
+                };
+
+                diagnostic
+                    .with_message(format!("Operator '{}' expects {}", operator, expected))
+                    .with_labels(labels)
+                    .with_notes(notes)
+            }
+            NotCallable { callee, found } => diagnostic
+                .with_message(format!("Value of type {} can't be called", found))
+                .with_labels(vec![
+                    Label::primary(file_id, span).with_message("...so this call fails"),
+                    Label::secondary(file_id, callee.clone())
+                        .with_message(format!("This is {}...", found)),
+                ]),
+        }
+    }
+}
+
+fn prefix_note(name: &str) -> String {
+    format!(
+        "prefix it with an underscore to silence this warning: '_{}'",
+        name
+    )
+}
@@ -1,26 +1,25 @@
-use std::{collections::HashMap, fmt::Display};
+use std::fmt::Display;
 
 use common::ProgramText;
 use prettytable::Table;
 
-use crate::{
-    chunk::{chunk_into_rows, Chunk},
-    stmt::GlobalPointer,
-};
+use crate::chunk::{chunk_into_rows, Chunk};
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct Function {
     pub arity: usize,
     pub chunk: Chunk,
     pub name: ProgramText,
+    // Has no side effects, so calls with the same arguments always give the same result
+    pub pure: bool,
 }
 
 impl Display for Function {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let mut table = Table::new();
 
-        table.add_row(row!["Name", "Arity"]);
-        table.add_row(row![self.name, self.arity]);
+        table.add_row(row!["Name", "Arity", "Pure"]);
+        table.add_row(row![self.name, self.arity, self.pure]);
 
         for row in chunk_into_rows(self.chunk.clone()) {
             table.add_row(row);
@@ -1,7 +1,8 @@
 use std::fmt::Display;
 
 use crate::{stmt::GlobalPointer, MemoryAddress, Opcode};
-use common::{Number, ProgramText};
+use common::{source::Location, Number, ProgramText};
+use parser::parse::Span;
 use prettytable::Row;
 
 #[derive(Clone, Debug, PartialEq)]
@@ -35,6 +36,98 @@ pub type OpcodeIndex = usize;
 pub struct Chunk {
     pub opcodes: Vec<Opcode>,
     pub constants: Vec<Constant>,
+    pub spans: SpanTable,
+}
+
+// Source spans of the opcodes. Consecutive opcodes often come from the same expression,
+// so a span is stored once, together with the first opcode it applies to.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SpanTable {
+    // Name of the source file, known once the spans are located in it
+    file: Option<String>,
+    entries: Vec<(OpcodeIndex, SourceSpan)>,
+}
+
+// Generated opcodes know the byte range of their source, locating them in the source file adds
+// the line and column. Bytecode files only keep those, as they're run without their source.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceSpan {
+    pub range: Option<Span>,
+    pub location: Option<Location>,
+}
+
+impl From<Span> for SourceSpan {
+    fn from(range: Span) -> Self {
+        Self {
+            range: Some(range),
+            location: None,
+        }
+    }
+}
+
+impl SpanTable {
+    pub fn new(file: Option<String>, entries: Vec<(OpcodeIndex, SourceSpan)>) -> Self {
+        Self { file, entries }
+    }
+
+    // Opcodes have to be recorded in order
+    pub fn record(&mut self, index: OpcodeIndex, span: impl Into<SourceSpan>) {
+        let span = span.into();
+        match self.entries.last() {
+            Some((_, last_span)) if *last_span == span => {}
+            _ => self.entries.push((index, span)),
+        }
+    }
+
+    // Forgets the spans of opcodes from the given index onwards
+    pub fn truncate(&mut self, length: usize) {
+        let kept = self.entries.partition_point(|(start, _)| *start < length);
+        self.entries.truncate(kept);
+    }
+
+    // Finds the line and column of every span, the given function turns byte offsets into them
+    pub fn locate(&mut self, file: &str, location: impl Fn(usize) -> Location) {
+        self.file = Some(file.to_owned());
+        for (_, span) in &mut self.entries {
+            if let Some(range) = &span.range {
+                span.location = Some(location(range.start));
+            }
+        }
+    }
+
+    pub fn span_at(&self, index: OpcodeIndex) -> Option<&SourceSpan> {
+        let following = self.entries.partition_point(|(start, _)| *start <= index);
+        following.checked_sub(1).map(|entry| &self.entries[entry].1)
+    }
+
+    pub fn range_at(&self, index: OpcodeIndex) -> Option<&Span> {
+        self.span_at(index)?.range.as_ref()
+    }
+
+    // Where the opcode comes from as `file:line:column`, once the spans are located
+    pub fn location_at(&self, index: OpcodeIndex) -> Option<String> {
+        let file = self.file.as_ref()?;
+        let location = self.span_at(index)?.location?;
+        Some(format!("{}:{}:{}", file, location.line, location.column))
+    }
+
+    pub fn file(&self) -> Option<&str> {
+        self.file.as_deref()
+    }
+
+    pub fn entries(&self) -> &[(OpcodeIndex, SourceSpan)] {
+        &self.entries
+    }
+}
+
+impl From<Vec<(OpcodeIndex, Span)>> for SpanTable {
+    fn from(entries: Vec<(OpcodeIndex, Span)>) -> Self {
+        let entries = entries
+            .into_iter()
+            .map(|(index, range)| (index, range.into()))
+            .collect();
+        Self::new(None, entries)
+    }
 }
 
 pub(crate) fn chunk_into_rows(chunk: Chunk) -> Vec<Row> {
@@ -63,7 +156,11 @@ pub(crate) fn chunk_into_rows(chunk: Chunk) -> Vec<Row> {
 
 impl Chunk {
     pub fn new(opcodes: Vec<Opcode>, constants: Vec<Constant>) -> Self {
-        Self { opcodes, constants }
+        Self {
+            opcodes,
+            constants,
+            spans: SpanTable::default(),
+        }
     }
 
     pub fn read(&self, index: ConstantIndex) -> Constant {
@@ -88,6 +185,12 @@ impl Chunk {
         length
     }
 
+    pub fn pop_opcode(&mut self) -> Option<Opcode> {
+        let opcode = self.opcodes.pop();
+        self.spans.truncate(self.opcodes.len());
+        opcode
+    }
+
     pub fn read_opcode(&self, index: OpcodeIndex) -> Opcode {
         self.opcodes[index]
     }
@@ -99,6 +202,8 @@ impl Chunk {
 
 #[cfg(test)]
 mod test {
+    use common::source::SourceDatabase;
+
     use super::*;
 
     #[test]
@@ -110,6 +215,7 @@ mod test {
                 Constant::Bool(false),
                 Constant::Bool(true),
             ],
+            spans: SpanTable::default(),
         };
 
         assert_eq!(chunk.read(0), Constant::Number(10.0));
@@ -135,4 +241,37 @@ mod test {
         assert_eq!(chunk.read_opcode(0), Opcode::Add);
         assert_eq!(chunk.read_opcode(first), Opcode::Add);
     }
+
+    #[test]
+    fn finds_spans_of_opcodes() {
+        let mut spans = SpanTable::default();
+        spans.record(0, 0..5);
+        spans.record(1, 0..5);
+        spans.record(2, 2..3);
+        spans.record(3, 0..5);
+
+        assert_eq!(
+            spans,
+            SpanTable::from(vec![(0, 0..5), (2, 2..3), (3, 0..5)])
+        );
+        assert_eq!(spans.range_at(1), Some(&(0..5)));
+        assert_eq!(spans.range_at(2), Some(&(2..3)));
+        assert_eq!(spans.range_at(10), Some(&(0..5)));
+        assert_eq!(SpanTable::default().span_at(0), None);
+    }
+
+    #[test]
+    fn locates_spans_in_their_file() {
+        let mut db = SourceDatabase::new();
+        let file_id = db.add("main.vt", "let a = 1;\nprint(a);");
+        let mut spans = SpanTable::from(vec![(0, 8..9), (1, 17..18)]);
+        assert_eq!(spans.location_at(0), None);
+
+        spans.locate(db.name(file_id), |offset| db.location(file_id, offset));
+        assert_eq!(spans.file(), Some("main.vt"));
+        assert_eq!(spans.location_at(0), Some("main.vt:1:9".to_owned()));
+        assert_eq!(spans.location_at(5), Some("main.vt:2:7".to_owned()));
+        // Ranges are kept for the tools that have the source
+        assert_eq!(spans.range_at(1), Some(&(17..18)));
+    }
 }
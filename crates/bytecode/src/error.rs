@@ -0,0 +1,131 @@
+use codespan_reporting::diagnostic::{Diagnostic, Label};
+use common::CompilerDiagnostic;
+use parser::parse::Span;
+
+use crate::{MAX_CONSTANTS, MAX_JUMP, MAX_LOCALS, MAX_UPVALUES};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GenerationError {
+    pub span: Span,
+    pub cause: GenerationErrorCause,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum GenerationErrorCause {
+    // Function's chunk refers to more constants than an opcode can address
+    TooManyConstants,
+    // Jump over more opcodes than an opcode can encode
+    JumpTooLong,
+    // Valid code that the VM can't run yet, e.g. classes
+    Unsupported(&'static str),
+    TooManyLocals,
+    TooManyUpvalues,
+}
+
+impl CompilerDiagnostic for GenerationError {
+    fn report(&self, file_id: usize) -> Diagnostic<usize> {
+        use GenerationErrorCause::*;
+        let span = self.span.clone();
+
+        match &self.cause {
+            TooManyConstants => Diagnostic::error()
+                .with_message(format!(
+                    "Function uses more than {} constants",
+                    MAX_CONSTANTS
+                ))
+                .with_labels(vec![Label::primary(file_id, span)])
+                .with_notes(vec!["try splitting it into smaller functions".to_owned()]),
+            JumpTooLong => Diagnostic::error()
+                .with_message(format!("Jump is longer than {} opcodes", MAX_JUMP))
+                .with_labels(vec![
+                    Label::primary(file_id, span).with_message("this is too long to jump over")
+                ])
+                .with_notes(vec!["try moving parts of it into functions".to_owned()]),
+            Unsupported(construct) => Diagnostic::error()
+                .with_message(format!("{} can't be compiled yet", construct))
+                .with_labels(vec![Label::primary(file_id, span)]),
+            TooManyLocals => Diagnostic::error()
+                .with_message(format!(
+                    "Function has more than {} local variables",
+                    MAX_LOCALS
+                ))
+                .with_labels(vec![
+                    Label::primary(file_id, span).with_message("this variable doesn't fit")
+                ]),
+            TooManyUpvalues => Diagnostic::error()
+                .with_message(format!(
+                    "Function captures more than {} variables",
+                    MAX_UPVALUES
+                ))
+                .with_labels(vec![Label::primary(file_id, span)]),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::test::try_generate_program;
+
+    use super::GenerationErrorCause::{self, *};
+
+    fn error(code: &str) -> (String, GenerationErrorCause) {
+        match try_generate_program(code) {
+            Err(error) => (code[error.span].to_owned(), error.cause),
+            Ok(_) => panic!("Generation should fail"),
+        }
+    }
+
+    fn numbers(amount: usize) -> String {
+        vec!["1"; amount].join(", ")
+    }
+
+    #[test]
+    fn reports_unsupported_constructs() {
+        assert_eq!(
+            error("let a = 1; class Foo {}"),
+            ("class Foo {}".to_owned(), Unsupported("Classes"))
+        );
+    }
+
+    #[test]
+    fn reports_functions_that_dont_fit() {
+        let code = format!("fn big() {{ [{}]; }}", numbers(super::MAX_CONSTANTS + 1));
+        assert_eq!(error(&code), (code.clone(), TooManyConstants));
+
+        let code = format!("if true {{ [{}]; }};", numbers(super::MAX_JUMP as usize));
+        let (span, cause) = error(&code);
+        assert_eq!((span.starts_with("if true"), cause), (true, JumpTooLong));
+    }
+
+    #[test]
+    fn reports_too_many_variables() {
+        let declarations: String = (0..=super::MAX_LOCALS)
+            .map(|index| format!("let v{} = {};", index, index))
+            .collect();
+
+        // blamed on the declaration that doesn't fit, even when it's never read
+        let (span, cause) = error(&declarations);
+        assert_eq!((span.as_str(), cause), ("let v256 = 256;", TooManyLocals));
+
+        // functions start with two slots taken by themselves and `this`
+        let code = format!("fn f() {{ {} v0; }}", declarations);
+        let (span, cause) = error(&code);
+        assert_eq!((span.as_str(), cause), ("let v254 = 254;", TooManyLocals));
+
+        // captured variables come from two frames, as neither of them fits all of them
+        let half = super::MAX_UPVALUES / 2;
+        let (outer, inner) =
+            declarations.split_at(declarations.find(&format!("let v{} ", half)).unwrap());
+        let uses: Vec<String> = (0..=super::MAX_UPVALUES)
+            .map(|index| format!("v{}", index))
+            .collect();
+        let code = format!(
+            "{} fn g() {{ {} let f = |_x| => [{}]; }}",
+            outer,
+            inner,
+            uses.join(", ")
+        );
+        let (span, cause) = error(&code);
+        assert_eq!((span.starts_with("|_x|"), cause), (true, TooManyUpvalues));
+    }
+}
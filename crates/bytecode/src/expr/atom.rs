@@ -1,8 +1,37 @@
-use parser::parse::expr::atom::AtomicValue;
+use parser::parse::{expr::atom::AtomicValue, Span};
 
-use crate::{chunk::Constant, BytecodeFrom, BytecodeGenerationResult, BytecodeGenerator, Opcode};
+use analyzer::resolution::Resolution;
 
-impl BytecodeFrom<AtomicValue> for BytecodeGenerator {
+use crate::{
+    chunk::Constant, error::GenerationErrorCause, BytecodeFrom, BytecodeGenerationResult,
+    BytecodeGenerator, MemoryAddress, Opcode, MAX_LOCALS,
+};
+
+impl BytecodeGenerator<'_> {
+    pub(crate) fn generate_identifier(
+        &mut self,
+        span: &Span,
+        is_assignment: bool,
+    ) -> BytecodeGenerationResult {
+        let resolution = self
+            .resolutions
+            .identifier(span)
+            .expect("Analyzer resolves every defined variable");
+        // Slots reserved for the function itself and `this` aren't declared anywhere
+        if matches!(resolution, Resolution::Local(slot) if *slot >= MAX_LOCALS) {
+            return self.error(span, GenerationErrorCause::TooManyLocals);
+        }
+
+        self.write_constant(MemoryAddress::from(resolution).into());
+
+        if !is_assignment {
+            self.write_opcode(Opcode::Get);
+        }
+        Ok(())
+    }
+}
+
+impl BytecodeFrom<AtomicValue> for BytecodeGenerator<'_> {
     fn generate(&mut self, data: AtomicValue) -> BytecodeGenerationResult {
         match data {
             AtomicValue::Boolean(bool) => {
@@ -14,20 +43,9 @@ impl BytecodeFrom<AtomicValue> for BytecodeGenerator {
             AtomicValue::Text(text) => {
                 self.write_constant(Constant::String(text));
             }
-            AtomicValue::Identifier {
-                name,
-                is_assignment,
-            } => {
-                let var_address = self
-                    .state
-                    .find_var_address(&name)
-                    .expect("Analyzer takes care of undefined variables");
-
-                self.write_constant(var_address.into());
-
-                if !is_assignment {
-                    self.write_opcode(Opcode::Get);
-                }
+            // Identifiers are resolved by the span of their expression
+            AtomicValue::Identifier { .. } => {
+                unreachable!("Identifiers are generated together with their expression")
             }
         };
 
@@ -41,7 +59,7 @@ mod test {
 
     use crate::{
         chunk::Constant,
-        test::{assert_bytecode_and_constants, declare_var, expr, expr_stmt},
+        test::{assert_bytecode_and_constants, generate_program},
         MemoryAddress, Opcode,
     };
 
@@ -74,22 +92,20 @@ mod test {
 
     #[test]
     fn generates_variable_identifiers() {
-        // We need to declare variable first
-        // otherwise generator won't find it inside the scope and will panic.
-        // Static analysis ensures that we won't get AST that allows it.
-        assert_bytecode_and_constants(
-            vec![
-                declare_var("foo".to_owned(), expr(AtomicValue::Text("bar".to_owned()))),
-                expr_stmt(expr(AtomicValue::Identifier {
-                    name: "foo".to_owned(),
-                    is_assignment: false,
-                })),
-            ],
-            vec![Opcode::Constant(0), Opcode::Constant(1), Opcode::Get],
+        // Addresses come from the analyzer, so the code has to be a valid program
+        let bytecode = generate_program("let foo = \"bar\"; foo;");
+        let chunk = &bytecode.main_function().chunk;
+
+        assert_eq!(
+            chunk.opcodes,
+            vec![Opcode::Constant(0), Opcode::Constant(1), Opcode::Get]
+        );
+        assert_eq!(
+            chunk.constants,
             vec![
                 Constant::String("bar".to_owned()),
                 Constant::MemoryAddress(MemoryAddress::Local(0)),
-            ],
+            ]
         );
     }
 
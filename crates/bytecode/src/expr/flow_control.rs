@@ -4,7 +4,7 @@ mod test {
 
     use crate::{
         chunk::Constant,
-        test::{assert_bytecode_and_constants, box_node, expr, expr_stmt, node},
+        test::{assert_bytecode_and_constants, box_node, expr, expr_stmt},
         Opcode,
     };
 
@@ -22,12 +22,15 @@ mod test {
             while_loop,
             vec![
                 Opcode::Constant(0),
-                Opcode::Jif(4),
+                Opcode::Jif(6),
                 Opcode::Constant(1),
+                Opcode::Pop(1),
                 Opcode::Null,
                 Opcode::Block(0),
-                Opcode::Jp(-4),
+                Opcode::Pop(1),
+                Opcode::Jp(-8),
                 Opcode::Null,
+                Opcode::Pop(1),
             ],
             vec![Constant::Bool(true), Constant::Number(0.0)],
         );
@@ -44,7 +47,7 @@ mod test {
             }),
             vec![
                 Opcode::Constant(0),
-                Opcode::Jif(3),
+                Opcode::Jif(2),
                 Opcode::Constant(1),
                 Opcode::Jp(1),
                 Opcode::Constant(2),
@@ -67,7 +70,8 @@ mod test {
                 Opcode::Constant(0),
                 Opcode::Jif(2),
                 Opcode::Constant(1),
-                Opcode::Jp(0),
+                Opcode::Jp(1),
+                Opcode::Null,
             ],
             vec![Constant::Bool(true), Constant::Bool(false)],
         );
@@ -89,12 +93,14 @@ mod test {
             data,
             vec![
                 Opcode::Constant(0),
-                Opcode::Jif(5),
+                Opcode::Jif(7),
                 Opcode::Constant(1),
-                Opcode::Break(4),
+                Opcode::Break(6),
+                Opcode::Pop(1),
                 Opcode::Null,
                 Opcode::Block(0),
-                Opcode::Jp(-5),
+                Opcode::Pop(1),
+                Opcode::Jp(-9),
                 Opcode::Null,
             ],
             vec![Constant::Bool(true), Constant::Number(5.0)],
@@ -115,11 +121,13 @@ mod test {
             data,
             vec![
                 Opcode::Constant(0),
-                Opcode::Jif(4),
-                Opcode::Jp(-1),
+                Opcode::Jif(6),
+                Opcode::Jp(-3),
+                Opcode::Pop(1),
                 Opcode::Null,
                 Opcode::Block(0),
-                Opcode::Jp(-4),
+                Opcode::Pop(1),
+                Opcode::Jp(-8),
                 Opcode::Null,
             ],
             vec![Constant::Bool(true)],
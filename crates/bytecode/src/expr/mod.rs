@@ -1,4 +1,9 @@
-use parser::parse::expr::{Expr, ExprKind};
+use parser::parse::{
+    expr::{atom::AtomicValue, Expr, ExprKind},
+    stmt::StmtKind,
+};
+
+use common::LAMBDA_NAME;
 
 use crate::{chunk::Constant, state::ScopeType, BytecodeFrom, BytecodeGenerator, Opcode};
 
@@ -7,7 +12,7 @@ mod binary;
 mod flow_control;
 mod unary;
 
-impl BytecodeFrom<Vec<Expr>> for BytecodeGenerator {
+impl BytecodeFrom<Vec<Expr>> for BytecodeGenerator<'_> {
     fn generate(&mut self, data: Vec<Expr>) -> crate::BytecodeGenerationResult {
         for expr in data {
             self.generate(expr)?;
@@ -16,9 +21,18 @@ impl BytecodeFrom<Vec<Expr>> for BytecodeGenerator {
     }
 }
 
-impl BytecodeFrom<Expr> for BytecodeGenerator {
+impl BytecodeFrom<Expr> for BytecodeGenerator<'_> {
     fn generate(&mut self, expr: Expr) -> crate::BytecodeGenerationResult {
+        self.spanned(expr.span.clone(), |generator| generator.generate_expr(expr))
+    }
+}
+
+impl BytecodeGenerator<'_> {
+    fn generate_expr(&mut self, expr: Expr) -> crate::BytecodeGenerationResult {
         match *expr.kind {
+            ExprKind::Atom(AtomicValue::Identifier { is_assignment, .. }) => {
+                self.generate_identifier(&expr.span, is_assignment)?;
+            }
             ExprKind::Atom(atomic_value) => {
                 self.generate(atomic_value)?;
             }
@@ -43,30 +57,38 @@ impl BytecodeFrom<Expr> for BytecodeGenerator {
                 let jif_patch = self.emit_patch(Opcode::Jif(0));
                 self.generate(body)?;
                 let jp_patch = self.emit_patch(Opcode::Jp(0));
-                self.patch(&jif_patch);
+                self.patch(&jif_patch, &expr.span)?;
 
+                // Both branches leave a value behind
                 if let Some(else_expr) = else_expr {
                     self.generate(else_expr)?;
+                } else {
+                    self.write_opcode(Opcode::Null);
                 }
 
-                self.patch(&jp_patch);
+                self.patch(&jp_patch, &expr.span)?;
             }
             ExprKind::While { condition, body } => {
                 self.enter_scope(ScopeType::Block);
-                let start = self.curr_index();
+                let start = self.state.current_scope().starting_index;
                 self.generate(condition)?;
 
                 let jif = self.emit_patch(Opcode::Jif(0));
                 self.generate(body)?;
+                self.write_opcode(Opcode::Pop(1));
 
-                let end = self.curr_index();
-                self.write_opcode(Opcode::Jp(-(end as isize - start as isize)));
-                self.patch(&jif);
+                self.jump_back(start, &expr.span)?;
+                self.patch(&jif, &expr.span)?;
                 // TODO: implement breaking from while loops with a value
                 self.write_opcode(Opcode::Null);
-                self.leave_scope();
+                self.leave_scope(&expr.span)?;
             }
             ExprKind::Block { stmts, return_expr } => {
+                // Every declaration takes one slot on the stack
+                let declared = stmts
+                    .iter()
+                    .filter(|stmt| !matches!(&*stmt.kind, StmtKind::Expression { .. }))
+                    .count();
                 self.generate(stmts)?;
 
                 if let Some(return_expr) = return_expr {
@@ -75,7 +97,8 @@ impl BytecodeFrom<Expr> for BytecodeGenerator {
                     self.write_opcode(Opcode::Null);
                 }
 
-                self.write_opcode(Opcode::Block(self.state.declared()));
+                self.write_opcode(Opcode::Block(declared));
+                self.state.current_scope_mut().declared -= declared;
             }
             ExprKind::Break { return_expr } => {
                 if let Some(return_expr) = return_expr {
@@ -83,17 +106,26 @@ impl BytecodeFrom<Expr> for BytecodeGenerator {
                 } else {
                     self.write_opcode(Opcode::Null);
                 }
+                // Variables declared inside of the loop are left behind
+                let declared = self.state.current_scope().declared;
+                if declared > 0 {
+                    self.write_opcode(Opcode::Block(declared));
+                }
                 self.emit_patch(Opcode::Break(0));
             }
             ExprKind::Continue => {
-                let ending_index = self.curr_index();
+                let declared = self.state.current_scope().declared;
+                if declared > 0 {
+                    self.write_opcode(Opcode::Pop(declared));
+                }
                 let starting_index = self.state.current_scope().starting_index;
-                self.write_opcode(Opcode::Jp(starting_index as isize - ending_index as isize));
+                self.jump_back(starting_index, &expr.span)?;
             }
             ExprKind::Call { callee, args } => {
+                let amount = args.len();
                 self.generate(args)?;
                 self.generate(callee)?;
-                self.write_opcode(Opcode::Call);
+                self.write_opcode(Opcode::Call(amount));
             }
             ExprKind::Return { value } => {
                 if let Some(value) = value {
@@ -103,8 +135,16 @@ impl BytecodeFrom<Expr> for BytecodeGenerator {
                 }
                 self.write_opcode(Opcode::Return);
             }
-            ExprKind::Array { values } => {}
-            ExprKind::Index { target, position } => {}
+            ExprKind::Array { values } => {
+                let amount = values.len();
+                self.generate(values)?;
+                self.write_opcode(Opcode::CreateArray(amount));
+            }
+            ExprKind::Index { target, position } => {
+                self.generate(target)?;
+                self.generate(position)?;
+                self.write_opcode(Opcode::GetIndex);
+            }
             ExprKind::GetProperty {
                 target,
                 identifier,
@@ -128,12 +168,22 @@ impl BytecodeFrom<Expr> for BytecodeGenerator {
                 self.write_opcode(Opcode::SetProperty(1));
             }
             ExprKind::Assignment { target, value } => {
+                // Elements of arrays don't have addresses, they are set through the array
+                if let ExprKind::Index { target, position } = *target.kind {
+                    self.generate(target)?;
+                    self.generate(position)?;
+                    self.generate(value)?;
+                    self.write_opcode(Opcode::SetIndex);
+                    return Ok(());
+                }
                 // TODO: If no additional logical will be added to it then it can just as well become a simple binary expression
                 self.generate(target)?;
                 self.generate(value)?;
                 self.write_opcode(Opcode::Asg);
             }
-            ExprKind::Closure { params, body } => {}
+            ExprKind::Closure { params, body } => {
+                self.generate_closure(LAMBDA_NAME.to_owned(), &expr.span, params, body)?;
+            }
             ExprKind::ObjectLiteral { properties } => {
                 let amount = properties.len();
                 for (key, value) in properties {
@@ -149,17 +199,19 @@ impl BytecodeFrom<Expr> for BytecodeGenerator {
 
 #[cfg(test)]
 mod test {
+    use analyzer::resolution::Resolutions;
     use parser::parse::expr::{atom::AtomicValue, ExprKind};
 
     use crate::{
         chunk::Constant,
-        test::{assert_bytecode_and_constants, box_node, declare_var, expr},
-        BytecodeGenerator, Opcode,
+        test::{assert_bytecode_and_constants, box_node, declare_var, expr, generate_program},
+        BytecodeGenerator, MemoryAddress, Opcode,
     };
 
     #[test]
     fn it_patches_opcodes() {
-        let mut generator = BytecodeGenerator::new();
+        let resolutions = Resolutions::default();
+        let mut generator = BytecodeGenerator::new(&resolutions);
         let patch = generator.emit_patch(Opcode::Jif(0));
         assert_eq!(patch.index, 0);
         // Adding some random opcodes to the chunk
@@ -167,14 +219,14 @@ mod test {
         generator.write_opcode(Opcode::Get);
         // We added some codes but the patched opcode remain the same
         assert_eq!(
-            generator.clone().code().chunk.opcodes[patch.index],
+            generator.clone().code().main_function().chunk.opcodes[patch.index],
             Opcode::Jif(0)
         );
-        generator.patch(&patch);
+        generator.patch(&patch, &(0..0)).unwrap();
         // After the patch the opcode internal value should be changed to +2
         // because we added two new opcodes and the jump should jump by 2
         assert_eq!(
-            generator.clone().code().chunk.opcodes[patch.index],
+            generator.clone().code().main_function().chunk.opcodes[patch.index],
             Opcode::Jif(2)
         );
     }
@@ -204,4 +256,82 @@ mod test {
             vec![Constant::Number(5.0)],
         );
     }
+
+    #[test]
+    fn generates_array_bytecode() {
+        assert_bytecode_and_constants(
+            box_node(ExprKind::Index {
+                target: box_node(ExprKind::Array {
+                    values: vec![
+                        expr(AtomicValue::Number(1.0)),
+                        expr(AtomicValue::Number(2.0)),
+                    ],
+                }),
+                position: expr(AtomicValue::Number(0.0)),
+            }),
+            vec![
+                Opcode::Constant(0),
+                Opcode::Constant(1),
+                Opcode::CreateArray(2),
+                Opcode::Constant(2),
+                Opcode::GetIndex,
+            ],
+            vec![
+                Constant::Number(1.0),
+                Constant::Number(2.0),
+                Constant::Number(0.0),
+            ],
+        );
+
+        // Elements are set through the array, not through an address
+        let bytecode = generate_program("let foo = []; foo[0] = 1;");
+        let chunk = &bytecode.main_function().chunk;
+        assert_eq!(
+            chunk.opcodes,
+            vec![
+                Opcode::CreateArray(0),
+                Opcode::Constant(0),
+                Opcode::Get,
+                Opcode::Constant(1),
+                Opcode::Constant(2),
+                Opcode::SetIndex,
+            ]
+        );
+        assert_eq!(
+            chunk.constants,
+            vec![
+                Constant::MemoryAddress(MemoryAddress::Local(0)),
+                Constant::Number(0.0),
+                Constant::Number(1.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn records_spans_of_opcodes() {
+        let code = "let a = 1; print(a + 2);";
+        let bytecode = generate_program(code);
+        let chunk = &bytecode.main_function().chunk;
+
+        let sources: Vec<(String, &str)> = (0..chunk.opcodes_len())
+            .map(|index| {
+                let span = chunk.spans.range_at(index).unwrap().clone();
+                (chunk.read_opcode(index).to_string(), &code[span])
+            })
+            .collect();
+
+        assert_eq!(
+            sources,
+            vec![
+                ("CONSTANT_0".to_owned(), "1"),
+                ("CONSTANT_1".to_owned(), "a"),
+                ("GET".to_owned(), "a"),
+                ("CONSTANT_2".to_owned(), "2"),
+                ("ADD".to_owned(), "a + 2"),
+                ("CONSTANT_3".to_owned(), "print"),
+                ("GET".to_owned(), "print"),
+                ("CALL_1".to_owned(), "(a + 2)"),
+            ]
+        );
+    }
 }
@@ -0,0 +1,360 @@
+use std::collections::HashMap;
+
+use analyzer::resolution::Resolutions;
+use parser::parse::{
+    expr::{atom::AtomicValue, Expr, ExprKind},
+    operator::{BinaryOperator, UnaryOperator},
+    stmt::{Stmt, StmtKind},
+    Ast, Node, Span,
+};
+
+// Evaluates parts of the program that only depend on literals before any bytecode gets generated.
+// Results must be exactly what the VM would compute, so operations that fail at runtime
+// (e.g. adding a number to a string) are left for the VM to report.
+pub fn fold_constants(ast: Ast, resolutions: &Resolutions) -> Ast {
+    let mut folder = ConstantFolder {
+        resolutions,
+        constants: HashMap::new(),
+    };
+
+    ast.into_iter().map(|stmt| folder.fold_stmt(stmt)).collect()
+}
+
+struct ConstantFolder<'r> {
+    resolutions: &'r Resolutions,
+    // Literal values of declarations that are never reassigned
+    constants: HashMap<Span, AtomicValue>,
+}
+
+fn literal(expr: &Expr) -> Option<&AtomicValue> {
+    match &*expr.kind {
+        ExprKind::Atom(AtomicValue::Identifier { .. }) => None,
+        ExprKind::Atom(value) => Some(value),
+        _ => None,
+    }
+}
+
+// Same rules as the VM uses for conditional jumps
+fn is_truthy(value: &AtomicValue) -> bool {
+    match value {
+        AtomicValue::Boolean(bool) => *bool,
+        _ => true,
+    }
+}
+
+fn fold_binary(lhs: &AtomicValue, op: BinaryOperator, rhs: &AtomicValue) -> Option<AtomicValue> {
+    use AtomicValue::*;
+    use BinaryOperator::*;
+
+    let value = match (lhs, op, rhs) {
+        (Number(a), Addition, Number(b)) => Number(a + b),
+        (Text(a), Addition, Text(b)) => Text(format!("{}{}", a, b)),
+        (Number(a), Subtraction, Number(b)) => Number(a - b),
+        (Number(a), Multiplication, Number(b)) => Number(a * b),
+        (Number(a), Division, Number(b)) => Number(a / b),
+        (Number(a), Modulo, Number(b)) => Number(a % b),
+        (Number(a), Power, Number(b)) => Number(a.powf(*b)),
+        (Number(a), LesserThan, Number(b)) => Boolean(a < b),
+        (Number(a), LesserEquals, Number(b)) => Boolean(a <= b),
+        (Number(a), GreaterThan, Number(b)) => Boolean(a > b),
+        (Number(a), GreaterEquals, Number(b)) => Boolean(a >= b),
+        (Boolean(a), And, Boolean(b)) => Boolean(*a && *b),
+        (Boolean(a), Or, Boolean(b)) => Boolean(*a || *b),
+        (a, Equals, b) => Boolean(literals_equal(a, b)),
+        (a, NotEquals, b) => Boolean(!literals_equal(a, b)),
+        _ => return None,
+    };
+
+    Some(value)
+}
+
+// Values of different types are never equal, NaN isn't equal to itself
+#[allow(clippy::float_cmp)]
+fn literals_equal(lhs: &AtomicValue, rhs: &AtomicValue) -> bool {
+    use AtomicValue::*;
+
+    match (lhs, rhs) {
+        (Number(a), Number(b)) => a == b,
+        (Text(a), Text(b)) => a == b,
+        (Boolean(a), Boolean(b)) => a == b,
+        _ => false,
+    }
+}
+
+fn fold_unary(op: UnaryOperator, rhs: &AtomicValue) -> Option<AtomicValue> {
+    match (op, rhs) {
+        (UnaryOperator::Negate, AtomicValue::Number(a)) => Some(AtomicValue::Number(-a)),
+        (UnaryOperator::Not, AtomicValue::Boolean(a)) => Some(AtomicValue::Boolean(!a)),
+        _ => None,
+    }
+}
+
+impl ConstantFolder<'_> {
+    fn fold_stmt(&mut self, stmt: Stmt) -> Stmt {
+        use StmtKind::*;
+        let span = stmt.span;
+
+        let kind = match *stmt.kind {
+            Expression { expr } => Expression {
+                expr: self.fold_expr(expr),
+            },
+            VariableDeclaration { name, expr } => {
+                let expr = self.fold_expr(expr);
+                if let Some(value) = literal(&expr) {
+                    if !self.resolutions.is_reassigned(&span) {
+                        self.constants.insert(span.clone(), value.clone());
+                    }
+                }
+                VariableDeclaration { name, expr }
+            }
+            FunctionDeclaration {
+                name,
+                params,
+                body,
+                annotations,
+            } => FunctionDeclaration {
+                name,
+                params,
+                body: self.fold_expr(body),
+                annotations,
+            },
+            ClassDeclaration {
+                name,
+                super_class,
+                methods,
+            } => ClassDeclaration {
+                name,
+                super_class,
+                methods: methods
+                    .into_iter()
+                    .map(|method| self.fold_stmt(method))
+                    .collect(),
+            },
+        };
+
+        Node {
+            kind: Box::new(kind),
+            span,
+        }
+    }
+
+    fn fold_option(&mut self, expr: Option<Expr>) -> Option<Expr> {
+        expr.map(|expr| self.fold_expr(expr))
+    }
+
+    fn fold_exprs(&mut self, exprs: Vec<Expr>) -> Vec<Expr> {
+        exprs.into_iter().map(|expr| self.fold_expr(expr)).collect()
+    }
+
+    fn fold_expr(&mut self, expr: Expr) -> Expr {
+        use ExprKind::*;
+        let span = expr.span;
+
+        let kind = match *expr.kind {
+            Atom(AtomicValue::Identifier {
+                name,
+                is_assignment: false,
+            }) => {
+                let constant = self
+                    .resolutions
+                    .declaration(&span)
+                    .and_then(|declaration| self.constants.get(declaration));
+
+                match constant {
+                    Some(value) => Atom(value.clone()),
+                    None => Atom(AtomicValue::Identifier {
+                        name,
+                        is_assignment: false,
+                    }),
+                }
+            }
+            Binary { lhs, op, rhs } => {
+                let lhs = self.fold_expr(lhs);
+                let rhs = self.fold_expr(rhs);
+
+                let folded = match (literal(&lhs), literal(&rhs)) {
+                    (Some(a), Some(b)) => fold_binary(a, op.kind, b),
+                    _ => None,
+                };
+                match folded {
+                    Some(value) => Atom(value),
+                    None => Binary { lhs, op, rhs },
+                }
+            }
+            Unary { op, rhs } => {
+                let rhs = self.fold_expr(rhs);
+
+                match literal(&rhs).and_then(|value| fold_unary(op.kind, value)) {
+                    Some(value) => Atom(value),
+                    None => Unary { op, rhs },
+                }
+            }
+            If {
+                condition,
+                body,
+                else_expr,
+            } => {
+                let condition = self.fold_expr(condition);
+                let taken = literal(&condition).map(is_truthy);
+
+                match (taken, else_expr) {
+                    (Some(true), _) => return self.fold_expr(body),
+                    (Some(false), Some(else_expr)) => return self.fold_expr(else_expr),
+                    // Without else the skipped body leaves nothing on the stack,
+                    // which no other expression does, so the jump stays
+                    (_, else_expr) => If {
+                        condition,
+                        body: self.fold_expr(body),
+                        else_expr: self.fold_option(else_expr),
+                    },
+                }
+            }
+            Block { stmts, return_expr } => Block {
+                stmts: stmts.into_iter().map(|stmt| self.fold_stmt(stmt)).collect(),
+                return_expr: self.fold_option(return_expr),
+            },
+            While { condition, body } => While {
+                condition: self.fold_expr(condition),
+                body: self.fold_expr(body),
+            },
+            Break { return_expr } => Break {
+                return_expr: self.fold_option(return_expr),
+            },
+            Call { callee, args } => Call {
+                callee: self.fold_expr(callee),
+                args: self.fold_exprs(args),
+            },
+            Return { value } => Return {
+                value: self.fold_option(value),
+            },
+            Array { values } => Array {
+                values: self.fold_exprs(values),
+            },
+            Index { target, position } => Index {
+                target: self.fold_expr(target),
+                position: self.fold_expr(position),
+            },
+            GetProperty {
+                target,
+                is_method_call,
+                identifier,
+            } => GetProperty {
+                target: self.fold_expr(target),
+                is_method_call,
+                identifier,
+            },
+            SetProperty {
+                target,
+                value,
+                identifier,
+            } => SetProperty {
+                target: self.fold_expr(target),
+                value: self.fold_expr(value),
+                identifier,
+            },
+            ObjectLiteral { properties } => ObjectLiteral {
+                properties: properties
+                    .into_iter()
+                    .map(|(key, value)| (key, self.fold_expr(value)))
+                    .collect(),
+            },
+            Assignment { target, value } => Assignment {
+                target,
+                value: self.fold_expr(value),
+            },
+            Closure { params, body } => Closure {
+                params,
+                body: self.fold_expr(body),
+            },
+            kind @ (Atom(_) | Continue) => kind,
+        };
+
+        Node {
+            kind: Box::new(kind),
+            span,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use analyzer::Analyzer;
+    use parser::parse;
+
+    use super::*;
+
+    fn fold(code: &str) -> Ast {
+        let ast = parse(code).unwrap();
+        let mut analyzer = Analyzer::new();
+        analyzer.analyze(&ast).unwrap();
+        fold_constants(ast, analyzer.resolutions())
+    }
+
+    // Node equality ignores spans, so the expected code can be written by hand
+    fn assert_folds(code: &str, expected: &str) {
+        assert_eq!(fold(code), parse(expected).unwrap());
+    }
+
+    fn folded_number(code: &str) -> f64 {
+        match &*fold(code).last().unwrap().kind {
+            StmtKind::Expression { expr } => match literal(expr) {
+                Some(AtomicValue::Number(number)) => *number,
+                _ => panic!("Expression wasn't folded into a number"),
+            },
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn folds_literals() {
+        assert_folds("2 + 2 * 8;", "18;");
+        assert_folds("-(2 ** 3) % 5;", "-3;");
+        assert_folds("1 < 2 and !false;", "true;");
+        assert_folds("\"foo\" == \"foo\";", "true;");
+        assert_folds("\"foo\" + \"bar\" + \"baz\";", "\"foobarbaz\";");
+        assert_folds("let a = \"a\"; a + \"b\";", "let a = \"a\"; \"ab\";");
+        // values of different types are never equal
+        assert_folds("1 == \"1\";", "false;");
+        assert_folds("let a = 2; (a + 1) * clock();", "let a = 2; 3 * clock();");
+    }
+
+    #[test]
+    fn keeps_runtime_semantics() {
+        assert!(folded_number("0 / 0;").is_nan());
+        assert_eq!(folded_number("1 / 0;"), f64::INFINITY);
+        assert_eq!(folded_number("-1 / 0;"), f64::NEG_INFINITY);
+        assert_folds("0 / 0 == 0 / 0;", "false;");
+        assert_folds("1 / 0 > 1000;", "true;");
+        // VM rejects these, so they have to fail at runtime
+        assert_folds("\"foo\" + 1;", "\"foo\" + 1;");
+        assert_folds("1 and true;", "1 and true;");
+        assert_folds("-\"foo\";", "-\"foo\";");
+    }
+
+    #[test]
+    fn propagates_constants() {
+        assert_folds(
+            "let a = 2; let b = a * 3; fn foo() { b + 1 }",
+            "let a = 2; let b = 6; fn foo() { 7 }",
+        );
+        // reassigned variables keep being read from the stack
+        assert_folds("let a = 2; a = 3; a + 1;", "let a = 2; a = 3; a + 1;");
+        // shadowing declaration is a different variable
+        assert_folds(
+            "let a = 2; fn foo(a) { a + 1 }",
+            "let a = 2; fn foo(a) { a + 1 }",
+        );
+    }
+
+    #[test]
+    fn removes_dead_branches() {
+        assert_folds("let a = if 1 > 2 { 1 } else { 2 };", "let a = { 2 };");
+        assert_folds("let a = if \"\" { 1 };", "let a = { 1 };");
+        assert_folds(
+            "let a = if false { 1 } else if true { 2 } else { 3 };",
+            "let a = { 2 };",
+        );
+        // there's nothing to replace a skipped branch with
+        assert_folds("if false { 1 };", "if false { 1 };");
+    }
+}
@@ -0,0 +1,547 @@
+use codespan_reporting::diagnostic::Diagnostic;
+use common::{source::Location, BuiltInFunction, CompilerDiagnostic};
+
+use crate::{
+    callables::Function,
+    chunk::{Chunk, Constant, OpcodeIndex, SourceSpan, SpanTable},
+    stmt::GlobalItem,
+    MemoryAddress, Opcode, ProgramBytecode,
+};
+
+// Compiled programs are stored in files with this extension, e.g. script.gvc
+pub const EXTENSION: &str = "gvc";
+
+// File starts with the magic bytes, the format version and the checksum of everything after them.
+// Numbers are little endian, sizes and indexes take 8 bytes and strings are prefixed with their length.
+const MAGIC: &[u8; 4] = b"GVC\0";
+pub const FORMAT_VERSION: u16 = 4;
+const HEADER_LEN: usize = MAGIC.len() + 2 + 4;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LoadError {
+    NotBytecode,
+    UnsupportedVersion(u16),
+    // File was modified or corrupted after it was written
+    ChecksumMismatch,
+    UnexpectedEnd,
+    // Tag of an item that this version of the format doesn't know
+    InvalidTag { item: &'static str, tag: u8 },
+    InvalidString,
+}
+
+impl CompilerDiagnostic for LoadError {
+    fn report(&self, _file_id: usize) -> Diagnostic<usize> {
+        use LoadError::*;
+
+        let message = match self {
+            NotBytecode => "File doesn't contain compiled gravitas bytecode".to_owned(),
+            UnsupportedVersion(version) => format!(
+                "Bytecode was compiled in format version {}, but only version {} is supported",
+                version, FORMAT_VERSION
+            ),
+            ChecksumMismatch => "Bytecode is corrupted, its checksum doesn't match".to_owned(),
+            UnexpectedEnd => "Bytecode ends unexpectedly".to_owned(),
+            InvalidTag { item, tag } => format!("Bytecode contains unknown {} {}", item, tag),
+            InvalidString => "Bytecode contains a string that isn't valid UTF-8".to_owned(),
+        };
+
+        Diagnostic::error()
+            .with_message(message)
+            .with_notes(vec!["try building the program again".to_owned()])
+    }
+}
+
+pub type LoadResult<T> = Result<T, LoadError>;
+
+// 32 bit FNV-1a
+fn checksum(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0x811c_9dc5, |hash: u32, byte| {
+        (hash ^ *byte as u32).wrapping_mul(0x0100_0193)
+    })
+}
+
+pub fn write(program: &ProgramBytecode) -> Vec<u8> {
+    let mut payload = Writer::default();
+    payload.size(program.global_fn_ptr);
+    payload.size(program.globals.len());
+    for global in &program.globals {
+        payload.global(global);
+    }
+
+    let mut bytes = Vec::with_capacity(HEADER_LEN + payload.bytes.len());
+    bytes.extend_from_slice(MAGIC);
+    bytes.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+    bytes.extend_from_slice(&checksum(&payload.bytes).to_le_bytes());
+    bytes.extend(payload.bytes);
+    bytes
+}
+
+pub fn load(bytes: &[u8]) -> LoadResult<ProgramBytecode> {
+    if bytes.len() < HEADER_LEN || &bytes[..MAGIC.len()] != MAGIC {
+        return Err(LoadError::NotBytecode);
+    }
+
+    let mut header = Reader::new(&bytes[MAGIC.len()..HEADER_LEN]);
+    let version = u16::from_le_bytes(header.array()?);
+    if version != FORMAT_VERSION {
+        return Err(LoadError::UnsupportedVersion(version));
+    }
+
+    let payload = &bytes[HEADER_LEN..];
+    if u32::from_le_bytes(header.array()?) != checksum(payload) {
+        return Err(LoadError::ChecksumMismatch);
+    }
+
+    let mut reader = Reader::new(payload);
+    let global_fn_ptr = reader.size()?;
+    let globals = reader.many(Reader::global)?;
+
+    Ok(ProgramBytecode {
+        global_fn_ptr,
+        globals,
+    })
+}
+
+#[derive(Default)]
+struct Writer {
+    bytes: Vec<u8>,
+}
+
+impl Writer {
+    fn byte(&mut self, byte: u8) {
+        self.bytes.push(byte);
+    }
+
+    fn bool(&mut self, bool: bool) {
+        self.byte(bool as u8);
+    }
+
+    fn size(&mut self, size: usize) {
+        self.bytes.extend_from_slice(&(size as u64).to_le_bytes());
+    }
+
+    fn distance(&mut self, distance: isize) {
+        self.bytes
+            .extend_from_slice(&(distance as i64).to_le_bytes());
+    }
+
+    fn string(&mut self, string: &str) {
+        self.size(string.len());
+        self.bytes.extend_from_slice(string.as_bytes());
+    }
+
+    fn global(&mut self, global: &GlobalItem) {
+        match global {
+            GlobalItem::Function(function) => {
+                self.byte(0);
+                self.string(&function.name);
+                self.size(function.arity);
+                self.bool(function.pure);
+
+                self.size(function.chunk.constants.len());
+                for constant in &function.chunk.constants {
+                    self.constant(constant);
+                }
+                self.size(function.chunk.opcodes.len());
+                for opcode in &function.chunk.opcodes {
+                    self.opcode(opcode);
+                }
+
+                // Byte ranges are no use without the source, only lines and columns are kept
+                let spans = &function.chunk.spans;
+                self.bool(spans.file().is_some());
+                if let Some(file) = spans.file() {
+                    self.string(file);
+                }
+                self.size(spans.entries().len());
+                for (index, span) in spans.entries() {
+                    self.size(*index);
+                    self.bool(span.location.is_some());
+                    if let Some(location) = span.location {
+                        self.size(location.line);
+                        self.size(location.column);
+                    }
+                }
+            }
+        }
+    }
+
+    fn constant(&mut self, constant: &Constant) {
+        match constant {
+            Constant::MemoryAddress(address) => {
+                self.byte(0);
+                self.address(address);
+            }
+            Constant::Number(number) => {
+                self.byte(1);
+                self.bytes.extend_from_slice(&number.to_le_bytes());
+            }
+            Constant::String(string) => {
+                self.byte(2);
+                self.string(string);
+            }
+            Constant::Bool(bool) => {
+                self.byte(3);
+                self.bool(*bool);
+            }
+            Constant::GlobalPointer(ptr) => {
+                self.byte(4);
+                self.size(*ptr);
+            }
+        }
+    }
+
+    fn address(&mut self, address: &MemoryAddress) {
+        match address {
+            MemoryAddress::Local(slot) => {
+                self.byte(0);
+                self.size(*slot);
+            }
+            MemoryAddress::Upvalue { index, is_ref } => {
+                self.byte(1);
+                self.size(*index);
+                self.bool(*is_ref);
+            }
+            MemoryAddress::BuiltInFunction(function) => {
+                self.byte(2);
+                self.byte(match function {
+                    BuiltInFunction::Clock => 0,
+                    BuiltInFunction::Print => 1,
+                });
+            }
+        }
+    }
+
+    fn opcode(&mut self, opcode: &Opcode) {
+        use Opcode::*;
+
+        // Opcodes without operands are written as their tag only
+        let tag = match opcode {
+            Constant(_) => 0,
+            Add => 1,
+            Sub => 2,
+            Mul => 3,
+            Div => 4,
+            Mod => 5,
+            Pow => 6,
+            Neg => 7,
+            Not => 8,
+            Eq => 9,
+            Ne => 10,
+            Lt => 11,
+            Le => 12,
+            Gt => 13,
+            Ge => 14,
+            Or => 15,
+            And => 16,
+            Jif(_) => 17,
+            Jp(_) => 18,
+            Pop(_) => 19,
+            Get => 20,
+            GetProperty { .. } => 21,
+            SetProperty(_) => 22,
+            Asg => 23,
+            Call(_) => 24,
+            Return => 25,
+            Block(_) => 26,
+            Break(_) => 27,
+            Null => 28,
+            CreateClosure(_) => 29,
+            CreateObject(_) => 30,
+            CreateArray(_) => 31,
+            GetIndex => 32,
+            SetIndex => 33,
+        };
+        self.byte(tag);
+
+        match *opcode {
+            Constant(amount)
+            | Pop(amount)
+            | Call(amount)
+            | SetProperty(amount)
+            | Block(amount)
+            | CreateClosure(amount)
+            | CreateObject(amount)
+            | CreateArray(amount) => self.size(amount),
+            Jif(distance) | Jp(distance) | Break(distance) => self.distance(distance),
+            GetProperty { bind_method } => self.bool(bind_method),
+            _ => {}
+        }
+    }
+}
+
+struct Reader<'b> {
+    bytes: &'b [u8],
+    position: usize,
+}
+
+impl<'b> Reader<'b> {
+    fn new(bytes: &'b [u8]) -> Self {
+        Self { bytes, position: 0 }
+    }
+
+    fn take(&mut self, amount: usize) -> LoadResult<&'b [u8]> {
+        let end = self
+            .position
+            .checked_add(amount)
+            .filter(|end| *end <= self.bytes.len())
+            .ok_or(LoadError::UnexpectedEnd)?;
+        let taken = &self.bytes[self.position..end];
+        self.position = end;
+        Ok(taken)
+    }
+
+    fn array<const N: usize>(&mut self) -> LoadResult<[u8; N]> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.take(N)?);
+        Ok(array)
+    }
+
+    fn byte(&mut self) -> LoadResult<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn bool(&mut self) -> LoadResult<bool> {
+        Ok(self.byte()? != 0)
+    }
+
+    fn size(&mut self) -> LoadResult<usize> {
+        Ok(u64::from_le_bytes(self.array()?) as usize)
+    }
+
+    fn distance(&mut self) -> LoadResult<isize> {
+        Ok(i64::from_le_bytes(self.array()?) as isize)
+    }
+
+    fn string(&mut self) -> LoadResult<String> {
+        let length = self.size()?;
+        let bytes = self.take(length)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| LoadError::InvalidString)
+    }
+
+    // Length prefixed sequence of items
+    fn many<T>(&mut self, read: fn(&mut Self) -> LoadResult<T>) -> LoadResult<Vec<T>> {
+        let length = self.size()?;
+        // Length comes from the file, so it isn't trusted with the allocation
+        let mut items = Vec::with_capacity(length.min(self.bytes.len()));
+        for _ in 0..length {
+            items.push(read(self)?);
+        }
+        Ok(items)
+    }
+
+    fn global(&mut self) -> LoadResult<GlobalItem> {
+        match self.byte()? {
+            0 => {
+                let name = self.string()?;
+                let arity = self.size()?;
+                let pure = self.bool()?;
+                let constants = self.many(Self::constant)?;
+                let opcodes = self.many(Self::opcode)?;
+                let file = match self.bool()? {
+                    true => Some(self.string()?),
+                    false => None,
+                };
+                let spans = self.many(Self::span_entry)?;
+
+                Ok(GlobalItem::Function(Function {
+                    arity,
+                    chunk: Chunk {
+                        opcodes,
+                        constants,
+                        spans: SpanTable::new(file, spans),
+                    },
+                    name,
+                    pure,
+                }))
+            }
+            tag => Err(LoadError::InvalidTag {
+                item: "global",
+                tag,
+            }),
+        }
+    }
+
+    fn span_entry(&mut self) -> LoadResult<(OpcodeIndex, SourceSpan)> {
+        let index = self.size()?;
+        let location = match self.bool()? {
+            true => Some(Location {
+                line: self.size()?,
+                column: self.size()?,
+            }),
+            false => None,
+        };
+        Ok((
+            index,
+            SourceSpan {
+                range: None,
+                location,
+            },
+        ))
+    }
+
+    fn constant(&mut self) -> LoadResult<Constant> {
+        Ok(match self.byte()? {
+            0 => Constant::MemoryAddress(self.address()?),
+            1 => Constant::Number(f64::from_le_bytes(self.array()?)),
+            2 => Constant::String(self.string()?),
+            3 => Constant::Bool(self.bool()?),
+            4 => Constant::GlobalPointer(self.size()?),
+            tag => {
+                return Err(LoadError::InvalidTag {
+                    item: "constant",
+                    tag,
+                })
+            }
+        })
+    }
+
+    fn address(&mut self) -> LoadResult<MemoryAddress> {
+        Ok(match self.byte()? {
+            0 => MemoryAddress::Local(self.size()?),
+            1 => MemoryAddress::Upvalue {
+                index: self.size()?,
+                is_ref: self.bool()?,
+            },
+            2 => MemoryAddress::BuiltInFunction(match self.byte()? {
+                0 => BuiltInFunction::Clock,
+                1 => BuiltInFunction::Print,
+                tag => {
+                    return Err(LoadError::InvalidTag {
+                        item: "built-in function",
+                        tag,
+                    })
+                }
+            }),
+            tag => {
+                return Err(LoadError::InvalidTag {
+                    item: "memory address",
+                    tag,
+                })
+            }
+        })
+    }
+
+    fn opcode(&mut self) -> LoadResult<Opcode> {
+        use Opcode::*;
+
+        Ok(match self.byte()? {
+            0 => Constant(self.size()?),
+            1 => Add,
+            2 => Sub,
+            3 => Mul,
+            4 => Div,
+            5 => Mod,
+            6 => Pow,
+            7 => Neg,
+            8 => Not,
+            9 => Eq,
+            10 => Ne,
+            11 => Lt,
+            12 => Le,
+            13 => Gt,
+            14 => Ge,
+            15 => Or,
+            16 => And,
+            17 => Jif(self.distance()?),
+            18 => Jp(self.distance()?),
+            19 => Pop(self.size()?),
+            20 => Get,
+            21 => GetProperty {
+                bind_method: self.bool()?,
+            },
+            22 => SetProperty(self.size()?),
+            23 => Asg,
+            24 => Call(self.size()?),
+            25 => Return,
+            26 => Block(self.size()?),
+            27 => Break(self.distance()?),
+            28 => Null,
+            29 => CreateClosure(self.size()?),
+            30 => CreateObject(self.size()?),
+            31 => CreateArray(self.size()?),
+            32 => GetIndex,
+            33 => SetIndex,
+            tag => {
+                return Err(LoadError::InvalidTag {
+                    item: "opcode",
+                    tag,
+                })
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use common::source::SourceDatabase;
+
+    use super::{load, write, LoadError, FORMAT_VERSION};
+    use crate::test::generate_program;
+
+    const PROGRAM: &str = "
+        fn add(a, b) => a + b
+        let numbers = [1.5, 2];
+        let flag = true;
+        let greeting = \"hello\";
+        let counter = |step| => {
+            numbers[0] = add(numbers[0], step);
+            print(numbers[0]);
+        };
+        while flag { flag = false; };
+        counter(1);
+    ";
+
+    #[test]
+    fn loads_written_programs() {
+        let mut db = SourceDatabase::new();
+        let file_id = db.add("main.vt", PROGRAM);
+        let mut program = generate_program(PROGRAM);
+        program.locate_spans(db.name(file_id), |offset| db.location(file_id, offset));
+        let bytes = write(&program);
+        assert_eq!(&bytes[..4], b"GVC\0");
+
+        let loaded = load(&bytes).unwrap();
+        assert_eq!(loaded.globals.len(), program.globals.len());
+        for (loaded, global) in loaded.globals.iter().zip(&program.globals) {
+            let (loaded, function) = (loaded.as_function(), global.as_function());
+            assert_eq!(loaded.name, function.name);
+            assert_eq!(loaded.arity, function.arity);
+            assert_eq!(loaded.pure, function.pure);
+            assert_eq!(loaded.chunk.opcodes, function.chunk.opcodes);
+            assert_eq!(loaded.chunk.constants, function.chunk.constants);
+            // Spans only keep where they are in the file
+            for index in 0..function.chunk.opcodes_len() {
+                let location = function.chunk.spans.location_at(index);
+                assert!(location.is_some());
+                assert_eq!(loaded.chunk.spans.location_at(index), location);
+                assert_eq!(loaded.chunk.spans.range_at(index), None);
+            }
+        }
+        assert_eq!(write(&loaded), bytes);
+    }
+
+    #[test]
+    fn rejects_invalid_files() {
+        let bytes = write(&generate_program(PROGRAM));
+
+        assert_eq!(load(b"let a = 1;"), Err(LoadError::NotBytecode));
+
+        let mut newer = bytes.clone();
+        newer[4..6].copy_from_slice(&(FORMAT_VERSION + 1).to_le_bytes());
+        assert_eq!(
+            load(&newer),
+            Err(LoadError::UnsupportedVersion(FORMAT_VERSION + 1))
+        );
+
+        let mut corrupted = bytes.clone();
+        *corrupted.last_mut().unwrap() ^= 1;
+        assert_eq!(load(&corrupted), Err(LoadError::ChecksumMismatch));
+
+        // Checksum only covers what's in the file, so a cut file is caught while reading it
+        let mut truncated = bytes[..bytes.len() - 1].to_vec();
+        let checksum = super::checksum(&truncated[10..]);
+        truncated[6..10].copy_from_slice(&checksum.to_le_bytes());
+        assert_eq!(load(&truncated), Err(LoadError::UnexpectedEnd));
+    }
+}
@@ -1,9 +1,13 @@
 use std::fmt::Display;
 
+use analyzer::resolution::{Resolution, Resolutions};
 use callables::Function;
 use chunk::{Chunk, Constant, ConstantIndex};
-use common::{BuiltInFunction, ProgramText, MAIN_FUNCTION_NAME};
-use parser::parse::{Ast, Program};
+use common::{source::Location, BuiltInFunction, ProgramText, MAIN_FUNCTION_NAME};
+use error::{GenerationError, GenerationErrorCause};
+use fold::fold_constants;
+use parser::parse::{stmt::StmtKind, Ast, Program, Span};
+use peephole::optimize;
 use state::{GeneratorState, ScopeType};
 use stmt::{GlobalItem, GlobalPointer};
 #[macro_use]
@@ -11,9 +15,14 @@ extern crate prettytable;
 
 pub mod callables;
 pub mod chunk;
+pub mod error;
 pub(crate) mod expr;
+pub mod fold;
+pub mod gvc;
+pub mod peephole;
 pub(crate) mod state;
 pub mod stmt;
+pub mod verify;
 
 #[derive(Debug, Hash, Clone, Copy, PartialEq, Eq)]
 pub struct Patch {
@@ -37,7 +46,7 @@ impl Display for MemoryAddress {
         let str = match self {
             Self::Local(address) => format!("local_address::{}", address),
             Self::Upvalue { index, .. } => format!("upvalue::{}", index),
-            Self::BuiltInFunction(function) => format!("built::in::function"),
+            Self::BuiltInFunction(_function) => "built::in::function".to_string(),
         };
         write!(f, "{}", str)?;
 
@@ -51,22 +60,17 @@ impl From<MemoryAddress> for Constant {
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
-pub struct Variable {
-    pub name: ProgramText,
-    pub depth: usize,
-    // Calculated index on the stack
-    pub index: usize,
-    pub upvalue_index: Option<usize>,
-}
-
-#[derive(Debug, Clone, PartialEq)]
-pub struct Upvalue {
-    upvalue_index: usize,
-    is_local: bool,
-    local_index: usize,
-    is_ref: bool,
-    name: ProgramText,
+impl From<&Resolution> for MemoryAddress {
+    fn from(resolution: &Resolution) -> Self {
+        match resolution {
+            Resolution::Local(slot) => MemoryAddress::Local(*slot),
+            Resolution::Upvalue { index, is_ref } => MemoryAddress::Upvalue {
+                index: *index,
+                is_ref: *is_ref,
+            },
+            Resolution::Builtin(function) => MemoryAddress::BuiltInFunction(function.clone()),
+        }
+    }
 }
 
 // Each opcode is described with e.g (Address, Number) which means that
@@ -122,8 +126,8 @@ pub enum Opcode {
     SetProperty(usize),
     // Assign (Address, Any)
     Asg,
-    // Call function or method, (Callable)
-    Call,
+    // Call function or method with n arguments, (n * Any, Callable)
+    Call(usize),
     // Return (Any)
     Return,
     Block(usize),
@@ -133,6 +137,12 @@ pub enum Opcode {
     CreateClosure(usize),
     // number of object properties to evaluate
     CreateObject(usize),
+    // number of array elements to pop
+    CreateArray(usize),
+    // Get array element (Array, Number)
+    GetIndex,
+    // Set array element (Array, Number, Any)
+    SetIndex,
 }
 
 impl Display for Opcode {
@@ -157,21 +167,24 @@ impl Display for Opcode {
             And => "AND",
             Get => "GET",
             Asg => "ASG",
-            Call => "CALL",
             Return => "RET",
             Null => "NULL",
+            GetIndex => "GET_INDEX",
+            SetIndex => "SET_INDEX",
             rest => {
                 let str = match rest {
                     Constant(index) => format!("CONSTANT_{}", index),
                     Jif(distance) => format!("JIF_{}", distance),
                     Jp(distance) => format!("JP_{}", distance),
                     Pop(amount) => format!("POP_{}", amount),
+                    Call(amount) => format!("CALL_{}", amount),
                     Block(amount) => format!("BLC_{}", amount),
                     Break(distance) => format!("BRK_{}", distance),
                     CreateClosure(amount) => format!("CLOSURE_{}", amount),
                     GetProperty { bind_method } => format!("GET_PROPERTY_BIND_{}", bind_method),
                     SetProperty(amount) => format!("SET_PROPERTY_{}", amount),
                     CreateObject(amount) => format!("CREATE_OBJECT_{}", amount),
+                    CreateArray(amount) => format!("CREATE_ARRAY_{}", amount),
                     _ => unreachable!(),
                 };
                 write!(f, "{}", str)?;
@@ -196,36 +209,74 @@ impl Opcode {
     }
 }
 
-pub type BytecodeGenerationResult = Result<(), ()>;
+// Limits of what a single function can address
+pub const MAX_CONSTANTS: usize = u16::MAX as usize + 1;
+pub const MAX_JUMP: isize = i16::MAX as isize;
+pub const MAX_LOCALS: usize = u8::MAX as usize + 1;
+pub const MAX_UPVALUES: usize = u8::MAX as usize + 1;
+
+pub type BytecodeGenerationResult = Result<(), GenerationError>;
+#[derive(Debug, Clone, PartialEq)]
 pub struct ProgramBytecode {
     pub global_fn_ptr: GlobalPointer,
     pub globals: Vec<GlobalItem>,
 }
-pub type GenerationResult = Result<ProgramBytecode, ()>;
 
-pub fn generate_bytecode(program: Program) -> GenerationResult {
-    let mut generator = BytecodeGenerator::new();
-    generator.generate(program)?;
+impl ProgramBytecode {
+    pub fn main_function(&self) -> &Function {
+        self.globals[self.global_fn_ptr].as_function()
+    }
+
+    // Gives spans of every function their line and column in the file the program comes from
+    pub fn locate_spans(&mut self, file: &str, location: impl Fn(usize) -> Location) {
+        for global in &mut self.globals {
+            global.as_function_mut().chunk.spans.locate(file, &location);
+        }
+    }
+}
+pub type GenerationResult = Result<ProgramBytecode, GenerationError>;
+
+// Resolutions come from the analysis of the same program
+pub fn generate_bytecode(program: Program, resolutions: &Resolutions) -> GenerationResult {
+    let program = fold_constants(program, resolutions);
+    let mut bytecode = generate_unoptimized_bytecode(program, resolutions)?;
+    optimize(&mut bytecode);
+    Ok(bytecode)
+}
+
+// Bytecode exactly the way the generator writes it, without folded constants or peephole passes
+pub fn generate_unoptimized_bytecode(
+    program: Program,
+    resolutions: &Resolutions,
+) -> GenerationResult {
+    let mut generator = BytecodeGenerator::new(resolutions);
+    generator.generate_main(program)?;
     Ok(generator.code())
 }
 
 #[derive(Debug, Clone)]
-struct BytecodeGenerator {
+struct BytecodeGenerator<'r> {
     state: GeneratorState,
+    resolutions: &'r Resolutions,
     functions: Vec<Function>,
     globals: Vec<GlobalItem>,
+    // Span of the innermost node that is being generated
+    span: Span,
 }
 
-impl BytecodeGenerator {
-    pub fn new() -> Self {
+impl<'r> BytecodeGenerator<'r> {
+    pub fn new(resolutions: &'r Resolutions) -> Self {
         Self {
             state: GeneratorState::new(),
+            resolutions,
             functions: vec![Function {
                 name: MAIN_FUNCTION_NAME.to_owned(),
                 arity: 0,
                 chunk: Chunk::default(),
+                pure: false,
             }],
             globals: vec![],
+            span: 0..0,
         }
     }
 
@@ -234,11 +285,27 @@ impl BytecodeGenerator {
     }
 
     pub fn write_opcode(&mut self, opcode: Opcode) -> usize {
-        self.current_chunk().write_opcode(opcode)
+        let span = self.span.clone();
+        let chunk = self.current_chunk();
+        let index = chunk.write_opcode(opcode);
+        chunk.spans.record(index, span);
+        index
     }
 
     pub fn write_constant(&mut self, constant: Constant) -> usize {
-        self.current_chunk().write_constant(constant)
+        let span = self.span.clone();
+        let chunk = self.current_chunk();
+        let constant_index = chunk.write_constant(constant);
+        chunk.spans.record(chunk.opcodes_len() - 1, span);
+        constant_index
+    }
+
+    // Opcodes written by the generation get the span of the node they come from
+    pub fn spanned<T>(&mut self, span: Span, generate: impl FnOnce(&mut Self) -> T) -> T {
+        let outer_span = std::mem::replace(&mut self.span, span);
+        let result = generate(self);
+        self.span = outer_span;
+        result
     }
 
     pub fn code(mut self) -> ProgramBytecode {
@@ -275,17 +342,39 @@ impl BytecodeGenerator {
         patch
     }
 
-    pub fn patch(&mut self, patch: &Patch) {
+    pub fn error<T>(&self, span: &Span, cause: GenerationErrorCause) -> Result<T, GenerationError> {
+        Err(GenerationError {
+            span: span.clone(),
+            cause,
+        })
+    }
+
+    // Span is the construct the jump belongs to, blamed if the jump is too long
+    pub fn patch(&mut self, patch: &Patch, span: &Span) -> BytecodeGenerationResult {
         self.state.remove_patch(patch);
         let current_index = self.curr_index();
+        let distance = (current_index - patch.index) as isize;
+        if distance > MAX_JUMP {
+            return self.error(span, GenerationErrorCause::JumpTooLong);
+        }
+
         let opcode = self
             .current_chunk()
             .opcodes
             .get_mut(patch.index)
             .expect("Patch tried to access wrong opcode.");
-        let patched_opcode = opcode.patch((current_index - patch.index) as isize);
+        let patched_opcode = opcode.patch(distance);
 
         let _ = std::mem::replace(opcode, patched_opcode);
+        Ok(())
+    }
+
+    // Constant opcodes of the current function have to reach all of its constants
+    pub fn check_constants(&mut self, span: &Span) -> BytecodeGenerationResult {
+        if self.current_chunk().constants.len() > MAX_CONSTANTS {
+            return self.error(span, GenerationErrorCause::TooManyConstants);
+        }
+        Ok(())
     }
 
     pub fn new_function(&mut self, name: ProgramText, arity: usize) {
@@ -293,22 +382,37 @@ impl BytecodeGenerator {
             arity,
             name,
             chunk: Chunk::default(),
+            pure: false,
         };
 
         self.enter_scope(ScopeType::Function);
         self.functions.push(new_fn);
     }
 
+    // Jumps to the opcode at the given index, pointer still gets advanced by one after the jump
+    pub fn jump_back(&mut self, index: usize, span: &Span) -> BytecodeGenerationResult {
+        let jump_index = self.current_chunk().opcodes_len();
+        let distance = index as isize - jump_index as isize - 1;
+        if -distance > MAX_JUMP {
+            return self.error(span, GenerationErrorCause::JumpTooLong);
+        }
+
+        self.write_opcode(Opcode::Jp(distance));
+        Ok(())
+    }
+
     pub fn enter_scope(&mut self, scope_type: ScopeType) {
-        let starting_index = self.curr_index();
+        // Index of the first opcode generated inside of the scope
+        let starting_index = self.current_chunk().opcodes_len();
         self.state.enter_scope(scope_type, starting_index);
     }
 
-    pub fn leave_scope(&mut self) {
+    pub fn leave_scope(&mut self, span: &Span) -> BytecodeGenerationResult {
         let scope = self.state.leave_scope();
         for patch in scope.patches {
-            self.patch(&patch);
+            self.patch(&patch, span)?;
         }
+        Ok(())
     }
 }
 
@@ -316,7 +420,29 @@ pub trait BytecodeFrom<T> {
     fn generate(&mut self, data: T) -> BytecodeGenerationResult;
 }
 
-impl BytecodeFrom<Ast> for BytecodeGenerator {
+impl BytecodeGenerator<'_> {
+    // Program results in the value of its last expression statement
+    pub fn generate_main(&mut self, program: Ast) -> BytecodeGenerationResult {
+        let ends_with_expression = matches!(
+            program.last().map(|stmt| &*stmt.kind),
+            Some(StmtKind::Expression { .. })
+        );
+        let span = match (program.first(), program.last()) {
+            (Some(first), Some(last)) => first.span.start..last.span.end,
+            _ => 0..0,
+        };
+        self.generate(program)?;
+        self.check_constants(&span)?;
+
+        if ends_with_expression {
+            // Drop the Pop of the last statement, so its value stays on the stack
+            self.current_chunk().pop_opcode();
+        }
+        Ok(())
+    }
+}
+
+impl BytecodeFrom<Ast> for BytecodeGenerator<'_> {
     fn generate(&mut self, ast: Ast) -> BytecodeGenerationResult {
         for stmt in ast {
             self.generate(stmt)?;
@@ -328,24 +454,53 @@ impl BytecodeFrom<Ast> for BytecodeGenerator {
 #[cfg(test)]
 pub(crate) mod test {
 
-    use crate::{chunk::Constant, BytecodeFrom, BytecodeGenerator, Opcode};
+    use analyzer::{resolution::Resolutions, Analyzer};
+    use parser::parse;
+
+    use crate::{
+        chunk::Constant, generate_unoptimized_bytecode, BytecodeFrom, BytecodeGenerator,
+        GenerationResult, Opcode, ProgramBytecode,
+    };
+
+    // Generates a whole program, with variables resolved by the analyzer.
+    // Nothing gets optimized, so every variable and opcode stays in the code.
+    // Analysis errors are the analyzer's to test, generating only needs the resolutions
+    pub(crate) fn try_generate_program(code: &str) -> GenerationResult {
+        let ast = parse(code).expect("Parsing failed");
+        let mut analyzer = Analyzer::new();
+        let _ = analyzer.analyze(&ast);
+
+        generate_unoptimized_bytecode(ast, analyzer.resolutions())
+    }
+
+    pub(crate) fn generate_program(code: &str) -> ProgramBytecode {
+        try_generate_program(code).expect("Generation failed")
+    }
 
     pub(crate) fn assert_bytecode<D>(data: D, expected_bytecode: Vec<Opcode>)
     where
-        BytecodeGenerator: BytecodeFrom<D>,
+        for<'r> BytecodeGenerator<'r>: BytecodeFrom<D>,
     {
-        let mut generator = BytecodeGenerator::new();
+        let resolutions = Resolutions::default();
+        let mut generator = BytecodeGenerator::new(&resolutions);
         generator.generate(data).expect("Generation failed");
-        assert_eq!(generator.code().chunk.opcodes, expected_bytecode)
+        assert_eq!(
+            generator.code().main_function().chunk.opcodes,
+            expected_bytecode
+        )
     }
 
     pub(crate) fn assert_constants<D>(data: D, expected_constants: Vec<Constant>)
     where
-        BytecodeGenerator: BytecodeFrom<D>,
+        for<'r> BytecodeGenerator<'r>: BytecodeFrom<D>,
     {
-        let mut generator = BytecodeGenerator::new();
+        let resolutions = Resolutions::default();
+        let mut generator = BytecodeGenerator::new(&resolutions);
         generator.generate(data).expect("Generation failed");
-        assert_eq!(generator.code().chunk.constants, expected_constants)
+        assert_eq!(
+            generator.code().main_function().chunk.constants,
+            expected_constants
+        )
     }
 
     pub(crate) fn assert_bytecode_and_constants<D: Clone>(
@@ -353,7 +508,7 @@ pub(crate) mod test {
         expected_bytecode: Vec<Opcode>,
         expected_constants: Vec<Constant>,
     ) where
-        BytecodeGenerator: BytecodeFrom<D>,
+        for<'r> BytecodeGenerator<'r>: BytecodeFrom<D>,
     {
         assert_bytecode(data.clone(), expected_bytecode);
         assert_constants(data, expected_constants);
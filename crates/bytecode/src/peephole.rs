@@ -0,0 +1,287 @@
+use std::collections::HashSet;
+
+use crate::{
+    chunk::{Chunk, OpcodeIndex, SpanTable},
+    Opcode, ProgramBytecode, MAX_JUMP,
+};
+
+// Cleans up patterns the generator leaves behind, e.g. jumps over nothing or jumps to jumps.
+// Every rewrite leaves the stack the way the original opcodes would, on every path.
+pub fn optimize(program: &mut ProgramBytecode) {
+    for global in &mut program.globals {
+        optimize_chunk(&mut global.as_function_mut().chunk);
+    }
+}
+
+pub fn optimize_chunk(chunk: &mut Chunk) {
+    // Removed opcodes bring others next to each other, so it goes on until nothing changes
+    loop {
+        let threaded = thread_jumps(&mut chunk.opcodes);
+        let simplified = simplify(chunk);
+        if !threaded && !simplified {
+            break;
+        }
+    }
+}
+
+// Pointer moves by one after every jump, so all of them land right after index + distance
+fn jump_target(index: OpcodeIndex, opcode: Opcode) -> Option<OpcodeIndex> {
+    match opcode {
+        Opcode::Jif(distance) | Opcode::Jp(distance) | Opcode::Break(distance) => {
+            Some((index as isize + distance + 1) as usize)
+        }
+        _ => None,
+    }
+}
+
+fn is_no_op(opcode: Opcode) -> bool {
+    matches!(
+        opcode,
+        Opcode::Jp(0) | Opcode::Break(0) | Opcode::Pop(0) | Opcode::Block(0)
+    )
+}
+
+// Jumps landing on an unconditional jump can go straight to where that one leads
+fn thread_jumps(opcodes: &mut [Opcode]) -> bool {
+    let mut changed = false;
+
+    for index in 0..opcodes.len() {
+        let opcode = opcodes[index];
+        let mut target = match jump_target(index, opcode) {
+            Some(target) => target,
+            None => continue,
+        };
+
+        // Jumps that only lead to each other loop forever, it's enough to land anywhere in them
+        let mut visited = vec![target];
+        while let Some(next) = opcodes
+            .get(target)
+            .filter(|opcode| matches!(opcode, Opcode::Jp(_)))
+            .and_then(|opcode| jump_target(target, *opcode))
+        {
+            if visited.contains(&next) {
+                break;
+            }
+            visited.push(next);
+            target = next;
+        }
+
+        let distance = target as isize - index as isize - 1;
+        if distance.abs() <= MAX_JUMP && opcode.patch(distance) != opcode {
+            opcodes[index] = opcode.patch(distance);
+            changed = true;
+        }
+    }
+
+    changed
+}
+
+// Removes opcodes that don't do anything and merges the ones that can be done at once
+fn simplify(chunk: &mut Chunk) -> bool {
+    let opcodes = &chunk.opcodes;
+    let targets: HashSet<OpcodeIndex> = opcodes
+        .iter()
+        .enumerate()
+        .filter_map(|(index, opcode)| jump_target(index, *opcode))
+        .collect();
+    let mut rewritten: Vec<Option<Opcode>> = opcodes.iter().copied().map(Some).collect();
+
+    for index in 0..rewritten.len() {
+        let opcode = match rewritten[index] {
+            Some(opcode) if is_no_op(opcode) => {
+                rewritten[index] = None;
+                continue;
+            }
+            Some(opcode) => opcode,
+            None => continue,
+        };
+
+        // Pairs are folded into their second opcode, which paths jumping to the first one
+        // also go through. Paths jumping straight to the second one would be changed too.
+        let next = index + 1;
+        if targets.contains(&next) {
+            continue;
+        }
+
+        let merged = match (opcode, rewritten.get(next).copied().flatten()) {
+            (Opcode::Null | Opcode::Constant(_), Some(Opcode::Pop(amount))) if amount > 0 => {
+                Opcode::Pop(amount - 1)
+            }
+            (Opcode::Pop(first), Some(Opcode::Pop(second))) => Opcode::Pop(first + second),
+            _ => continue,
+        };
+        rewritten[index] = None;
+        rewritten[next] = Some(merged);
+    }
+
+    let changed = rewritten
+        .iter()
+        .zip(opcodes)
+        .any(|(new, old)| *new != Some(*old));
+    if changed {
+        rebuild(chunk, rewritten);
+    }
+    changed
+}
+
+// Jumps to removed opcodes land on the next opcode that's left
+fn rebuild(chunk: &mut Chunk, rewritten: Vec<Option<Opcode>>) {
+    let mut new_indices = Vec::with_capacity(rewritten.len() + 1);
+    let mut kept = 0;
+    for opcode in &rewritten {
+        new_indices.push(kept);
+        if opcode.is_some() {
+            kept += 1;
+        }
+    }
+    // End of the chunk
+    new_indices.push(kept);
+
+    let mut opcodes = Vec::with_capacity(kept);
+    let mut spans = SpanTable::new(chunk.spans.file().map(str::to_owned), vec![]);
+
+    for (index, opcode) in rewritten.into_iter().enumerate() {
+        let opcode = match opcode {
+            Some(opcode) => opcode,
+            None => continue,
+        };
+        let new_index = new_indices[index];
+
+        let opcode = match jump_target(index, opcode) {
+            Some(target) => opcode.patch(new_indices[target] as isize - new_index as isize - 1),
+            None => opcode,
+        };
+        opcodes.push(opcode);
+
+        if let Some(span) = chunk.spans.span_at(index) {
+            spans.record(new_index, span.clone());
+        }
+    }
+
+    chunk.opcodes = opcodes;
+    chunk.spans = spans;
+}
+
+#[cfg(test)]
+mod test {
+    use super::optimize_chunk;
+    use crate::{
+        chunk::{Chunk, Constant, SpanTable},
+        Opcode,
+    };
+
+    fn optimized(opcodes: Vec<Opcode>) -> Vec<Opcode> {
+        let mut chunk = Chunk::new(opcodes, vec![Constant::Bool(true)]);
+        optimize_chunk(&mut chunk);
+        chunk.opcodes
+    }
+
+    #[test]
+    fn removes_no_ops() {
+        assert_eq!(
+            optimized(vec![
+                Opcode::Null,
+                Opcode::Jp(0),
+                Opcode::Block(0),
+                Opcode::Null,
+                Opcode::Pop(1),
+            ]),
+            vec![Opcode::Null]
+        );
+        // Jumps over the removed opcodes get shorter
+        assert_eq!(
+            optimized(vec![
+                Opcode::Constant(0),
+                Opcode::Jif(3),
+                Opcode::Null,
+                Opcode::Pop(1),
+                Opcode::Jp(0),
+                Opcode::Null,
+            ]),
+            vec![Opcode::Constant(0), Opcode::Jif(0), Opcode::Null]
+        );
+    }
+
+    #[test]
+    fn merges_pops() {
+        assert_eq!(
+            optimized(vec![
+                Opcode::Null,
+                Opcode::Null,
+                Opcode::Null,
+                Opcode::Eq,
+                Opcode::Pop(1),
+                Opcode::Pop(1),
+                Opcode::Null,
+            ]),
+            vec![
+                Opcode::Null,
+                Opcode::Null,
+                Opcode::Null,
+                Opcode::Eq,
+                Opcode::Pop(2),
+                Opcode::Null,
+            ]
+        );
+        // If without else as a statement, Pop is also reached by the path that skipped Null
+        let if_statement = vec![
+            Opcode::Constant(0),
+            Opcode::Jif(2),
+            Opcode::Null,
+            Opcode::Jp(1),
+            Opcode::Null,
+            Opcode::Pop(1),
+            Opcode::Null,
+        ];
+        assert_eq!(optimized(if_statement.clone()), if_statement);
+    }
+
+    #[test]
+    fn threads_jump_chains() {
+        // Inner if else jumps to the end of the outer one
+        assert_eq!(
+            optimized(vec![
+                Opcode::Constant(0),
+                Opcode::Jif(6),
+                Opcode::Constant(0),
+                Opcode::Jif(2),
+                Opcode::Null,
+                Opcode::Jp(1),
+                Opcode::Null,
+                Opcode::Jp(1),
+                Opcode::Null,
+                Opcode::Null,
+            ]),
+            vec![
+                Opcode::Constant(0),
+                Opcode::Jif(6),
+                Opcode::Constant(0),
+                Opcode::Jif(2),
+                Opcode::Null,
+                Opcode::Jp(3),
+                Opcode::Null,
+                Opcode::Jp(1),
+                Opcode::Null,
+                Opcode::Null,
+            ]
+        );
+        // Loops of jumps stay where they are
+        assert_eq!(
+            optimized(vec![Opcode::Jp(0), Opcode::Jp(-1)]),
+            vec![Opcode::Jp(-1)]
+        );
+    }
+
+    #[test]
+    fn keeps_spans_of_remaining_opcodes() {
+        let mut chunk = Chunk::new(
+            vec![Opcode::Null, Opcode::Pop(1), Opcode::Null, Opcode::Jp(0)],
+            vec![],
+        );
+        chunk.spans = SpanTable::from(vec![(0, 0..4), (2, 5..9), (3, 10..12)]);
+        optimize_chunk(&mut chunk);
+
+        assert_eq!(chunk.opcodes, vec![Opcode::Null]);
+        assert_eq!(chunk.spans, SpanTable::from(vec![(0, 5..9)]));
+    }
+}
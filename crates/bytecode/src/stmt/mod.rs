@@ -1,21 +1,23 @@
 use std::fmt::Display;
 
 use crate::{
-    callables::Function, chunk::Constant, BytecodeFrom, BytecodeGenerationResult,
-    BytecodeGenerator, MemoryAddress, Opcode,
+    callables::Function,
+    chunk::Constant,
+    error::{GenerationError, GenerationErrorCause},
+    BytecodeFrom, BytecodeGenerationResult, BytecodeGenerator, MemoryAddress, Opcode, MAX_LOCALS,
+    MAX_UPVALUES,
 };
-use common::ProgramText;
 use parser::parse::{
     expr::ExprKind,
     stmt::{Stmt, StmtKind},
-    FunctionBody, Params,
+    FunctionBody, Params, Span,
 };
 
 mod var;
 
 pub type GlobalPointer = usize;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum GlobalItem {
     Function(Function),
 }
@@ -34,6 +36,12 @@ impl GlobalItem {
             GlobalItem::Function(function) => function,
         }
     }
+
+    pub fn as_function_mut(&mut self) -> &mut Function {
+        match self {
+            GlobalItem::Function(function) => function,
+        }
+    }
 }
 
 impl From<Function> for GlobalItem {
@@ -50,21 +58,19 @@ impl Display for GlobalItem {
     }
 }
 
-impl BytecodeGenerator {
+impl BytecodeGenerator<'_> {
+    // Span is the whole function, blamed when it doesn't fit into a chunk
     pub(crate) fn compile_function(
         &mut self,
         name: String,
+        span: &Span,
         params: Params,
         body: FunctionBody,
-    ) -> Result<Function, ()> {
-        self.new_function(name.clone(), params.kind.len());
-
-        for param in params.kind {
-            self.state.declare_var(param.kind);
+    ) -> Result<Function, GenerationError> {
+        for param in &params.kind {
+            self.check_slot(&param.span)?;
         }
-
-        self.state.declare_var(name.clone());
-        self.state.declare_var("this".to_owned());
+        self.new_function(name, params.kind.len());
 
         match *body.kind {
             ExprKind::Block { stmts, return_expr } => {
@@ -86,66 +92,120 @@ impl BytecodeGenerator {
             }
         };
 
+        self.check_constants(span)?;
         let new_fn = self
             .functions
             .pop()
             .expect("We just defined and evaluated function. It shouldn't happen.");
-        self.leave_scope();
+        self.leave_scope(span)?;
 
-        return Ok(new_fn);
+        Ok(new_fn)
+    }
+
+    // Functions and closures are both created at runtime from a global and their captures
+    pub(crate) fn generate_closure(
+        &mut self,
+        name: String,
+        span: &Span,
+        params: Params,
+        body: FunctionBody,
+    ) -> BytecodeGenerationResult {
+        let captures = self.resolutions.captures(span);
+        if captures.len() > MAX_UPVALUES {
+            return self.error(span, GenerationErrorCause::TooManyUpvalues);
+        }
+
+        let mut new_fn = self.compile_function(name, span, params, body)?;
+        new_fn.pure = self.resolutions.is_pure(span);
+        let fn_ptr = self.declare_global(new_fn.into());
+
+        self.write_constant(Constant::GlobalPointer(fn_ptr));
+
+        // VM pops the addresses, so the last one written becomes the first upvalue
+        for capture in captures.iter().rev() {
+            self.write_constant(MemoryAddress::from(capture).into());
+        }
+
+        self.write_opcode(Opcode::CreateClosure(captures.len()));
+        Ok(())
+    }
+
+    // Frame outgrows its slots where a variable is declared, not where it's used
+    fn check_slot(&self, declaration: &Span) -> BytecodeGenerationResult {
+        match self.resolutions.slot(declaration) {
+            Some(slot) if slot >= MAX_LOCALS => {
+                self.error(declaration, GenerationErrorCause::TooManyLocals)
+            }
+            _ => Ok(()),
+        }
     }
 
     pub fn declare_global(&mut self, item: GlobalItem) -> GlobalPointer {
-        self.state.declare_var(item.name().clone());
         self.globals.push(item);
         self.globals.len() - 1
     }
 }
 
-impl BytecodeFrom<Stmt> for BytecodeGenerator {
+impl BytecodeFrom<Stmt> for BytecodeGenerator<'_> {
     fn generate(&mut self, stmt: Stmt) -> BytecodeGenerationResult {
+        self.spanned(stmt.span.clone(), |generator| generator.generate_stmt(stmt))
+    }
+}
+
+impl BytecodeGenerator<'_> {
+    fn generate_stmt(&mut self, stmt: Stmt) -> BytecodeGenerationResult {
         match *stmt.kind {
+            // Only declarations keep their values on the stack
             StmtKind::Expression { expr } => {
                 self.generate(expr)?;
+                self.write_opcode(Opcode::Pop(1));
             }
-            StmtKind::VariableDeclaration { name, expr } => {
+            // Value stays on the stack, in the slot chosen by the analyzer
+            StmtKind::VariableDeclaration { expr, .. } => {
+                self.check_slot(&stmt.span)?;
                 self.generate(expr)?;
-                self.state.declare_var(name);
+                self.state.current_scope_mut().declared += 1;
             }
-            StmtKind::FunctionDeclaration { name, params, body } => {
-                let new_fn = self.compile_function(name.clone(), params, body)?;
-                let fn_ptr = self.declare_global(new_fn.into());
-
-                let (upvalues_addresses, upvalues_count) = {
-                    let upvalues = self.state.scope_upvalues();
-                    let count = upvalues.len();
-                    let addresses: Vec<Constant> = upvalues
-                        .iter()
-                        .map(|upvalue| {
-                            // It's still on the stack because depth 1 means that it's the function in which closure is declared
-                            if upvalue.is_local {
-                                Constant::MemoryAddress(MemoryAddress::Local(upvalue.local_index))
-                            } else {
-                                Constant::MemoryAddress(MemoryAddress::Upvalue {
-                                    index: upvalue.upvalue_index,
-                                    is_ref: upvalue.is_ref,
-                                })
-                            }
-                        })
-                        .collect();
-
-                    (addresses, count)
-                };
-
-                self.write_constant(Constant::GlobalPointer(fn_ptr));
-
-                for upvalue_address in upvalues_addresses {
-                    self.write_constant(upvalue_address);
-                }
-
-                self.write_opcode(Opcode::CreateClosure(upvalues_count));
+            StmtKind::FunctionDeclaration {
+                name, params, body, ..
+            } => {
+                self.check_slot(&stmt.span)?;
+                self.generate_closure(name, &stmt.span, params, body)?;
+                self.state.current_scope_mut().declared += 1;
+            }
+            // VM doesn't know how to construct classes yet
+            StmtKind::ClassDeclaration { .. } => {
+                return self.error(&stmt.span, GenerationErrorCause::Unsupported("Classes"));
             }
         }
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::test::generate_program;
+
+    #[test]
+    fn marks_pure_functions() {
+        let bytecode = generate_program(
+            "fn add(a, b) => a + b fn log(a) => print(a) fn add_and_log(a) => log(add(a, a))",
+        );
+        let purity: Vec<(&str, bool)> = bytecode
+            .globals
+            .iter()
+            .map(|global| global.as_function())
+            .map(|function| (function.name.as_str(), function.pure))
+            .collect();
+
+        assert_eq!(
+            purity,
+            vec![
+                ("add", true),
+                ("log", false),
+                ("add_and_log", false),
+                ("main", false)
+            ]
+        );
+    }
+}
@@ -1,38 +1,98 @@
 #[cfg(test)]
 mod test {
-    use parser::parse::expr::atom::AtomicValue;
+    use analyzer::analyze;
+    use parser::{parse, utils::error::ParseErrorCause};
 
-    use crate::{
-        chunk::Constant,
-        test::{declare_var, expr, expr_stmt},
-        BytecodeFrom, BytecodeGenerator, MemoryAddress,
-    };
+    use crate::{chunk::Constant, test::generate_program, MemoryAddress, ProgramBytecode};
+
+    fn addresses(bytecode: &ProgramBytecode, function: &str) -> Vec<MemoryAddress> {
+        let function = bytecode
+            .globals
+            .iter()
+            .map(|global| global.as_function())
+            .find(|global| global.name == function)
+            .unwrap();
+
+        function
+            .chunk
+            .constants
+            .iter()
+            .filter_map(|constant| match constant {
+                Constant::MemoryAddress(address) => Some(address.clone()),
+                _ => None,
+            })
+            .collect()
+    }
 
     #[test]
     fn finds_local_variable() {
-        let mut generator = BytecodeGenerator::new();
-        let data = vec![
-            declare_var("local".to_owned(), expr(AtomicValue::Number(0.0))),
-            expr_stmt(expr(AtomicValue::Identifier {
-                name: "local".to_owned(),
-                is_assignment: false,
-            })),
-        ];
-
-        generator
-            .generate(data)
-            .expect("Failed to generate bytecode which finds local variable.");
-
-        let bytecode = generator.code().chunk;
+        let bytecode = generate_program("let local = 0; local;");
         assert_eq!(
-            bytecode.constants[1],
+            bytecode.main_function().chunk.constants[1],
             Constant::MemoryAddress(MemoryAddress::Local(0))
         )
     }
 
-    fn finds_variable_in_upper_scope() {}
+    #[test]
+    fn places_variables_in_slots() {
+        use MemoryAddress::*;
+
+        // slots of a block are free again after it ends
+        let bytecode = generate_program("let a = 1; { let b = 2; b; }; let c = 3; c;");
+        assert_eq!(addresses(&bytecode, "main"), vec![Local(1), Local(1)]);
+
+        // arguments go first, then the function itself and `this`
+        let bytecode = generate_program("fn foo(x, y) { let z = x + y; foo; this; z }");
+        assert_eq!(
+            addresses(&bytecode, "foo"),
+            vec![Local(0), Local(1), Local(2), Local(3), Local(4)]
+        );
+    }
+
+    #[test]
+    fn this_outside_of_a_class_is_an_error() {
+        let ast = parse("fn foo(x, y) { this }").unwrap();
+        assert_eq!(
+            analyze(&ast)
+                .unwrap_err()
+                .into_iter()
+                .map(|error| error.cause)
+                .collect::<Vec<_>>(),
+            vec![ParseErrorCause::UsedOutsideClass]
+        );
+    }
 
-    fn finds_global_variable() {}
+    #[test]
+    fn captures_upvalues() {
+        use MemoryAddress::*;
 
-    fn finds_closed_variable() {}
+        let bytecode =
+            generate_program("let a = 1; fn outer() { let b = 2; fn inner() { a + b } inner }");
+        // closures are created with the addresses of the captured variables, last one first
+        assert_eq!(addresses(&bytecode, "main"), vec![Local(0)]);
+        assert_eq!(
+            addresses(&bytecode, "outer"),
+            vec![
+                Local(2),
+                Upvalue {
+                    index: 0,
+                    is_ref: false
+                },
+                Local(3)
+            ]
+        );
+        assert_eq!(
+            addresses(&bytecode, "inner"),
+            vec![
+                Upvalue {
+                    index: 0,
+                    is_ref: true
+                },
+                Upvalue {
+                    index: 1,
+                    is_ref: false
+                }
+            ]
+        );
+    }
 }
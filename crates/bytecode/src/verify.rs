@@ -0,0 +1,639 @@
+use std::collections::HashMap;
+
+use codespan_reporting::diagnostic::Diagnostic;
+use common::{CompilerDiagnostic, ProgramText};
+
+use crate::{
+    callables::Function,
+    chunk::{Chunk, Constant, ConstantIndex, OpcodeIndex},
+    stmt::GlobalPointer,
+    MemoryAddress, Opcode, ProgramBytecode,
+};
+
+// Bytecode loaded from a file could be written by anything,
+// so it's checked before the VM trusts it with its stack and globals
+#[derive(Debug, Clone, PartialEq)]
+pub struct VerificationError {
+    pub function: ProgramText,
+    // Missing when the whole function is at fault
+    pub opcode: Option<OpcodeIndex>,
+    pub cause: VerificationErrorCause,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum VerificationErrorCause {
+    ConstantOutOfBounds {
+        index: ConstantIndex,
+        constants: usize,
+    },
+    // Jumps can land on any opcode of their chunk or right after the last one
+    JumpOutOfBounds {
+        target: isize,
+    },
+    InvalidGlobalPointer(GlobalPointer),
+    // Local variable above the values the function has on its stack
+    InvalidLocal {
+        slot: usize,
+        depth: usize,
+    },
+    // Upvalue that the closures of the function don't capture
+    InvalidUpvalue {
+        index: usize,
+        upvalues: usize,
+    },
+    // Assignment to a value that isn't the address of a local variable or an upvalue
+    NotAssignable,
+    // Closure is created from something else than a function and the addresses it captures
+    InvalidClosure,
+    StackUnderflow {
+        depth: usize,
+        popped: usize,
+    },
+    // Paths leading to the same opcode leave different amounts of values on the stack
+    UnbalancedStack {
+        expected: usize,
+        found: usize,
+    },
+    // Function can get to the end of its chunk without returning
+    MissingReturn,
+    // Main function finishes the program by running out of opcodes instead
+    ReturnFromMain,
+}
+
+impl CompilerDiagnostic for VerificationError {
+    fn report(&self, _file_id: usize) -> Diagnostic<usize> {
+        use VerificationErrorCause::*;
+
+        let problem = match &self.cause {
+            ConstantOutOfBounds { index, constants } => format!(
+                "refers to constant {}, but there are only {}",
+                index, constants
+            ),
+            JumpOutOfBounds { target } => format!("jumps to {}, outside of its chunk", target),
+            InvalidGlobalPointer(ptr) => format!("refers to global {} that doesn't exist", ptr),
+            InvalidLocal { slot, depth } => format!(
+                "refers to local {}, but there are only {} values on the stack",
+                slot, depth
+            ),
+            InvalidUpvalue { index, upvalues } => format!(
+                "refers to upvalue {}, but the function captures only {}",
+                index, upvalues
+            ),
+            NotAssignable => "assigns to something that isn't a variable".to_owned(),
+            InvalidClosure => {
+                "creates a closure without a function and the addresses it captures".to_owned()
+            }
+            StackUnderflow { depth, popped } => {
+                format!("pops {} values from a stack that holds {}", popped, depth)
+            }
+            UnbalancedStack { expected, found } => format!(
+                "is reached with {} values on the stack, but also with {}",
+                found, expected
+            ),
+            MissingReturn => "doesn't return".to_owned(),
+            ReturnFromMain => "returns from the main function".to_owned(),
+        };
+
+        let location = match self.opcode {
+            Some(opcode) => format!("Opcode {} of function '{}'", opcode, self.function),
+            None => format!("Function '{}'", self.function),
+        };
+
+        Diagnostic::error()
+            .with_message(format!("Invalid bytecode: {} {}", location, problem))
+            .with_notes(vec!["try building the program again".to_owned()])
+    }
+}
+
+// Values an opcode pops from the stack and pushes back onto it
+fn stack_effect(opcode: Opcode) -> (usize, usize) {
+    use Opcode::*;
+
+    match opcode {
+        Constant(_) | Null => (0, 1),
+        Add | Sub | Mul | Div | Mod | Pow | Eq | Ne | Lt | Le | Gt | Ge | Or | And => (2, 1),
+        Neg | Not | Get => (1, 1),
+        Jif(_) => (1, 0),
+        Jp(_) | Break(_) => (0, 0),
+        Pop(amount) => (amount, 0),
+        GetProperty { .. } | GetIndex | Asg => (2, 1),
+        SetProperty(_) | SetIndex => (3, 1),
+        Call(amount) | CreateClosure(amount) | Block(amount) => (amount + 1, 1),
+        Return => (1, 0),
+        CreateObject(amount) => (amount * 2, 1),
+        CreateArray(amount) => (amount, 1),
+    }
+}
+
+// Opcodes where the execution continues, the pointer moves by one after every jump
+fn successors(index: OpcodeIndex, opcode: Opcode) -> Vec<isize> {
+    let next = index as isize + 1;
+
+    match opcode {
+        Opcode::Jif(distance) => vec![next, next + distance],
+        Opcode::Jp(distance) | Opcode::Break(distance) => vec![next + distance],
+        Opcode::Return => vec![],
+        _ => vec![next],
+    }
+}
+
+// What the verifier knows about a value on the stack
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Value {
+    // Address of a local variable or an upvalue, written as a constant
+    Assignable,
+    Function(GlobalPointer),
+    Unknown,
+}
+
+impl Value {
+    fn of(constant: &Constant) -> Self {
+        match constant {
+            Constant::MemoryAddress(MemoryAddress::Local(_) | MemoryAddress::Upvalue { .. }) => {
+                Value::Assignable
+            }
+            Constant::GlobalPointer(ptr) => Value::Function(*ptr),
+            _ => Value::Unknown,
+        }
+    }
+
+    // Value that arrives at an opcode through two different paths
+    fn join(self, other: Self) -> Self {
+        if self == other {
+            self
+        } else {
+            Value::Unknown
+        }
+    }
+}
+
+struct Verifier<'p> {
+    program: &'p ProgramBytecode,
+    errors: Vec<VerificationError>,
+    // Fewest upvalues the closures of a function are created with
+    upvalues: HashMap<GlobalPointer, usize>,
+    // Function, opcode and index of every upvalue address
+    upvalue_uses: Vec<(GlobalPointer, OpcodeIndex, usize)>,
+}
+
+impl<'p> Verifier<'p> {
+    fn report(
+        &mut self,
+        function: &Function,
+        opcode: Option<OpcodeIndex>,
+        cause: VerificationErrorCause,
+    ) {
+        let error = VerificationError {
+            function: function.name.clone(),
+            opcode,
+            cause,
+        };
+        // Every path through a faulty opcode would report it again
+        if !self.errors.contains(&error) {
+            self.errors.push(error);
+        }
+    }
+
+    // Opcodes are checked on their own first, so the stack simulation can rely on them
+    fn check_opcodes(&mut self, ptr: GlobalPointer, function: &Function) -> bool {
+        let chunk = &function.chunk;
+        let errors = self.errors.len();
+
+        for (index, opcode) in chunk.opcodes.iter().copied().enumerate() {
+            if let Opcode::Constant(constant) = opcode {
+                match chunk.constants.get(constant) {
+                    None => self.report(
+                        function,
+                        Some(index),
+                        VerificationErrorCause::ConstantOutOfBounds {
+                            index: constant,
+                            constants: chunk.constants.len(),
+                        },
+                    ),
+                    Some(Constant::GlobalPointer(ptr)) if *ptr >= self.program.globals.len() => {
+                        self.report(
+                            function,
+                            Some(index),
+                            VerificationErrorCause::InvalidGlobalPointer(*ptr),
+                        )
+                    }
+                    // Closures are known only once every function has been simulated
+                    Some(Constant::MemoryAddress(MemoryAddress::Upvalue {
+                        index: upvalue,
+                        ..
+                    })) => self.upvalue_uses.push((ptr, index, *upvalue)),
+                    Some(_) => {}
+                }
+            }
+
+            for target in successors(index, opcode) {
+                if target < 0 || target > chunk.opcodes.len() as isize {
+                    self.report(
+                        function,
+                        Some(index),
+                        VerificationErrorCause::JumpOutOfBounds { target },
+                    );
+                }
+            }
+        }
+
+        self.errors.len() == errors
+    }
+
+    // Follows every path through the function, keeping track of the values it put on the stack
+    fn check_stack(&mut self, function: &Function, is_main: bool) {
+        let chunk = &function.chunk;
+        let opcodes = &chunk.opcodes;
+        // Called functions start with their arguments, themselves and `this`
+        let initial_depth = if is_main { 0 } else { function.arity + 2 };
+        // One more for the end of the chunk
+        let mut stacks: Vec<Option<Vec<Value>>> = vec![None; opcodes.len() + 1];
+        let mut pending = vec![(0, vec![Value::Unknown; initial_depth])];
+
+        while let Some((index, mut stack)) = pending.pop() {
+            match &stacks[index] {
+                Some(expected) if expected.len() != stack.len() => {
+                    self.report(
+                        function,
+                        Some(index),
+                        VerificationErrorCause::UnbalancedStack {
+                            expected: expected.len(),
+                            found: stack.len(),
+                        },
+                    );
+                    continue;
+                }
+                Some(expected) => {
+                    let joined: Vec<Value> = expected
+                        .iter()
+                        .zip(&stack)
+                        .map(|(expected, value)| expected.join(*value))
+                        .collect();
+                    if &joined == expected {
+                        continue;
+                    }
+                    stack = joined;
+                }
+                None => {}
+            }
+            stacks[index] = Some(stack.clone());
+
+            if index == opcodes.len() {
+                if !is_main {
+                    self.report(function, None, VerificationErrorCause::MissingReturn);
+                }
+                continue;
+            }
+
+            let opcode = opcodes[index];
+            if is_main && opcode == Opcode::Return {
+                self.report(
+                    function,
+                    Some(index),
+                    VerificationErrorCause::ReturnFromMain,
+                );
+                continue;
+            }
+
+            let depth = stack.len();
+            let (popped, pushed) = stack_effect(opcode);
+            if popped > depth {
+                self.report(
+                    function,
+                    Some(index),
+                    VerificationErrorCause::StackUnderflow { depth, popped },
+                );
+                continue;
+            }
+
+            if let Some(cause) = self.check_operands(opcode, chunk, &stack) {
+                self.report(function, Some(index), cause);
+                continue;
+            }
+
+            let pushed_value = match opcode {
+                Opcode::Constant(constant) => Value::of(&chunk.constants[constant]),
+                _ => Value::Unknown,
+            };
+            stack.truncate(depth - popped);
+            stack.resize(depth - popped + pushed, pushed_value);
+
+            for target in successors(index, opcode) {
+                pending.push((target as usize, stack.clone()));
+            }
+        }
+    }
+
+    // Values the opcode works with have to be what the VM expects them to be
+    fn check_operands(
+        &mut self,
+        opcode: Opcode,
+        chunk: &Chunk,
+        stack: &[Value],
+    ) -> Option<VerificationErrorCause> {
+        let depth = stack.len();
+
+        match opcode {
+            Opcode::Constant(constant) => match chunk.constants[constant] {
+                Constant::MemoryAddress(MemoryAddress::Local(slot)) if slot >= depth => {
+                    Some(VerificationErrorCause::InvalidLocal { slot, depth })
+                }
+                _ => None,
+            },
+            // Address is below the assigned value
+            Opcode::Asg if stack[depth - 2] != Value::Assignable => {
+                Some(VerificationErrorCause::NotAssignable)
+            }
+            Opcode::CreateClosure(amount) => {
+                let captures = &stack[depth - amount..];
+                match stack[depth - amount - 1] {
+                    Value::Function(ptr)
+                        if captures.iter().all(|value| *value == Value::Assignable) =>
+                    {
+                        let upvalues = self.upvalues.entry(ptr).or_insert(amount);
+                        *upvalues = amount.min(*upvalues);
+                        None
+                    }
+                    _ => Some(VerificationErrorCause::InvalidClosure),
+                }
+            }
+            _ => None,
+        }
+    }
+
+    fn check_upvalues(&mut self) {
+        for (ptr, opcode, index) in std::mem::take(&mut self.upvalue_uses) {
+            // Functions that no closure is created for run without upvalues, like main
+            let upvalues = self.upvalues.get(&ptr).copied().unwrap_or(0);
+            if index >= upvalues {
+                let function = self.program.globals[ptr].as_function();
+                self.report(
+                    function,
+                    Some(opcode),
+                    VerificationErrorCause::InvalidUpvalue { index, upvalues },
+                );
+            }
+        }
+    }
+}
+
+pub fn verify(program: &ProgramBytecode) -> Result<(), Vec<VerificationError>> {
+    let mut verifier = Verifier {
+        program,
+        errors: vec![],
+        upvalues: HashMap::new(),
+        upvalue_uses: vec![],
+    };
+
+    if program.global_fn_ptr >= program.globals.len() {
+        verifier.errors.push(VerificationError {
+            function: "main".to_owned(),
+            opcode: None,
+            cause: VerificationErrorCause::InvalidGlobalPointer(program.global_fn_ptr),
+        });
+    }
+
+    for (ptr, global) in program.globals.iter().enumerate() {
+        let function = global.as_function();
+        if verifier.check_opcodes(ptr, function) {
+            verifier.check_stack(function, ptr == program.global_fn_ptr);
+        }
+    }
+    // Closures of a faulty function might not have been seen
+    if verifier.errors.is_empty() {
+        // Main function is run without any upvalues
+        verifier.upvalues.insert(program.global_fn_ptr, 0);
+        verifier.check_upvalues();
+    }
+
+    if verifier.errors.is_empty() {
+        Ok(())
+    } else {
+        Err(verifier.errors)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use common::{BuiltInFunction, LAMBDA_NAME};
+
+    use super::{verify, VerificationError, VerificationErrorCause::*};
+    use crate::{
+        callables::Function,
+        chunk::{Chunk, Constant},
+        test::generate_program,
+        MemoryAddress, Opcode, ProgramBytecode,
+    };
+
+    // Program whose main function runs the given chunk
+    fn verify_main(opcodes: Vec<Opcode>, constants: Vec<Constant>) -> Vec<VerificationError> {
+        let main = Function {
+            arity: 0,
+            chunk: Chunk::new(opcodes, constants),
+            name: "main".to_owned(),
+            pure: false,
+        };
+        let program = ProgramBytecode {
+            global_fn_ptr: 0,
+            globals: vec![main.into()],
+        };
+
+        verify(&program).err().unwrap_or_default()
+    }
+
+    fn causes(errors: Vec<VerificationError>) -> Vec<super::VerificationErrorCause> {
+        errors.into_iter().map(|error| error.cause).collect()
+    }
+
+    #[test]
+    fn accepts_generated_programs() {
+        let programs = [
+            "let a = 1; print(a); let b = [a, 2]; b[0] = 3; b;",
+            "fn fib(n) { if n < 2 { n } else { fib(n - 1) + fib(n - 2) } } fib(10);",
+            "fn make() { let count = 0; |step| => { count = count + step; count } } make()(2);",
+            "let i = 0; while i < 10 { let next = i + 1; if next == 5 { break; }; i = next; };",
+            "let i = 0; while i < 3 { let a = i; i = a + 1; if a < 2 { continue; }; };",
+            "let point = new { x: 1, y: 2 }; point.x = point.y; point.x;",
+            "",
+        ];
+
+        for code in programs {
+            assert_eq!(verify(&generate_program(code)), Ok(()), "{}", code);
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_references() {
+        assert_eq!(
+            causes(verify_main(
+                vec![Opcode::Constant(1)],
+                vec![Constant::Bool(true)]
+            )),
+            vec![ConstantOutOfBounds {
+                index: 1,
+                constants: 1
+            }]
+        );
+        assert_eq!(
+            causes(verify_main(
+                vec![Opcode::Constant(0), Opcode::CreateClosure(0)],
+                vec![Constant::GlobalPointer(3)]
+            )),
+            vec![InvalidGlobalPointer(3)]
+        );
+        assert_eq!(
+            causes(verify_main(vec![Opcode::Null, Opcode::Jp(5)], vec![])),
+            vec![JumpOutOfBounds { target: 7 }]
+        );
+        assert_eq!(
+            causes(verify_main(vec![Opcode::Jp(-2)], vec![])),
+            vec![JumpOutOfBounds { target: -1 }]
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_addresses() {
+        let local = |slot| Constant::MemoryAddress(MemoryAddress::Local(slot));
+        let upvalue = |index| {
+            Constant::MemoryAddress(MemoryAddress::Upvalue {
+                index,
+                is_ref: false,
+            })
+        };
+
+        assert_eq!(
+            causes(verify_main(
+                vec![Opcode::Null, Opcode::Constant(0), Opcode::Null, Opcode::Asg],
+                vec![local(3)]
+            )),
+            vec![InvalidLocal { slot: 3, depth: 1 }]
+        );
+        assert_eq!(
+            causes(verify_main(
+                vec![Opcode::Constant(0), Opcode::Get, Opcode::Pop(1)],
+                vec![upvalue(0)]
+            )),
+            vec![InvalidUpvalue {
+                index: 0,
+                upvalues: 0
+            }]
+        );
+
+        // Closure captures a single variable
+        let mut program = generate_program("fn make() { let count = 0; || => count }");
+        let closure = program
+            .globals
+            .iter()
+            .position(|global| global.as_function().name == LAMBDA_NAME)
+            .unwrap();
+        let mut function = program.globals[closure].as_function().clone();
+        for constant in &mut function.chunk.constants {
+            if let Constant::MemoryAddress(MemoryAddress::Upvalue { index, .. }) = constant {
+                *index = 1;
+            }
+        }
+        program.globals[closure] = function.into();
+        assert_eq!(
+            causes(verify(&program).unwrap_err()),
+            vec![InvalidUpvalue {
+                index: 1,
+                upvalues: 1
+            }]
+        );
+    }
+
+    #[test]
+    fn rejects_assignments_to_values() {
+        let builtin =
+            Constant::MemoryAddress(MemoryAddress::BuiltInFunction(BuiltInFunction::Print));
+        assert_eq!(
+            causes(verify_main(
+                vec![
+                    Opcode::Constant(0),
+                    Opcode::Null,
+                    Opcode::Asg,
+                    Opcode::Pop(1)
+                ],
+                vec![builtin]
+            )),
+            vec![NotAssignable]
+        );
+
+        // Value of a variable could be anything, even an address
+        let local = Constant::MemoryAddress(MemoryAddress::Local(0));
+        assert_eq!(
+            causes(verify_main(
+                vec![
+                    Opcode::Null,
+                    Opcode::Constant(0),
+                    Opcode::Get,
+                    Opcode::Null,
+                    Opcode::Asg,
+                    Opcode::Pop(2)
+                ],
+                vec![local]
+            )),
+            vec![NotAssignable]
+        );
+
+        assert_eq!(
+            causes(verify_main(
+                vec![Opcode::Null, Opcode::CreateClosure(0), Opcode::Pop(1)],
+                vec![]
+            )),
+            vec![InvalidClosure]
+        );
+    }
+
+    #[test]
+    fn rejects_unbalanced_stacks() {
+        let errors = verify_main(vec![Opcode::Null, Opcode::Add], vec![]);
+        assert_eq!(errors[0].opcode, Some(1));
+        assert_eq!(
+            causes(errors),
+            vec![StackUnderflow {
+                depth: 1,
+                popped: 2
+            }]
+        );
+
+        // Only one of the branches leaves a value behind
+        assert_eq!(
+            causes(verify_main(
+                vec![
+                    Opcode::Constant(0),
+                    Opcode::Jif(1),
+                    Opcode::Null,
+                    Opcode::Null
+                ],
+                vec![Constant::Bool(true)]
+            )),
+            vec![UnbalancedStack {
+                expected: 0,
+                found: 1
+            }]
+        );
+
+        assert_eq!(
+            causes(verify_main(vec![Opcode::Null, Opcode::Return], vec![])),
+            vec![ReturnFromMain]
+        );
+
+        let mut program = generate_program("fn id(a) => a");
+        program.globals[0] = Function {
+            arity: 1,
+            chunk: Chunk::new(vec![Opcode::Pop(1)], vec![]),
+            name: "id".to_owned(),
+            pure: true,
+        }
+        .into();
+        assert_eq!(
+            verify(&program),
+            Err(vec![VerificationError {
+                function: "id".to_owned(),
+                opcode: None,
+                cause: MissingReturn
+            }])
+        );
+    }
+}
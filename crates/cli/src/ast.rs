@@ -0,0 +1,38 @@
+use clap::{Args, ValueEnum};
+use common::source::SourceDatabase;
+use parser::{json::to_json, parse};
+
+use crate::compiler::log_errors;
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub(crate) enum AstFormat {
+    Text,
+    Json,
+}
+
+#[derive(Debug, Args)]
+pub(crate) struct PrintAst {
+    #[arg(short, long)]
+    file_path: String,
+    #[arg(long, value_enum, default_value_t = AstFormat::Text)]
+    format: AstFormat,
+}
+
+impl PrintAst {
+    pub(crate) fn run(&self) {
+        let mut db = SourceDatabase::new();
+        let file_id = db.load(&self.file_path).expect("File not found!");
+        let ast = parse(db.source(file_id))
+            .map_err(|errors| log_errors(errors, &db, file_id))
+            .expect("Parsing failed. See above errors to find out what went wrong.");
+
+        match self.format {
+            AstFormat::Text => {
+                for stmt in &ast {
+                    println!("{}", stmt);
+                }
+            }
+            AstFormat::Json => println!("{}", to_json(&ast)),
+        }
+    }
+}
@@ -0,0 +1,40 @@
+use bytecode::gvc;
+use clap::Args;
+use common::source::SourceDatabase;
+
+use std::{fs::write, path::Path};
+
+use crate::compiler::{compile_bytecode, load_lint_config};
+
+// Compiles a program ahead of time, so it can be run with `run --bytecode`
+#[derive(Debug, Args)]
+pub(crate) struct Build {
+    #[arg(short, long)]
+    file_path: String,
+    // Defaults to the source file with the .gvc extension
+    #[arg(short, long)]
+    output: Option<String>,
+    // Fail the compilation on every lint that would only warn
+    #[arg(long, action)]
+    deny_warnings: bool,
+}
+
+impl Build {
+    pub(crate) fn run(&self) {
+        let mut db = SourceDatabase::new();
+        let file_id = db.load(&self.file_path).expect("File not found!");
+        let mut lints = load_lint_config(Path::new(&self.file_path));
+        if self.deny_warnings {
+            lints.deny_warnings();
+        }
+
+        let bytecode = compile_bytecode(&db, file_id, &lints);
+        let output = match &self.output {
+            Some(output) => Path::new(output).to_path_buf(),
+            None => Path::new(&self.file_path).with_extension(gvc::EXTENSION),
+        };
+
+        write(&output, gvc::write(&bytecode)).expect("Couldn't write the bytecode");
+        println!("Compiled {} into {}", self.file_path, output.display());
+    }
+}
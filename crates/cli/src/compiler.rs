@@ -1,51 +1,133 @@
-use analyzer::analyze;
-use bytecode::generate_bytecode;
-use codespan_reporting::{
-    files::SimpleFiles,
-    term::{
-        self,
-        termcolor::{ColorChoice, StandardStream},
-    },
+use analyzer::{
+    lint::{LintConfig, LintConfigError},
+    warning::Warning,
+    Analyzer,
+};
+use bytecode::{generate_bytecode, ProgramBytecode};
+use codespan_reporting::term::{
+    self,
+    termcolor::{ColorChoice, StandardStream},
+};
+use common::{
+    source::{FileId, SourceDatabase},
+    CompilerDiagnostic,
 };
-use common::CompilerDiagnostic;
 use parser::{parse, parse::Program};
-use std::path::Path;
+use std::{fs::read_to_string, path::Path};
 use vm::{run, runtime_value::RuntimeValue};
 
-pub(crate) fn log_errors(errors: Vec<impl CompilerDiagnostic>, code: &str) {
-    let mut files = SimpleFiles::new();
-    let file_id = files.add("test.vt", code);
+pub(crate) fn log_errors(
+    errors: Vec<impl CompilerDiagnostic>,
+    db: &SourceDatabase,
+    file_id: FileId,
+) {
     let writer = StandardStream::stderr(ColorChoice::Always);
     let config = codespan_reporting::term::Config::default();
 
     for err in errors {
-        term::emit(&mut writer.lock(), &config, &files, &err.report(file_id)).unwrap();
+        let mut diagnostic = err.report(file_id);
+        // Bytecode is run without its source, so its spans have nothing to point at
+        let source_length = db.source(file_id).len();
+        diagnostic
+            .labels
+            .retain(|label| label.range.end <= source_length);
+        // Diagnostics without labels wouldn't mention the file at all
+        if diagnostic.labels.is_empty() {
+            diagnostic.notes.push(format!("in {}", db.name(file_id)));
+        }
+        term::emit(&mut writer.lock(), &config, db, &diagnostic).unwrap();
     }
 }
 
-pub(crate) fn compile(code: &str) -> Program {
-    parse(code)
-        .and_then(|ast| {
-            if let Err(errors) = analyze(&ast) {
-                return Err(errors);
-            }
-            Ok(ast)
-        })
-        .map_err(|errors| log_errors(errors, code))
-        .expect("Compilation failed. See above errors to find out what went wrong.")
+const LINT_CONFIG_FILE: &str = "gravitas.toml";
+
+// Looks for the project's gravitas.toml, starting from the compiled file's directory
+pub(crate) fn load_lint_config(file_path: &Path) -> LintConfig {
+    let config_path = file_path
+        .ancestors()
+        .skip(1)
+        .map(|dir| dir.join(LINT_CONFIG_FILE))
+        .find(|path| path.is_file());
+
+    let path = match config_path {
+        Some(path) => path,
+        None => return LintConfig::default(),
+    };
+
+    let config = read_to_string(&path)
+        .map_err(|err| LintConfigError::Unreadable(err.to_string()))
+        .and_then(|config| LintConfig::from_toml(&config));
+    // Errors name the configuration file instead of the compiled one
+    let mut db = SourceDatabase::new();
+    let file_id = db.add(path.display().to_string(), "");
+
+    config
+        .map_err(|err| log_errors(vec![err], &db, file_id))
+        .expect(
+            "Couldn't load the lint configuration. See above errors to find out what went wrong.",
+        )
 }
 
-pub(crate) fn compile_and_run(code: &str, debug: bool) -> RuntimeValue {
-    let ast = compile(code);
-    analyze(&ast)
-        .map_err(|errors| log_errors(errors, &code))
-        .expect("Static analysis failed. Investigate above errors to find the cause.");
+// Program together with the analyzer that has seen it, which knows where its variables live
+pub(crate) fn compile(
+    db: &SourceDatabase,
+    file_id: FileId,
+    lints: &LintConfig,
+) -> (Program, Analyzer) {
+    let source = db.source(file_id);
+    let ast = parse(source)
+        .map_err(|errors| log_errors(errors, db, file_id))
+        .expect("Compilation failed. See above errors to find out what went wrong.");
+
+    let mut analyzer = Analyzer::with_thresholds(lints.thresholds().clone());
+    let analysis = analyzer.analyze(&ast);
+    let warnings = lints.apply(analyzer.warnings(), source);
+    let denied = warnings.iter().any(Warning::is_denied);
+    log_errors(warnings, db, file_id);
 
-    let bytecode = generate_bytecode(ast.clone())
-        .map_err(|error| println!("TODO: generation errors"))
+    analysis
+        .map_err(|errors| log_errors(errors, db, file_id))
+        .expect("Compilation failed. See above errors to find out what went wrong.");
+    assert!(
+        !denied,
+        "Compilation failed because of denied lints. See above errors to find out what went wrong."
+    );
+
+    (ast, analyzer)
+}
+
+pub(crate) fn compile_bytecode(
+    db: &SourceDatabase,
+    file_id: FileId,
+    lints: &LintConfig,
+) -> ProgramBytecode {
+    let (ast, analyzer) = compile(db, file_id, lints);
+
+    let mut bytecode = generate_bytecode(ast, analyzer.resolutions())
+        .map_err(|error| log_errors(vec![error], db, file_id))
         .expect("Bytecode generation failed. Investigate above errors to find the cause.");
+    // Debug traces and bytecode files show lines and columns instead of byte offsets
+    bytecode.locate_spans(db.name(file_id), |offset| db.location(file_id, offset));
+    bytecode
+}
 
+pub(crate) fn run_bytecode(
+    bytecode: ProgramBytecode,
+    db: &SourceDatabase,
+    file_id: FileId,
+    debug: bool,
+) -> RuntimeValue {
     run(bytecode, debug)
+        .map_err(|error| log_errors(vec![error], db, file_id))
+        .expect("Program crashed. Investigate above errors to find the cause.")
 }
 
-pub(crate) fn compile_file<P: AsRef<Path>>(path: P) {}
+pub(crate) fn compile_and_run(
+    db: &SourceDatabase,
+    file_id: FileId,
+    lints: &LintConfig,
+    debug: bool,
+) -> RuntimeValue {
+    let bytecode = compile_bytecode(db, file_id, lints);
+    run_bytecode(bytecode, db, file_id, debug)
+}
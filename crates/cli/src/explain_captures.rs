@@ -0,0 +1,51 @@
+use clap::Args;
+use common::source::SourceDatabase;
+
+use std::path::Path;
+
+use crate::compiler::{compile, load_lint_config};
+
+#[derive(Debug, Args)]
+pub(crate) struct ExplainCaptures {
+    #[arg(short, long)]
+    file_path: String,
+}
+
+impl ExplainCaptures {
+    pub(crate) fn run(&self) {
+        let mut db = SourceDatabase::new();
+        let file_id = db.load(&self.file_path).expect("File not found!");
+        let lints = load_lint_config(Path::new(&self.file_path));
+        let (_ast, analyzer) = compile(&db, file_id, &lints);
+
+        for closure in analyzer.captures() {
+            let location = db.location(file_id, closure.function.start);
+            println!(
+                "{} at {}:{}",
+                closure.name(),
+                location.line,
+                location.column
+            );
+            if closure.captures.is_empty() {
+                println!("    captures nothing");
+            }
+
+            for capture in &closure.captures {
+                let mutability = if capture.mutable {
+                    "mutable"
+                } else {
+                    "immutable"
+                };
+                print!("    {} {}, {}", capture.name, capture.mode(), mutability);
+                if let Some(declaration) = &capture.declaration {
+                    let declared = db.location(file_id, declaration.start);
+                    print!(", declared at {}:{}", declared.line, declared.column);
+                }
+                if capture.transitive {
+                    print!(", through the enclosing closure");
+                }
+                println!();
+            }
+        }
+    }
+}
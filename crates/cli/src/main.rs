@@ -3,8 +3,13 @@ use options::GravitasAction;
 
 use crate::options::Gravitas;
 
+pub(crate) mod ast;
+pub(crate) mod build;
 pub(crate) mod compiler;
+pub(crate) mod explain_captures;
+pub(crate) mod metrics;
 pub(crate) mod options;
+pub(crate) mod rename;
 pub(crate) mod repl;
 pub(crate) mod run_file;
 
@@ -14,5 +19,10 @@ fn main() {
     match gravitas.action {
         GravitasAction::Repl(repl) => repl.run(),
         GravitasAction::RunFile(run_file) => run_file.run(),
+        GravitasAction::Build(build) => build.run(),
+        GravitasAction::Ast(print_ast) => print_ast.run(),
+        GravitasAction::ExplainCaptures(explain_captures) => explain_captures.run(),
+        GravitasAction::Metrics(metrics) => metrics.run(),
+        GravitasAction::Rename(rename) => rename.run(),
     }
 }
@@ -0,0 +1,87 @@
+use analyzer::metrics::{measure, FunctionMetrics, Metric, Thresholds};
+use clap::{Args, ValueEnum};
+use common::source::{FileId, SourceDatabase};
+use parser::parse;
+
+use std::path::Path;
+
+use crate::compiler::{load_lint_config, log_errors};
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub(crate) enum MetricsFormat {
+    Table,
+    Json,
+}
+
+#[derive(Debug, Args)]
+pub(crate) struct Metrics {
+    #[arg(short, long)]
+    file_path: String,
+    #[arg(long, value_enum, default_value_t = MetricsFormat::Table)]
+    format: MetricsFormat,
+}
+
+const COLUMNS: &[(&str, Metric)] = &[
+    ("complexity", Metric::Complexity),
+    ("nesting", Metric::NestingDepth),
+    ("params", Metric::Parameters),
+    ("stmts", Metric::Statements),
+    ("closures", Metric::Closures),
+    ("blocks", Metric::BlockDepth),
+];
+
+// Values above the limits from gravitas.toml are marked with an exclamation mark
+fn print_table(
+    functions: &[FunctionMetrics],
+    thresholds: &Thresholds,
+    db: &SourceDatabase,
+    file_id: FileId,
+) {
+    let name_width = functions
+        .iter()
+        .map(|function| function.name.len())
+        .chain(Some("function".len()))
+        .max()
+        .unwrap_or_default();
+
+    print!("{:<width$}  {:>6}", "function", "line", width = name_width);
+    for (header, _) in COLUMNS {
+        print!("  {:>10}", header);
+    }
+    println!();
+
+    for function in functions {
+        let line = db.location(file_id, function.span.start).line;
+        print!("{:<width$}  {:>6}", function.name, line, width = name_width);
+
+        for (_, metric) in COLUMNS {
+            let value = function.get(*metric);
+            let exceeded = thresholds.limit(*metric).is_some_and(|limit| value > limit);
+            let cell = format!("{}{}", value, if exceeded { "!" } else { "" });
+            print!("  {:>10}", cell);
+        }
+        println!();
+    }
+}
+
+impl Metrics {
+    pub(crate) fn run(&self) {
+        let mut db = SourceDatabase::new();
+        let file_id = db.load(&self.file_path).expect("File not found!");
+        let thresholds = load_lint_config(Path::new(&self.file_path))
+            .thresholds()
+            .clone();
+        let ast = parse(db.source(file_id))
+            .map_err(|errors| log_errors(errors, &db, file_id))
+            .expect("Parsing failed. See above errors to find out what went wrong.");
+
+        let functions = measure(&ast);
+        match self.format {
+            MetricsFormat::Table => print_table(&functions, &thresholds, &db, file_id),
+            MetricsFormat::Json => println!(
+                "{}",
+                serde_json::to_string_pretty(&functions).expect("Metrics are always serializable")
+            ),
+        }
+    }
+}
@@ -1,4 +1,7 @@
-use crate::{repl::Repl, run_file::RunFile};
+use crate::{
+    ast::PrintAst, build::Build, explain_captures::ExplainCaptures, metrics::Metrics,
+    rename::Rename, repl::Repl, run_file::RunFile,
+};
 use clap::{Parser, Subcommand};
 
 #[derive(Parser)]
@@ -10,5 +13,11 @@ pub(crate) struct Gravitas {
 #[derive(Subcommand)]
 pub(crate) enum GravitasAction {
     Repl(Repl),
+    #[command(alias = "run")]
     RunFile(RunFile),
+    Build(Build),
+    Ast(PrintAst),
+    ExplainCaptures(ExplainCaptures),
+    Metrics(Metrics),
+    Rename(Rename),
 }
@@ -0,0 +1,51 @@
+use analyzer::rename::{apply_edits, rename};
+use clap::Args;
+use common::source::{Location, SourceDatabase};
+
+use std::fs;
+
+use crate::compiler::log_errors;
+
+#[derive(Debug, Args)]
+pub(crate) struct Rename {
+    file_path: String,
+    // line:column of the name, both counted from 1
+    #[arg(long, value_parser = parse_location)]
+    at: Location,
+    #[arg(long)]
+    to: String,
+}
+
+fn parse_location(text: &str) -> Result<Location, String> {
+    let (line, column) = text
+        .split_once(':')
+        .ok_or_else(|| format!("expected line:column, found '{}'", text))?;
+    let number = |text: &str| {
+        text.parse::<usize>()
+            .map_err(|_| format!("'{}' isn't a line or column number", text))
+    };
+
+    Ok(Location {
+        line: number(line)?,
+        column: number(column)?,
+    })
+}
+
+impl Rename {
+    pub(crate) fn run(&self) {
+        let mut db = SourceDatabase::new();
+        let file_id = db.load(&self.file_path).expect("File not found!");
+        let source = db.source(file_id);
+        let offset = db
+            .offset(file_id, self.at)
+            .expect("Position is outside of the file!");
+
+        let edits = rename(source, offset, &self.to)
+            .map_err(|error| log_errors(vec![error], &db, file_id))
+            .expect("Rename failed. See above errors to find out why.");
+
+        fs::write(&self.file_path, apply_edits(source, &edits))
+            .expect("Couldn't write the renamed file!");
+        println!("Renamed {} occurrence(s)", edits.len());
+    }
+}
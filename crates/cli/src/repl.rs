@@ -1,4 +1,6 @@
+use analyzer::lint::LintConfig;
 use clap::Args;
+use common::source::SourceDatabase;
 use rustyline::{error::ReadlineError, Editor};
 
 use crate::compiler::compile_and_run;
@@ -12,13 +14,16 @@ pub(crate) struct Repl {
 impl Repl {
     pub(crate) fn run(&self) {
         let mut rl = Editor::<()>::new();
+        let mut db = SourceDatabase::new();
+        let lints = LintConfig::default();
 
         loop {
             let readline = rl.readline(">> ");
             match readline {
                 Ok(code) => {
                     rl.add_history_entry(code.as_str());
-                    let program_output = compile_and_run(&code, self.debug);
+                    let file_id = db.add("<repl>", code);
+                    let program_output = compile_and_run(&db, file_id, &lints, self.debug);
 
                     println!("> {}", program_output);
                 }
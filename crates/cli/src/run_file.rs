@@ -1,10 +1,10 @@
-use std::{fs::read_to_string, path::Path};
-
+use bytecode::{gvc, verify::verify};
 use clap::Args;
+use common::source::SourceDatabase;
 
-use vm::run;
+use std::{fs::read, path::Path};
 
-use crate::compiler::compile_and_run;
+use crate::compiler::{compile_and_run, load_lint_config, log_errors, run_bytecode};
 
 #[derive(Debug, Args)]
 pub(crate) struct RunFile {
@@ -12,12 +12,41 @@ pub(crate) struct RunFile {
     pub(crate) debug: bool,
     #[arg(short, long)]
     file_path: String,
+    // Fail the compilation on every lint that would only warn
+    #[arg(long, action)]
+    deny_warnings: bool,
+    // File contains bytecode written by the build command
+    #[arg(long, action)]
+    bytecode: bool,
 }
 
 impl RunFile {
     pub(crate) fn run(&self) {
-        let path = Path::new(&self.file_path);
-        let code = read_to_string(path).expect("File not found!");
-        compile_and_run(&code, self.debug);
+        if self.bytecode {
+            return self.run_bytecode();
+        }
+
+        let mut db = SourceDatabase::new();
+        let file_id = db.load(&self.file_path).expect("File not found!");
+        let mut lints = load_lint_config(Path::new(&self.file_path));
+        if self.deny_warnings {
+            lints.deny_warnings();
+        }
+        compile_and_run(&db, file_id, &lints, self.debug);
+    }
+
+    fn run_bytecode(&self) {
+        let bytes = read(&self.file_path).expect("File not found!");
+        let mut db = SourceDatabase::new();
+        // Source of the program isn't shipped with its bytecode
+        let file_id = db.add(self.file_path.clone(), "");
+
+        let bytecode = gvc::load(&bytes)
+            .map_err(|error| log_errors(vec![error], &db, file_id))
+            .expect("Couldn't load the bytecode. See above errors to find out what went wrong.");
+        verify(&bytecode)
+            .map_err(|errors| log_errors(errors, &db, file_id))
+            .expect("Bytecode is invalid. See above errors to find out what went wrong.");
+        run_bytecode(bytecode, &db, file_id, self.debug);
     }
 }
@@ -2,6 +2,8 @@ use std::str::FromStr;
 
 use codespan_reporting::diagnostic::Diagnostic;
 
+pub mod source;
+
 pub trait CompilerDiagnostic: Sized {
     fn report(&self, file_id: usize) -> Diagnostic<usize>;
 }
@@ -21,9 +23,21 @@ pub enum BuiltInFunction {
     Print,
 }
 
-impl Into<String> for BuiltInFunction {
-    fn into(self) -> String {
+pub const BUILT_IN_FUNCTIONS: &[BuiltInFunction] =
+    &[BuiltInFunction::Clock, BuiltInFunction::Print];
+
+impl BuiltInFunction {
+    pub fn arity(&self) -> usize {
         match self {
+            BuiltInFunction::Clock => 0,
+            BuiltInFunction::Print => 1,
+        }
+    }
+}
+
+impl From<BuiltInFunction> for String {
+    fn from(val: BuiltInFunction) -> Self {
+        match val {
             BuiltInFunction::Clock => "clock".to_string(),
             BuiltInFunction::Print => "print".to_string(),
         }
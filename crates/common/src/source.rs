@@ -0,0 +1,212 @@
+use std::{cmp::Ordering, fs, io, ops::Range, path::Path};
+
+use codespan_reporting::files::{self, Files};
+
+pub type FileId = usize;
+
+// 1-indexed position in the source, as shown to the user
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Location {
+    pub line: usize,
+    pub column: usize,
+}
+
+#[derive(Debug, Clone)]
+struct SourceFile {
+    name: String,
+    source: String,
+    line_starts: Vec<usize>,
+}
+
+impl SourceFile {
+    fn new(name: String, source: String) -> Self {
+        let line_starts = files::line_starts(&source).collect();
+
+        Self {
+            name,
+            source,
+            line_starts,
+        }
+    }
+
+    fn line_index(&self, byte_index: usize) -> usize {
+        match self.line_starts.binary_search(&byte_index) {
+            Ok(line) => line,
+            Err(next_line) => next_line - 1,
+        }
+    }
+
+    fn line_start(&self, line_index: usize) -> Result<usize, files::Error> {
+        match line_index.cmp(&self.line_starts.len()) {
+            Ordering::Less => Ok(self.line_starts[line_index]),
+            Ordering::Equal => Ok(self.source.len()),
+            Ordering::Greater => Err(files::Error::LineTooLarge {
+                given: line_index,
+                max: self.line_starts.len() - 1,
+            }),
+        }
+    }
+}
+
+// Keeps every source file taking part in the compilation,
+// so diagnostics can point at the real file, line and column.
+#[derive(Debug, Clone, Default)]
+pub struct SourceDatabase {
+    files: Vec<SourceFile>,
+}
+
+impl SourceDatabase {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, name: impl Into<String>, source: impl Into<String>) -> FileId {
+        self.files.push(SourceFile::new(name.into(), source.into()));
+        self.files.len() - 1
+    }
+
+    pub fn load<P: AsRef<Path>>(&mut self, path: P) -> io::Result<FileId> {
+        let path = path.as_ref();
+        let source = fs::read_to_string(path)?;
+        Ok(self.add(path.display().to_string(), source))
+    }
+
+    pub fn name(&self, file_id: FileId) -> &str {
+        &self.files[file_id].name
+    }
+
+    pub fn source(&self, file_id: FileId) -> &str {
+        &self.files[file_id].source
+    }
+
+    pub fn location(&self, file_id: FileId, byte_index: usize) -> Location {
+        let location = Files::location(self, file_id, byte_index)
+            .expect("Byte index outside of the source file");
+
+        Location {
+            line: location.line_number,
+            column: location.column_number,
+        }
+    }
+
+    // Byte index of the location, the column can point right behind the last character of the line
+    pub fn offset(&self, file_id: FileId, location: Location) -> Option<usize> {
+        let file = &self.files[file_id];
+        let line_index = location.line.checked_sub(1)?;
+        let line_start = *file.line_starts.get(line_index)?;
+        let line_end = file.line_start(line_index + 1).ok()?;
+        let line = file.source[line_start..line_end].trim_end_matches('\n');
+
+        line.char_indices()
+            .map(|(offset, _)| offset)
+            .chain(Some(line.len()))
+            .nth(location.column.checked_sub(1)?)
+            .map(|offset| line_start + offset)
+    }
+
+    fn get(&self, file_id: FileId) -> Result<&SourceFile, files::Error> {
+        self.files.get(file_id).ok_or(files::Error::FileMissing)
+    }
+}
+
+impl<'a> Files<'a> for SourceDatabase {
+    type FileId = FileId;
+    type Name = &'a str;
+    type Source = &'a str;
+
+    fn name(&'a self, file_id: FileId) -> Result<&'a str, files::Error> {
+        Ok(&self.get(file_id)?.name)
+    }
+
+    fn source(&'a self, file_id: FileId) -> Result<&'a str, files::Error> {
+        Ok(&self.get(file_id)?.source)
+    }
+
+    fn line_index(&'a self, file_id: FileId, byte_index: usize) -> Result<usize, files::Error> {
+        Ok(self.get(file_id)?.line_index(byte_index))
+    }
+
+    fn line_range(
+        &'a self,
+        file_id: FileId,
+        line_index: usize,
+    ) -> Result<Range<usize>, files::Error> {
+        let file = self.get(file_id)?;
+        let line_start = file.line_start(line_index)?;
+        let next_line_start = file.line_start(line_index + 1)?;
+
+        Ok(line_start..next_line_start)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn computes_line_and_column() {
+        let mut db = SourceDatabase::new();
+        let first = db.add("first.vt", "let a = 1;\nlet b = a;\n");
+        let second = db.add("second.vt", "b;");
+
+        assert_eq!(db.name(first), "first.vt");
+        assert_eq!(db.name(second), "second.vt");
+        assert_eq!(db.location(first, 0), Location { line: 1, column: 1 });
+        assert_eq!(db.location(first, 15), Location { line: 2, column: 5 });
+        assert_eq!(db.location(second, 1), Location { line: 1, column: 2 });
+    }
+
+    #[test]
+    fn counts_columns_in_characters() {
+        let mut db = SourceDatabase::new();
+        let file = db.add("unicode.vt", "\"żółw\" + x;");
+
+        assert_eq!(db.location(file, 11), Location { line: 1, column: 9 });
+    }
+
+    #[test]
+    fn finds_offsets_of_locations() {
+        let mut db = SourceDatabase::new();
+        let file = db.add("unicode.vt", "let a = 1;\n\"żółw\" + a;\n");
+
+        assert_eq!(
+            db.offset(
+                file,
+                Location {
+                    line: 2,
+                    column: 10
+                }
+            ),
+            Some(23)
+        );
+        assert_eq!(
+            db.location(file, 23),
+            Location {
+                line: 2,
+                column: 10
+            }
+        );
+        assert_eq!(
+            db.offset(
+                file,
+                Location {
+                    line: 1,
+                    column: 11
+                }
+            ),
+            Some(10)
+        );
+        assert_eq!(
+            db.offset(
+                file,
+                Location {
+                    line: 1,
+                    column: 12
+                }
+            ),
+            None
+        );
+        assert_eq!(db.offset(file, Location { line: 4, column: 1 }), None);
+        assert_eq!(db.offset(file, Location { line: 0, column: 1 }), None);
+    }
+}
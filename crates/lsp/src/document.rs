@@ -0,0 +1,365 @@
+use analyzer::{
+    captures::ClosureCaptures,
+    lint::LintConfig,
+    references::{Declaration, References},
+    rename::{rename, RenameError},
+    resolution::{Resolution, Resolutions},
+    symbols::{self, ScopeSymbols, SymbolKind},
+    types::{Type, Types},
+    Analyzer,
+};
+use codespan_reporting::diagnostic::{self, LabelStyle, Severity};
+use common::{find_std_function, CompilerDiagnostic};
+use lsp_types::{
+    CompletionItem, CompletionItemKind, Diagnostic, DiagnosticRelatedInformation,
+    DiagnosticSeverity, DocumentSymbol, Hover, HoverContents, Location, MarkupContent, MarkupKind,
+    NumberOrString, Position, Range, TextEdit, Url,
+};
+use parser::parse::{stmt::Stmt, Span};
+
+use crate::position::LineIndex;
+
+pub(crate) struct Document {
+    text: String,
+    lines: LineIndex,
+    diagnostics: Vec<diagnostic::Diagnostic<usize>>,
+    // Comes from the last version that parsed, so navigation keeps working while the user types
+    analysis: Option<Analysis>,
+}
+
+impl Document {
+    pub(crate) fn new(text: String) -> Self {
+        let mut document = Self {
+            text: String::new(),
+            lines: LineIndex::new(""),
+            diagnostics: Vec::new(),
+            analysis: None,
+        };
+        document.update(text);
+        document
+    }
+
+    pub(crate) fn update(&mut self, text: String) {
+        let ast = match parser::parse(&text) {
+            Ok(ast) => ast,
+            Err(errors) => {
+                self.diagnostics = errors.iter().map(|error| error.report(0)).collect();
+                self.lines = LineIndex::new(&text);
+                self.text = text;
+                return;
+            }
+        };
+
+        let mut analyzer = Analyzer::new();
+        let errors = analyzer.analyze(&ast).err().unwrap_or_default();
+        let warnings = LintConfig::default().apply(analyzer.warnings(), &text);
+        self.diagnostics = errors
+            .iter()
+            .map(|error| error.report(0))
+            .chain(warnings.iter().map(|warning| warning.report(0)))
+            .collect();
+
+        self.lines = LineIndex::new(&text);
+        self.text = text.clone();
+        self.analysis = Some(Analysis::new(text, &ast, &analyzer));
+    }
+
+    pub(crate) fn diagnostics(&self, uri: &Url) -> Vec<Diagnostic> {
+        self.diagnostics
+            .iter()
+            .map(|diagnostic| self.convert_diagnostic(diagnostic, uri))
+            .collect()
+    }
+
+    fn convert_diagnostic(
+        &self,
+        diagnostic: &diagnostic::Diagnostic<usize>,
+        uri: &Url,
+    ) -> Diagnostic {
+        let primary = diagnostic
+            .labels
+            .iter()
+            .find(|label| label.style == LabelStyle::Primary);
+        // End of input doesn't point at anything
+        let span = primary
+            .map(|label| label.range.clone())
+            .unwrap_or(self.text.len()..self.text.len());
+
+        let severity = match diagnostic.severity {
+            Severity::Bug | Severity::Error => DiagnosticSeverity::ERROR,
+            Severity::Warning => DiagnosticSeverity::WARNING,
+            Severity::Note => DiagnosticSeverity::INFORMATION,
+            Severity::Help => DiagnosticSeverity::HINT,
+        };
+
+        let mut message = diagnostic.message.clone();
+        for note in &diagnostic.notes {
+            message.push('\n');
+            message.push_str(note);
+        }
+
+        let related: Vec<DiagnosticRelatedInformation> = diagnostic
+            .labels
+            .iter()
+            .filter(|label| label.style == LabelStyle::Secondary)
+            .map(|label| DiagnosticRelatedInformation {
+                location: Location::new(uri.clone(), self.lines.range(&self.text, &label.range)),
+                message: label.message.clone(),
+            })
+            .collect();
+
+        Diagnostic {
+            range: self.lines.range(&self.text, &span),
+            severity: Some(severity),
+            code: diagnostic.code.clone().map(NumberOrString::String),
+            source: Some("gravitas".to_owned()),
+            message,
+            related_information: (!related.is_empty()).then_some(related),
+            ..Diagnostic::default()
+        }
+    }
+
+    pub(crate) fn definition(&self, position: Position) -> Option<Range> {
+        let analysis = self.analysis.as_ref()?;
+        let declaration = analysis.references.target(analysis.offset(position))?;
+        Some(analysis.range(&declaration.name_span))
+    }
+
+    pub(crate) fn references(&self, position: Position, include_declaration: bool) -> Vec<Range> {
+        let analysis = match &self.analysis {
+            Some(analysis) => analysis,
+            None => return Vec::new(),
+        };
+        let declaration = match analysis.references.target(analysis.offset(position)) {
+            Some(declaration) => declaration,
+            None => return Vec::new(),
+        };
+
+        let mut spans: Vec<&Span> = analysis.references.uses_of(&declaration.span).collect();
+        if include_declaration {
+            spans.push(&declaration.name_span);
+        }
+        spans.sort_by_key(|span| span.start);
+
+        spans.into_iter().map(|span| analysis.range(span)).collect()
+    }
+
+    pub(crate) fn hover(&self, position: Position) -> Option<Hover> {
+        let analysis = self.analysis.as_ref()?;
+        let offset = analysis.offset(position);
+
+        let identifier = analysis.references.identifier_at(offset);
+        let (span, value) = match analysis.references.target(offset) {
+            Some(declaration) => {
+                let span = identifier
+                    .map(|identifier| &identifier.span)
+                    .unwrap_or(&declaration.name_span);
+                (span, analysis.describe(declaration))
+            }
+            None => {
+                let identifier = identifier?;
+                match analysis.resolutions.identifier(&identifier.span)? {
+                    Resolution::Builtin(builtin) => (
+                        &identifier.span,
+                        format!(
+                            "```gravitas\nfn {}\n```\nNative function taking {} argument(s)",
+                            identifier.name,
+                            builtin.arity()
+                        ),
+                    ),
+                    _ => return None,
+                }
+            }
+        };
+
+        Some(Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value,
+            }),
+            range: Some(analysis.range(span)),
+        })
+    }
+
+    // Parameters are left out, the outline only shows what's declared in the bodies
+    pub(crate) fn symbols(&self) -> Vec<DocumentSymbol> {
+        match &self.analysis {
+            Some(analysis) => analysis.outline(None),
+            None => Vec::new(),
+        }
+    }
+
+    // Works on the current text, renaming a stale version would break the program
+    pub(crate) fn rename(
+        &self,
+        position: Position,
+        new_name: &str,
+    ) -> Result<Vec<TextEdit>, RenameError> {
+        let offset = self.lines.offset(&self.text, position);
+        let edits = rename(&self.text, offset, new_name)?;
+
+        Ok(edits
+            .into_iter()
+            .map(|edit| TextEdit::new(self.lines.range(&self.text, &edit.range), edit.text))
+            .collect())
+    }
+
+    pub(crate) fn completions(&self, position: Position) -> Vec<CompletionItem> {
+        let analysis = match &self.analysis {
+            Some(analysis) => analysis,
+            None => return Vec::new(),
+        };
+        let offset = analysis.offset(position);
+
+        symbols::visible_at(&analysis.scopes, offset)
+            .into_iter()
+            .map(|symbol| {
+                let declaration = symbol
+                    .declaration
+                    .as_ref()
+                    .and_then(|span| analysis.references.declaration(span));
+                let detail = match declaration {
+                    Some(declaration) => Some(declaration.detail.clone()),
+                    None => find_std_function(&symbol.name)
+                        .map(|builtin| format!("native fn, {} argument(s)", builtin.arity())),
+                };
+
+                CompletionItem {
+                    label: symbol.name.clone(),
+                    kind: Some(completion_kind(symbol.kind)),
+                    detail,
+                    ..CompletionItem::default()
+                }
+            })
+            .collect()
+    }
+}
+
+fn completion_kind(kind: SymbolKind) -> CompletionItemKind {
+    match kind {
+        SymbolKind::Builtin | SymbolKind::Function | SymbolKind::Closure => {
+            CompletionItemKind::FUNCTION
+        }
+        SymbolKind::Variable | SymbolKind::Parameter => CompletionItemKind::VARIABLE,
+        SymbolKind::Class => CompletionItemKind::CLASS,
+    }
+}
+
+struct Analysis {
+    text: String,
+    lines: LineIndex,
+    resolutions: Resolutions,
+    scopes: Vec<ScopeSymbols>,
+    captures: Vec<ClosureCaptures>,
+    references: References,
+    types: Types,
+}
+
+impl Analysis {
+    fn new(text: String, ast: &[Stmt], analyzer: &Analyzer) -> Self {
+        Self {
+            lines: LineIndex::new(&text),
+            references: References::new(ast, &text, analyzer.resolutions()),
+            text,
+            resolutions: analyzer.resolutions().clone(),
+            scopes: analyzer.scopes(),
+            captures: analyzer.captures().to_vec(),
+            types: analyzer.types().clone(),
+        }
+    }
+
+    fn offset(&self, position: Position) -> usize {
+        self.lines.offset(&self.text, position)
+    }
+
+    fn range(&self, span: &Span) -> Range {
+        self.lines.range(&self.text, span)
+    }
+
+    fn describe(&self, declaration: &Declaration) -> String {
+        let mut detail = declaration.detail.clone();
+        // Functions already show their parameters
+        if matches!(
+            declaration.kind,
+            SymbolKind::Variable | SymbolKind::Parameter
+        ) {
+            match self.types.of_declaration(&declaration.span) {
+                Some(Type::Unknown | Type::Never) | None => {}
+                Some(ty) => detail = format!("{}: {}", detail, ty),
+            }
+        }
+        let mut sections = vec![format!("```gravitas\n{}\n```", detail)];
+
+        if let Some(docs) = self.doc_comment(&declaration.span) {
+            sections.push(docs);
+        }
+
+        if let Some(function) = &declaration.function {
+            if self.resolutions.is_pure(function) {
+                sections.push("Pure function".to_owned());
+            }
+            let captures = self
+                .captures
+                .iter()
+                .find(|closure| &closure.function == function);
+            if let Some(captures) = captures.filter(|closure| !closure.captures.is_empty()) {
+                sections.push(captures.to_string());
+            }
+        }
+
+        sections.join("\n\n")
+    }
+
+    // `///` comments written right above the declaration
+    fn doc_comment(&self, span: &Span) -> Option<String> {
+        let line_start = self.text[..span.start].rfind('\n').map_or(0, |end| end + 1);
+        if !self.text[line_start..span.start].trim().is_empty() {
+            return None;
+        }
+
+        let mut lines: Vec<&str> = self.text[..line_start]
+            .lines()
+            .rev()
+            .map_while(|line| line.trim().strip_prefix("///"))
+            .map(|line| line.strip_prefix(' ').unwrap_or(line))
+            .collect();
+        lines.reverse();
+
+        (!lines.is_empty()).then(|| lines.join("\n"))
+    }
+
+    #[allow(deprecated)]
+    fn outline(&self, parent: Option<usize>) -> Vec<DocumentSymbol> {
+        let declarations = self.references.declarations();
+
+        declarations
+            .iter()
+            .enumerate()
+            .filter(|(_, declaration)| {
+                declaration.parent == parent && declaration.kind != SymbolKind::Parameter
+            })
+            .map(|(index, declaration)| {
+                let kind = match declaration.kind {
+                    SymbolKind::Class => lsp_types::SymbolKind::CLASS,
+                    SymbolKind::Function if self.references.is_method(declaration) => {
+                        lsp_types::SymbolKind::METHOD
+                    }
+                    SymbolKind::Function | SymbolKind::Closure => lsp_types::SymbolKind::FUNCTION,
+                    _ => lsp_types::SymbolKind::VARIABLE,
+                };
+                let children = self.outline(Some(index));
+
+                DocumentSymbol {
+                    name: declaration.name.clone(),
+                    detail: Some(declaration.detail.clone()),
+                    kind,
+                    tags: None,
+                    deprecated: None,
+                    range: self.range(&declaration.span),
+                    selection_range: self.range(&declaration.name_span),
+                    children: (!children.is_empty()).then_some(children),
+                }
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,220 @@
+use std::{collections::HashMap, error::Error};
+
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, RequestId, Response};
+use lsp_types::{
+    notification::{
+        DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument,
+        Notification as NotificationType, PublishDiagnostics,
+    },
+    request::{
+        Completion, DocumentSymbolRequest, GotoDefinition, HoverRequest, References, Rename,
+        Request as RequestType,
+    },
+    CompletionResponse, DocumentSymbolResponse, GotoDefinitionResponse, Location, OneOf,
+    PublishDiagnosticsParams, ServerCapabilities, TextDocumentSyncCapability, TextDocumentSyncKind,
+    Url, WorkspaceEdit,
+};
+
+use crate::document::Document;
+
+pub(crate) mod document;
+pub(crate) mod position;
+
+pub type ServerResult<T> = Result<T, Box<dyn Error + Send + Sync>>;
+
+pub fn capabilities() -> ServerCapabilities {
+    ServerCapabilities {
+        // Whole text gets parsed again anyway
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        definition_provider: Some(OneOf::Left(true)),
+        references_provider: Some(OneOf::Left(true)),
+        hover_provider: Some(true.into()),
+        document_symbol_provider: Some(OneOf::Left(true)),
+        completion_provider: Some(Default::default()),
+        rename_provider: Some(OneOf::Left(true)),
+        ..ServerCapabilities::default()
+    }
+}
+
+// Serves a single client until it shuts the server down
+pub fn run(connection: &Connection) -> ServerResult<()> {
+    connection.initialize(serde_json::to_value(capabilities())?)?;
+
+    let mut server = Server {
+        connection,
+        documents: HashMap::new(),
+    };
+    for message in &connection.receiver {
+        match message {
+            Message::Request(request) => {
+                if connection.handle_shutdown(&request)? {
+                    return Ok(());
+                }
+                server.handle_request(request)?;
+            }
+            Message::Notification(notification) => server.handle_notification(notification)?,
+            Message::Response(_) => {}
+        }
+    }
+
+    Ok(())
+}
+
+struct Server<'c> {
+    connection: &'c Connection,
+    documents: HashMap<Url, Document>,
+}
+
+fn params<R: RequestType>(request: Request) -> ServerResult<R::Params> {
+    let (_, params) = request.extract(R::METHOD)?;
+    Ok(params)
+}
+
+fn notification_params<N: NotificationType>(notification: Notification) -> ServerResult<N::Params> {
+    Ok(notification.extract(N::METHOD)?)
+}
+
+impl Server<'_> {
+    fn send(&self, message: impl Into<Message>) -> ServerResult<()> {
+        self.connection.sender.send(message.into())?;
+        Ok(())
+    }
+
+    fn respond<R: RequestType>(&self, id: RequestId, result: R::Result) -> ServerResult<()> {
+        self.send(Response::new_ok(id, result))
+    }
+
+    fn document(&self, uri: &Url) -> Option<&Document> {
+        self.documents.get(uri)
+    }
+
+    fn handle_request(&mut self, request: Request) -> ServerResult<()> {
+        let id = request.id.clone();
+
+        match request.method.as_str() {
+            GotoDefinition::METHOD => {
+                let params = params::<GotoDefinition>(request)?.text_document_position_params;
+                let (uri, position) = (params.text_document.uri, params.position);
+                let location = self
+                    .document(&uri)
+                    .and_then(|document| document.definition(position))
+                    .map(|range| GotoDefinitionResponse::Scalar(Location::new(uri, range)));
+                self.respond::<GotoDefinition>(id, location)
+            }
+            References::METHOD => {
+                let params = params::<References>(request)?;
+                let include_declaration = params.context.include_declaration;
+                let position = params.text_document_position.position;
+                let uri = params.text_document_position.text_document.uri;
+                let locations = self.document(&uri).map(|document| {
+                    document
+                        .references(position, include_declaration)
+                        .into_iter()
+                        .map(|range| Location::new(uri.clone(), range))
+                        .collect()
+                });
+                self.respond::<References>(id, locations)
+            }
+            HoverRequest::METHOD => {
+                let params = params::<HoverRequest>(request)?.text_document_position_params;
+                let hover = self
+                    .document(&params.text_document.uri)
+                    .and_then(|document| document.hover(params.position));
+                self.respond::<HoverRequest>(id, hover)
+            }
+            DocumentSymbolRequest::METHOD => {
+                let params = params::<DocumentSymbolRequest>(request)?;
+                let symbols = self
+                    .document(&params.text_document.uri)
+                    .map(|document| DocumentSymbolResponse::Nested(document.symbols()));
+                self.respond::<DocumentSymbolRequest>(id, symbols)
+            }
+            Completion::METHOD => {
+                let position = params::<Completion>(request)?.text_document_position;
+                let completions = self.document(&position.text_document.uri).map(|document| {
+                    CompletionResponse::Array(document.completions(position.position))
+                });
+                self.respond::<Completion>(id, completions)
+            }
+            Rename::METHOD => {
+                let params = params::<Rename>(request)?;
+                let position = params.text_document_position.position;
+                let uri = params.text_document_position.text_document.uri;
+                let document = match self.document(&uri) {
+                    Some(document) => document,
+                    None => return self.respond::<Rename>(id, None),
+                };
+
+                match document.rename(position, &params.new_name) {
+                    Ok(edits) => {
+                        let changes = HashMap::from([(uri, edits)]);
+                        self.respond::<Rename>(id, Some(WorkspaceEdit::new(changes)))
+                    }
+                    // Editors show the reason to the user
+                    Err(error) => self.send(Response::new_err(
+                        id,
+                        ErrorCode::RequestFailed as i32,
+                        error.to_string(),
+                    )),
+                }
+            }
+            method => self.send(Response::new_err(
+                id,
+                ErrorCode::MethodNotFound as i32,
+                format!("Unsupported request {}", method),
+            )),
+        }
+    }
+
+    fn handle_notification(&mut self, notification: Notification) -> ServerResult<()> {
+        match notification.method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                let document =
+                    notification_params::<DidOpenTextDocument>(notification)?.text_document;
+                self.documents
+                    .insert(document.uri.clone(), Document::new(document.text));
+                self.publish_diagnostics(document.uri, Some(document.version))
+            }
+            DidChangeTextDocument::METHOD => {
+                let params = notification_params::<DidChangeTextDocument>(notification)?;
+                let uri = params.text_document.uri;
+                // Full synchronization sends the whole text in the last change
+                let text = match params.content_changes.into_iter().last() {
+                    Some(change) => change.text,
+                    None => return Ok(()),
+                };
+                match self.documents.get_mut(&uri) {
+                    Some(document) => document.update(text),
+                    None => {
+                        self.documents.insert(uri.clone(), Document::new(text));
+                    }
+                }
+                self.publish_diagnostics(uri, Some(params.text_document.version))
+            }
+            DidCloseTextDocument::METHOD => {
+                let uri = notification_params::<DidCloseTextDocument>(notification)?
+                    .text_document
+                    .uri;
+                self.documents.remove(&uri);
+                // Problems of closed files shouldn't stay in the editor
+                self.send(Notification::new(
+                    PublishDiagnostics::METHOD.to_owned(),
+                    PublishDiagnosticsParams::new(uri, Vec::new(), None),
+                ))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn publish_diagnostics(&self, uri: Url, version: Option<i32>) -> ServerResult<()> {
+        let diagnostics = match self.document(&uri) {
+            Some(document) => document.diagnostics(&uri),
+            None => return Ok(()),
+        };
+
+        self.send(Notification::new(
+            PublishDiagnostics::METHOD.to_owned(),
+            PublishDiagnosticsParams::new(uri, diagnostics, version),
+        ))
+    }
+}
@@ -0,0 +1,11 @@
+use lsp_server::Connection;
+
+fn main() -> lsp::ServerResult<()> {
+    let (connection, io_threads) = Connection::stdio();
+    lsp::run(&connection)?;
+    // Server has to drop its side of the connection, otherwise the writer never stops
+    drop(connection);
+    io_threads.join()?;
+
+    Ok(())
+}
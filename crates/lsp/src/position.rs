@@ -0,0 +1,75 @@
+use lsp_types::{Position, Range};
+use parser::parse::Span;
+
+// Translates byte offsets used by the spans into LSP positions,
+// which count characters in UTF-16 code units
+#[derive(Debug, Clone)]
+pub(crate) struct LineIndex {
+    // Byte offset of the first character of every line
+    line_starts: Vec<usize>,
+}
+
+impl LineIndex {
+    pub(crate) fn new(text: &str) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(text.match_indices('\n').map(|(offset, _)| offset + 1))
+            .collect();
+
+        Self { line_starts }
+    }
+
+    pub(crate) fn position(&self, text: &str, offset: usize) -> Position {
+        let offset = offset.min(text.len());
+        let line = match self.line_starts.binary_search(&offset) {
+            Ok(line) => line,
+            Err(next_line) => next_line - 1,
+        };
+        let line_start = self.line_starts[line];
+        let character = text[line_start..offset].encode_utf16().count();
+
+        Position::new(line as u32, character as u32)
+    }
+
+    pub(crate) fn range(&self, text: &str, span: &Span) -> Range {
+        Range::new(
+            self.position(text, span.start),
+            self.position(text, span.end),
+        )
+    }
+
+    // Positions past the end of a line point at its end
+    pub(crate) fn offset(&self, text: &str, position: Position) -> usize {
+        let line_start = match self.line_starts.get(position.line as usize) {
+            Some(&start) => start,
+            None => return text.len(),
+        };
+
+        let mut character = 0;
+        for (offset, char) in text[line_start..].char_indices() {
+            if char == '\n' || character >= position.character as usize {
+                return line_start + offset;
+            }
+            character += char.len_utf16();
+        }
+
+        text.len()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn converts_offsets() {
+        let text = "let a = 1;\nlet ż = \"😀\" + a;\n";
+        let index = LineIndex::new(text);
+        let a = text.rfind('a').unwrap();
+
+        assert_eq!(index.position(text, a), Position::new(1, 15));
+        assert_eq!(index.offset(text, Position::new(1, 15)), a);
+        assert_eq!(index.position(text, 4), Position::new(0, 4));
+        assert_eq!(index.offset(text, Position::new(0, 40)), 10);
+        assert_eq!(index.offset(text, Position::new(7, 0)), text.len());
+    }
+}
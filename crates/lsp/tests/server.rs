@@ -0,0 +1,406 @@
+use lsp_server::{Connection, Message, Notification, Request, RequestId, ResponseError};
+use lsp_types::{
+    notification::{
+        DidChangeTextDocument, DidOpenTextDocument, Exit, Initialized,
+        Notification as NotificationType, PublishDiagnostics,
+    },
+    request::{
+        Completion, DocumentSymbolRequest, GotoDefinition, HoverRequest, Initialize, References,
+        Rename, Request as RequestType, Shutdown,
+    },
+    CompletionParams, CompletionResponse, DiagnosticSeverity, DidChangeTextDocumentParams,
+    DidOpenTextDocumentParams, DocumentSymbolParams, DocumentSymbolResponse, GotoDefinitionParams,
+    GotoDefinitionResponse, HoverContents, HoverParams, InitializeParams, InitializedParams,
+    NumberOrString, Position, PublishDiagnosticsParams, Range, ReferenceContext, ReferenceParams,
+    RenameParams, TextDocumentContentChangeEvent, TextDocumentIdentifier, TextDocumentItem,
+    TextDocumentPositionParams, TextEdit, Url, VersionedTextDocumentIdentifier,
+};
+use std::{
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+const PROGRAM: &str = "/// Adds two numbers
+/// and nothing else
+@pure fn add(a, b) => a + b
+
+let total = add(1, 2);
+let counter = 0;
+let increment = |step| => { let shadow = step; counter = counter + step; };
+print(total + unknown);
+";
+
+// Talks to the server running on another thread, just like an editor would
+struct Client {
+    connection: Connection,
+    server: Option<JoinHandle<()>>,
+    next_id: i32,
+    uri: Url,
+}
+
+impl Client {
+    fn start(text: &str) -> Self {
+        let (server, connection) = Connection::memory();
+        let server = thread::spawn(move || lsp::run(&server).unwrap());
+
+        let mut client = Self {
+            connection,
+            server: Some(server),
+            next_id: 0,
+            uri: Url::parse("file:///program.vt").unwrap(),
+        };
+        client.request::<Initialize>(InitializeParams::default());
+        client.notify::<Initialized>(InitializedParams {});
+        client.notify::<DidOpenTextDocument>(DidOpenTextDocumentParams {
+            text_document: TextDocumentItem::new(
+                client.uri.clone(),
+                "gravitas".to_owned(),
+                1,
+                text.to_owned(),
+            ),
+        });
+
+        client
+    }
+
+    fn receive(&self) -> Message {
+        self.connection
+            .receiver
+            .recv_timeout(TIMEOUT)
+            .expect("Server didn't respond")
+    }
+
+    fn request<R: RequestType>(&mut self, params: R::Params) -> R::Result {
+        self.try_request::<R>(params).expect("Request failed")
+    }
+
+    fn try_request<R: RequestType>(
+        &mut self,
+        params: R::Params,
+    ) -> Result<R::Result, ResponseError> {
+        self.next_id += 1;
+        let id = RequestId::from(self.next_id);
+        self.connection
+            .sender
+            .send(Request::new(id.clone(), R::METHOD.to_owned(), params).into())
+            .unwrap();
+
+        loop {
+            match self.receive() {
+                Message::Response(response) if response.id == id => {
+                    if let Some(error) = response.error {
+                        return Err(error);
+                    }
+                    return Ok(serde_json::from_value(response.result.unwrap()).unwrap());
+                }
+                _ => continue,
+            }
+        }
+    }
+
+    fn notify<N: NotificationType>(&self, params: N::Params) {
+        self.connection
+            .sender
+            .send(Notification::new(N::METHOD.to_owned(), params).into())
+            .unwrap();
+    }
+
+    fn diagnostics(&self) -> PublishDiagnosticsParams {
+        loop {
+            if let Message::Notification(notification) = self.receive() {
+                if notification.method == PublishDiagnostics::METHOD {
+                    return serde_json::from_value(notification.params).unwrap();
+                }
+            }
+        }
+    }
+
+    fn document(&self) -> TextDocumentIdentifier {
+        TextDocumentIdentifier::new(self.uri.clone())
+    }
+
+    fn at(&self, position: Position) -> TextDocumentPositionParams {
+        TextDocumentPositionParams::new(self.document(), position)
+    }
+
+    fn definition(&mut self, position: Position) -> Option<Range> {
+        let response = self.request::<GotoDefinition>(GotoDefinitionParams {
+            text_document_position_params: self.at(position),
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        });
+
+        match response? {
+            GotoDefinitionResponse::Scalar(location) => Some(location.range),
+            _ => panic!("Expected a single location"),
+        }
+    }
+
+    fn references(&mut self, position: Position) -> Vec<Range> {
+        let locations = self.request::<References>(ReferenceParams {
+            text_document_position: self.at(position),
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+            context: ReferenceContext {
+                include_declaration: true,
+            },
+        });
+
+        locations
+            .unwrap()
+            .into_iter()
+            .map(|location| location.range)
+            .collect()
+    }
+
+    fn hover(&mut self, position: Position) -> String {
+        let hover = self.request::<HoverRequest>(HoverParams {
+            text_document_position_params: self.at(position),
+            work_done_progress_params: Default::default(),
+        });
+
+        match hover.expect("Nothing to show").contents {
+            HoverContents::Markup(markup) => markup.value,
+            _ => panic!("Expected markdown"),
+        }
+    }
+
+    fn rename(&mut self, position: Position, new_name: &str) -> Result<Vec<TextEdit>, String> {
+        let edit = self
+            .try_request::<Rename>(RenameParams {
+                text_document_position: self.at(position),
+                new_name: new_name.to_owned(),
+                work_done_progress_params: Default::default(),
+            })
+            .map_err(|error| error.message)?;
+
+        let mut changes = edit.unwrap().changes.unwrap();
+        Ok(changes.remove(&self.uri).unwrap())
+    }
+
+    fn completions(&mut self, position: Position) -> Vec<String> {
+        let response = self.request::<Completion>(CompletionParams {
+            text_document_position: self.at(position),
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+            context: None,
+        });
+
+        match response.unwrap() {
+            CompletionResponse::Array(items) => items.into_iter().map(|item| item.label).collect(),
+            _ => panic!("Expected a list of completions"),
+        }
+    }
+}
+
+impl Drop for Client {
+    fn drop(&mut self) {
+        // Failed assertions shouldn't hang waiting for the server
+        if thread::panicking() {
+            return;
+        }
+        self.request::<Shutdown>(());
+        self.notify::<Exit>(());
+        self.server.take().unwrap().join().unwrap();
+    }
+}
+
+// Position of the nth occurrence of the text, programs in the tests are plain ASCII
+fn find(text: &str, needle: &str, nth: usize) -> Position {
+    let offset = text
+        .match_indices(needle)
+        .nth(nth)
+        .map(|(offset, _)| offset)
+        .unwrap();
+    let line = text[..offset].matches('\n').count();
+    let line_start = text[..offset].rfind('\n').map_or(0, |end| end + 1);
+
+    Position::new(line as u32, (offset - line_start) as u32)
+}
+
+fn range(text: &str, needle: &str, nth: usize) -> Range {
+    let start = find(text, needle, nth);
+    Range::new(
+        start,
+        Position::new(start.line, start.character + needle.len() as u32),
+    )
+}
+
+#[test]
+fn publishes_diagnostics() {
+    let client = Client::start(PROGRAM);
+
+    let published = client.diagnostics();
+    assert_eq!(published.version, Some(1));
+    let summary: Vec<_> = published
+        .diagnostics
+        .iter()
+        .map(|diagnostic| {
+            (
+                diagnostic.range,
+                diagnostic.severity.unwrap(),
+                diagnostic.code.clone(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            (
+                range(PROGRAM, "unknown", 0),
+                DiagnosticSeverity::ERROR,
+                None
+            ),
+            (
+                range(PROGRAM, "let shadow = step;", 0),
+                DiagnosticSeverity::WARNING,
+                Some(NumberOrString::String("unused_variable".to_owned()))
+            ),
+        ]
+    );
+
+    client.notify::<DidChangeTextDocument>(DidChangeTextDocumentParams {
+        text_document: VersionedTextDocumentIdentifier::new(client.uri.clone(), 2),
+        content_changes: vec![TextDocumentContentChangeEvent {
+            range: None,
+            range_length: None,
+            text: "let a = 1;\nprint(a);\nlet".to_owned(),
+        }],
+    });
+    let published = client.diagnostics();
+    assert_eq!(published.version, Some(2));
+    assert_eq!(published.diagnostics.len(), 1);
+    assert_eq!(published.diagnostics[0].message, "Expected identifier");
+}
+
+#[test]
+fn navigates_between_declarations_and_uses() {
+    let mut client = Client::start(PROGRAM);
+
+    assert_eq!(
+        client.definition(find(PROGRAM, "add", 1)),
+        Some(range(PROGRAM, "add", 0))
+    );
+    // parameters are declarations of their own
+    assert_eq!(
+        client.definition(find(PROGRAM, "b", 2)),
+        Some(range(PROGRAM, "b", 1))
+    );
+    assert_eq!(client.definition(find(PROGRAM, "print", 0)), None);
+
+    assert_eq!(
+        client.references(find(PROGRAM, "counter", 1)),
+        vec![
+            range(PROGRAM, "counter", 0),
+            range(PROGRAM, "counter", 1),
+            range(PROGRAM, "counter", 2),
+        ]
+    );
+    assert_eq!(
+        client.references(find(PROGRAM, "total", 0)),
+        vec![range(PROGRAM, "total", 0), range(PROGRAM, "total", 1)]
+    );
+}
+
+#[test]
+fn shows_hover_information() {
+    let mut client = Client::start(PROGRAM);
+
+    assert_eq!(
+        client.hover(find(PROGRAM, "add", 1)),
+        "```gravitas\n@pure fn add(a, b)\n```\n\nAdds two numbers\nand nothing else\n\nPure function"
+    );
+    assert_eq!(
+        client.hover(find(PROGRAM, "increment", 0)),
+        "```gravitas\nlet increment = |step|\n```\n\nincrement captures:\n- counter by reference"
+    );
+    assert_eq!(
+        client.hover(find(PROGRAM, "total", 1)),
+        "```gravitas\nlet total: Number\n```"
+    );
+    assert_eq!(
+        client.hover(find(PROGRAM, "print", 0)),
+        "```gravitas\nfn print\n```\nNative function taking 1 argument(s)"
+    );
+}
+
+#[test]
+fn lists_symbols_and_completions() {
+    let mut client = Client::start(
+        "class Animal {
+    fn speak() { let sound = \"...\"; sound }
+}
+fn outer(x) {
+    fn inner() => x
+    inner
+}
+let animal = Animal();
+",
+    );
+
+    let symbols = client.request::<DocumentSymbolRequest>(DocumentSymbolParams {
+        text_document: client.document(),
+        work_done_progress_params: Default::default(),
+        partial_result_params: Default::default(),
+    });
+    let symbols = match symbols.unwrap() {
+        DocumentSymbolResponse::Nested(symbols) => symbols,
+        _ => panic!("Expected nested symbols"),
+    };
+    let outline: Vec<(String, Vec<String>)> = symbols
+        .into_iter()
+        .map(|symbol| {
+            let children = symbol
+                .children
+                .unwrap_or_default()
+                .into_iter()
+                .map(|child| child.name)
+                .collect();
+            (symbol.name, children)
+        })
+        .collect();
+    assert_eq!(
+        outline,
+        vec![
+            ("Animal".to_owned(), vec!["speak".to_owned()]),
+            ("outer".to_owned(), vec!["inner".to_owned()]),
+            ("animal".to_owned(), vec![]),
+        ]
+    );
+
+    let mut completions = client.completions(Position::new(4, 19));
+    completions.sort();
+    assert_eq!(
+        completions,
+        vec!["Animal", "clock", "inner", "outer", "print", "x"]
+    );
+}
+
+#[test]
+fn renames_symbols() {
+    let mut client = Client::start(PROGRAM);
+
+    let edits = client.rename(find(PROGRAM, "counter", 2), "count").unwrap();
+    let ranges: Vec<Range> = edits.iter().map(|edit| edit.range).collect();
+    assert_eq!(
+        ranges,
+        vec![
+            range(PROGRAM, "counter", 0),
+            range(PROGRAM, "counter", 1),
+            range(PROGRAM, "counter", 2),
+        ]
+    );
+    assert!(edits.iter().all(|edit| edit.new_text == "count"));
+
+    // `counter` inside of the closure would be captured by the parameter
+    assert_eq!(
+        client.rename(find(PROGRAM, "counter", 0), "step"),
+        Err("Rename would make 'counter' refer to a different declaration".to_owned())
+    );
+    assert_eq!(
+        client.rename(find(PROGRAM, "print", 0), "show"),
+        Err("Builtin function 'print' can't be renamed".to_owned())
+    );
+}
@@ -0,0 +1,116 @@
+use crate::parse::{Ast, AstRef};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+// Bump whenever the shape of the serialized tree changes,
+// so external tools can tell which layout they are reading.
+pub const AST_FORMAT_VERSION: u32 = 1;
+
+#[derive(Serialize)]
+struct AstDocumentRef<'a> {
+    version: u32,
+    ast: AstRef<'a>,
+}
+
+#[derive(Deserialize)]
+struct AstDocument {
+    ast: Ast,
+}
+
+#[derive(Deserialize)]
+struct VersionHeader {
+    version: u32,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum AstJsonError {
+    UnsupportedVersion(u32),
+    Malformed(String),
+}
+
+impl fmt::Display for AstJsonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AstJsonError::UnsupportedVersion(version) => write!(
+                f,
+                "Unsupported AST format version {}, expected {}",
+                version, AST_FORMAT_VERSION
+            ),
+            AstJsonError::Malformed(reason) => write!(f, "Malformed AST document: {}", reason),
+        }
+    }
+}
+
+impl From<serde_json::Error> for AstJsonError {
+    fn from(error: serde_json::Error) -> Self {
+        AstJsonError::Malformed(error.to_string())
+    }
+}
+
+pub fn to_json(ast: AstRef) -> String {
+    let document = AstDocumentRef {
+        version: AST_FORMAT_VERSION,
+        ast,
+    };
+    serde_json::to_string_pretty(&document).expect("AST is always serializable")
+}
+
+pub fn from_json(json: &str) -> Result<Ast, AstJsonError> {
+    // Check the version first, so documents from other versions
+    // don't get reported as malformed trees.
+    let header: VersionHeader = serde_json::from_str(json)?;
+    if header.version != AST_FORMAT_VERSION {
+        return Err(AstJsonError::UnsupportedVersion(header.version));
+    }
+
+    let document: AstDocument = serde_json::from_str(json)?;
+    Ok(document.ast)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parse;
+
+    fn assert_round_trip(code: &str) {
+        let ast = parse(code).unwrap();
+        let json = to_json(&ast);
+        let imported = from_json(&json).unwrap();
+
+        assert_eq!(ast, imported);
+        // Node equality ignores spans, so compare serialized output as well
+        assert_eq!(json, to_json(&imported));
+    }
+
+    #[test]
+    fn round_trips_ast() {
+        assert_round_trip("let x = 10; x = -x + 2 ** 3;");
+        assert_round_trip("fn foo(a, b) { return a + b; } foo(1, 2);");
+        assert_round_trip("let c = |a| => { a }; [1, \"two\", false][0];");
+        assert_round_trip("while true { if x { break 5; } else { continue; } };");
+        assert_round_trip("let o = new { foo: 1 }; o.foo = o.bar(); !o.foo;");
+        assert_round_trip("class Foo: Bar { fn baz() { 1 } }");
+    }
+
+    #[test]
+    fn exports_spans_and_version() {
+        let ast = parse("1;").unwrap();
+        let json: serde_json::Value = serde_json::from_str(&to_json(&ast)).unwrap();
+
+        assert_eq!(json["version"], AST_FORMAT_VERSION);
+        assert_eq!(json["ast"][0]["span"]["start"], 0);
+        assert_eq!(json["ast"][0]["span"]["end"], 2);
+    }
+
+    #[test]
+    fn rejects_unsupported_documents() {
+        assert_eq!(
+            from_json(r#"{ "version": 0, "ast": [] }"#),
+            Err(AstJsonError::UnsupportedVersion(0))
+        );
+        assert!(matches!(
+            from_json(r#"{ "version": 1, "ast": [{}] }"#),
+            Err(AstJsonError::Malformed(_))
+        ));
+    }
+}
@@ -1,6 +1,9 @@
 use crate::parse::{Parser, ParserOutput};
+pub use parse::incremental::{reparse, ParsedSource, TextEdit, TextEditError};
 use std::{fs, path::Path};
+pub use token::is_identifier;
 
+pub mod json;
 pub mod parse;
 pub(crate) mod token;
 pub mod utils;
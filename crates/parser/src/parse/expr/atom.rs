@@ -7,11 +7,12 @@ use crate::{
     utils::combine,
 };
 use common::{Number, ProgramText};
+use serde::{Deserialize, Serialize};
 use std::fmt;
 
 pub type VariableProperty = Node<String>;
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum AtomicValue {
     Boolean(bool),
     Number(Number),
@@ -43,7 +44,7 @@ impl fmt::Display for AtomicValue {
 }
 
 impl<'t> Parser<'t> {
-    pub(super) fn parse_atom_expr(&mut self) -> ExprResult {
+    pub(super) fn parse_atom_expr(&mut self) -> ExprResult<'_> {
         let lexeme = self.advance()?;
         let atom_span = lexeme.span();
 
@@ -70,7 +71,7 @@ impl<'t> Parser<'t> {
         Ok(Expr::boxed(ExprKind::Atom(val), atom_span))
     }
 
-    pub(super) fn parse_obj_literal(&mut self, nested: bool) -> ExprResult {
+    pub(super) fn parse_obj_literal(&mut self, nested: bool) -> ExprResult<'_> {
         let start = if !nested {
             let new = self.expect(Token::New)?.span();
             let bracket = self
@@ -123,7 +124,7 @@ impl<'t> Parser<'t> {
 
         Ok(Expr::boxed(
             ExprKind::ObjectLiteral { properties },
-            combine(&start, &close_bracket),
+            combine(&start, close_bracket),
         ))
     }
 }
@@ -167,7 +168,6 @@ pub(crate) mod test {
     }
 
     #[quickcheck]
-    #[test]
     fn parses_atom_strings(text: String) {
         let text = text.replace("\"", "");
         // Quote the string, so it's lexed as a string token and not an identifier
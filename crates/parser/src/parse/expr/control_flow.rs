@@ -3,7 +3,7 @@ use crate::utils::error::ParseErrorCause;
 use crate::{
     parse::{
         expr::{Expr, ExprKind},
-        stmt::Stmt,
+        stmt::{Stmt, StmtKind},
         ExprResult, Parser,
     },
     token::{
@@ -14,7 +14,7 @@ use crate::{
 };
 
 impl<'t> Parser<'t> {
-    pub(crate) fn parse_block_expr(&mut self) -> ExprResult {
+    pub(crate) fn parse_block_expr(&mut self) -> ExprResult<'_> {
         let open_bracket = self.expect(OPEN_BRACKET)?.span();
         let mut stmts: Vec<Stmt> = vec![];
         let mut return_expr = None;
@@ -25,14 +25,20 @@ impl<'t> Parser<'t> {
             }
             match self.parse_expr_or_stmt()? {
                 ExprOrStmt::Expr(expr) => {
-                    // return_expr must always come last in the block
-                    return_expr = Some(expr);
+                    if self.peek() == CLOSE_BRACKET {
+                        return_expr = Some(expr);
+                        break;
+                    }
 
-                    if self.peek() != CLOSE_BRACKET {
-                        return Err(ParseErrorCause::ReturnExprMustBeLast);
+                    // if, while and blocks don't need a semicolon to become a statement
+                    if expr.kind.is_block_like() {
+                        let span = expr.span.clone();
+                        stmts.push(Stmt::boxed(StmtKind::Expression { expr }, span));
+                        continue;
                     }
 
-                    break;
+                    // return_expr must always come last in the block
+                    return Err(ParseErrorCause::ReturnExprMustBeLast);
                 }
                 ExprOrStmt::Stmt(stmt) => {
                     stmts.push(stmt);
@@ -45,7 +51,7 @@ impl<'t> Parser<'t> {
         Ok(Expr::boxed(ExprKind::Block { return_expr, stmts }, span))
     }
 
-    pub(super) fn parse_if_expr(&mut self) -> ExprResult {
+    pub(super) fn parse_if_expr(&mut self) -> ExprResult<'_> {
         let start_span = self.expect(Token::If)?.span();
         let condition = self.parse_expression()?;
         let body = self.parse_block_expr()?;
@@ -67,7 +73,7 @@ impl<'t> Parser<'t> {
             .map(|expr| &expr.span)
             .unwrap_or(&body.span);
 
-        let span = combine(&start_span, &end_span);
+        let span = combine(&start_span, end_span);
 
         Ok(Expr::boxed(
             ExprKind::If {
@@ -79,7 +85,7 @@ impl<'t> Parser<'t> {
         ))
     }
 
-    pub(super) fn parse_while_expr(&mut self) -> ExprResult {
+    pub(super) fn parse_while_expr(&mut self) -> ExprResult<'_> {
         let keyword = self.expect(Token::While)?.span();
         let condition = self.parse_expression()?;
         let body = self.parse_block_expr()?;
@@ -88,7 +94,7 @@ impl<'t> Parser<'t> {
         Ok(Expr::boxed(ExprKind::While { condition, body }, span))
     }
 
-    pub(super) fn parse_break_expr(&mut self) -> ExprResult {
+    pub(super) fn parse_break_expr(&mut self) -> ExprResult<'_> {
         let keyword = self.expect(Token::Break)?.span();
         let return_expr = if self.peek().is_expr() {
             Some(self.parse_expression()?)
@@ -105,7 +111,7 @@ impl<'t> Parser<'t> {
         Ok(Expr::boxed(ExprKind::Break { return_expr }, span))
     }
 
-    pub(super) fn parse_continue_expr(&mut self) -> ExprResult {
+    pub(super) fn parse_continue_expr(&mut self) -> ExprResult<'_> {
         let keyword = self.expect(Token::Continue)?.span();
 
         Ok(Expr::boxed(ExprKind::Continue, keyword))
@@ -133,7 +139,18 @@ mod test {
             "{ let x = 10; let y = 5; 5 }",
         );
 
-        assert_expr_error("{", ParseErrorCause::Expected(Expect::Token(CLOSE_BRACKET)))
+        assert_expr_error("{", ParseErrorCause::Expected(Expect::Token(CLOSE_BRACKET)));
+    }
+
+    #[test]
+    fn parser_parses_block_like_statements() {
+        assert_expr(
+            "{ if true { 1 } while false { } 5 }",
+            "{ if true { 1 }; while false {  }; 5 }",
+        );
+        assert_expr("{ { } { } }", "{ {  }; {  } }");
+
+        assert_expr_error("{ 2 3 }", ParseErrorCause::ReturnExprMustBeLast);
     }
 
     #[test]
@@ -16,9 +16,10 @@ use crate::{
         error::{Expect, Forbidden, ParseErrorCause},
     },
 };
+use serde::{Deserialize, Serialize};
+use std::convert::TryInto;
 use std::fmt;
 use std::fmt::Formatter;
-use std::{convert::TryInto, fmt::write};
 
 pub mod atom;
 pub(crate) mod control_flow;
@@ -26,7 +27,7 @@ pub(crate) mod control_flow;
 pub type Expr = Node<Box<ExprKind>>;
 pub type PathSegment = Node<ProgramText>;
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum ExprKind {
     // 1, false, "foo", foo
     Atom(AtomicValue),
@@ -110,6 +111,16 @@ pub enum ExprKind {
     },
 }
 
+impl ExprKind {
+    // Expressions ending with a block that can be used as statements without a trailing semicolon
+    pub(crate) fn is_block_like(&self) -> bool {
+        matches!(
+            self,
+            ExprKind::Block { .. } | ExprKind::If { .. } | ExprKind::While { .. }
+        )
+    }
+}
+
 impl fmt::Display for ExprKind {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         use ExprKind::*;
@@ -206,20 +217,14 @@ impl fmt::Display for ExprKind {
             GetProperty {
                 target, identifier, ..
             } => {
-                write!(f, "{}.{}", target.kind.to_string(), identifier)?;
+                write!(f, "{}.{}", target.kind, identifier)?;
             }
             SetProperty {
                 target,
                 value,
                 identifier,
             } => {
-                write!(
-                    f,
-                    "{}.{} = {}",
-                    target.kind.to_string(),
-                    identifier,
-                    value.kind.to_string()
-                )?;
+                write!(f, "{}.{} = {}", target.kind, identifier, value.kind)?;
             }
             Assignment { target, value } => {
                 write!(f, "{} = {}", target, value)?;
@@ -231,7 +236,7 @@ impl fmt::Display for ExprKind {
             ObjectLiteral { properties } => {
                 write!(f, "obj ")?;
                 for (name, value) in properties {
-                    write!(f, "{}:{}", name, value.to_string())?;
+                    write!(f, "{}:{}", name, value)?;
                 }
                 write!(f, " obj")?;
             }
@@ -241,11 +246,11 @@ impl fmt::Display for ExprKind {
 }
 
 impl<'t> Parser<'t> {
-    pub(crate) fn parse_expression(&mut self) -> ExprResult {
+    pub(crate) fn parse_expression(&mut self) -> ExprResult<'_> {
         self.parse_expression_bp(0)
     }
 
-    fn parse_expression_bp(&mut self, min_bp: u8) -> ExprResult {
+    fn parse_expression_bp(&mut self, min_bp: u8) -> ExprResult<'_> {
         if !self.peek().is_expr() {
             return Err(ParseErrorCause::Expected(Expect::Expression));
         }
@@ -398,7 +403,7 @@ impl<'t> Parser<'t> {
         Ok(lhs)
     }
 
-    pub(super) fn parse_array_expr(&mut self) -> ExprResult {
+    pub(super) fn parse_array_expr(&mut self) -> ExprResult<'_> {
         let start = self.expect(OPEN_SQUARE)?.span();
         let mut values: Vec<Expr> = Vec::new();
 
@@ -428,7 +433,7 @@ impl<'t> Parser<'t> {
         ))
     }
 
-    pub(super) fn parse_return_expr(&mut self) -> ExprResult {
+    pub(super) fn parse_return_expr(&mut self) -> ExprResult<'_> {
         let return_keyword = self.expect(Token::Return)?.span();
         let value = if self.peek().is_expr() {
             Some(self.parse_expression()?)
@@ -445,7 +450,7 @@ impl<'t> Parser<'t> {
         Ok(Expr::boxed(ExprKind::Return { value }, span))
     }
 
-    pub(super) fn parse_closure_expression(&mut self) -> ExprResult {
+    pub(super) fn parse_closure_expression(&mut self) -> ExprResult<'_> {
         let params = self.parse_params()?;
         self.expect(Token::Arrow)?;
         let body = self.parse_expression()?;
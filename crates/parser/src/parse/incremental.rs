@@ -0,0 +1,521 @@
+use crate::parse::{
+    expr::{Expr, ExprKind},
+    stmt::{Stmt, StmtKind},
+    Ast, Node, Parser, ProgramErrors, Span,
+};
+use crate::token::{DetachedLexeme, Lexeme, Source, Token};
+use std::{fmt, ops::Range};
+
+// Replaces `range` of the previous source (in its byte offsets) with `text`
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextEdit {
+    pub range: Range<usize>,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TextEditError {
+    // Range is reversed or reaches past the end of the source
+    OutOfBounds { range: Range<usize>, len: usize },
+    // Range starts or ends inside of a character
+    NotCharBoundary(usize),
+}
+
+impl fmt::Display for TextEditError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TextEditError::OutOfBounds { range, len } => write!(
+                f,
+                "edit of {}..{} is out of bounds of a source with {} bytes",
+                range.start, range.end, len
+            ),
+            TextEditError::NotCharBoundary(offset) => {
+                write!(f, "edit at {} splits a character", offset)
+            }
+        }
+    }
+}
+
+impl TextEdit {
+    pub fn new(range: Range<usize>, text: impl Into<String>) -> Self {
+        Self {
+            range,
+            text: text.into(),
+        }
+    }
+
+    pub fn apply(&self, source: &str) -> Result<String, TextEditError> {
+        let Range { start, end } = self.range;
+        if start > end || end > source.len() {
+            return Err(TextEditError::OutOfBounds {
+                range: self.range.clone(),
+                len: source.len(),
+            });
+        }
+        if let Some(&offset) = [start, end]
+            .iter()
+            .find(|offset| !source.is_char_boundary(**offset))
+        {
+            return Err(TextEditError::NotCharBoundary(offset));
+        }
+
+        let mut edited = source.to_owned();
+        edited.replace_range(self.range.clone(), &self.text);
+        Ok(edited)
+    }
+
+    fn delta(&self) -> isize {
+        self.text.len() as isize - self.range.len() as isize
+    }
+
+    // End of the inserted text in the edited source
+    fn edited_end(&self) -> usize {
+        self.range.start + self.text.len()
+    }
+}
+
+// Tree of a source together with its tokens, which `reparse` reuses after an edit
+#[derive(Debug, Clone)]
+pub struct ParsedSource {
+    pub ast: Ast,
+    tokens: Vec<DetachedLexeme>,
+    len: usize,
+}
+
+impl ParsedSource {
+    pub fn parse(source: &str) -> Result<Self, ProgramErrors> {
+        let lexemes: Vec<_> = Source::starting_at(source, 0).collect();
+        let ast = Parser::from_lexemes(lexemes.clone()).parse()?;
+        Ok(Self::new(ast, &lexemes, source.len()))
+    }
+
+    fn new(ast: Ast, lexemes: &[Lexeme], len: usize) -> Self {
+        Self {
+            ast,
+            tokens: lexemes.iter().map(Lexeme::detach).collect(),
+            len,
+        }
+    }
+}
+
+// Parses `source` (the text after applying `edit`) reusing statements and tokens of `previous`,
+// the parse of the text before the edit.
+// Statements in front of the edit are kept as they are and parsing resumes right after them.
+// Once a freshly parsed statement ends where an untouched statement behind the edit used to end,
+// the remaining text is the same as before, so the rest of the previous tree is reused with shifted spans.
+pub fn reparse(
+    previous: &ParsedSource,
+    source: &str,
+    edit: &TextEdit,
+) -> Result<ParsedSource, ProgramErrors> {
+    let delta = edit.delta();
+    // Edit doesn't belong to the previous source, nothing of it can be trusted
+    if edit.range.start > edit.range.end
+        || edit.range.end > previous.len
+        || source.len() as isize != previous.len as isize + delta
+    {
+        return ParsedSource::parse(source);
+    }
+
+    let previous_ast = &previous.ast;
+    let mut prefix_len = previous_ast
+        .iter()
+        .take_while(|stmt| stmt.span.end <= edit.range.start)
+        .count();
+
+    // Function body is an expression, so its end depends on whatever follows it
+    if let Some(stmt) = prefix_len.checked_sub(1).map(|last| &previous_ast[last]) {
+        if let StmtKind::FunctionDeclaration { .. } = &*stmt.kind {
+            prefix_len -= 1;
+        }
+    }
+
+    let resume_from = prefix_len
+        .checked_sub(1)
+        .map(|last| previous_ast[last].span.end)
+        .unwrap_or(0);
+
+    let suffix = &previous_ast[prefix_len..];
+    let lexemes = relex(&previous.tokens, source, edit, resume_from);
+    let unchanged = lexemes.partition_point(|lexeme| lexeme.span_end <= resume_from);
+
+    let mut ast: Ast = previous_ast[..prefix_len].to_vec();
+    let mut parser = Parser::from_lexemes(lexemes[unchanged..].to_vec());
+
+    while parser.peek() != Token::Eof {
+        match parser.parse_stmt() {
+            Ok(stmt) => ast.push(stmt),
+            // Error recovery needs the whole picture, so leave it to the full parse
+            Err(_) => return ParsedSource::parse(source),
+        }
+
+        let previous_end = parser.lexer.current_span().end as isize - delta;
+        if previous_end < edit.range.end as isize {
+            continue;
+        }
+
+        if let Ok(index) =
+            suffix.binary_search_by_key(&(previous_end as usize), |stmt| stmt.span.end)
+        {
+            ast.extend(suffix[index + 1..].iter().map(|stmt| {
+                let mut stmt = stmt.clone();
+                shift_stmt(&mut stmt, delta);
+                stmt
+            }));
+            break;
+        }
+    }
+
+    Ok(ParsedSource::new(ast, &lexemes, source.len()))
+}
+
+// Lexes the edited source from `resume_from` until a token starts behind the edit where a previous
+// token used to start. Lexing doesn't depend on what came before a token, so from there on
+// the tokens are the previous ones, shifted by the length difference of the edit.
+fn relex<'t>(
+    previous: &[DetachedLexeme],
+    source: &'t str,
+    edit: &TextEdit,
+    resume_from: usize,
+) -> Vec<Lexeme<'t>> {
+    let delta = edit.delta();
+    let unchanged = previous.partition_point(|token| token.span.end <= resume_from);
+    let mut lexemes: Vec<_> = previous[..unchanged]
+        .iter()
+        .map(|token| token.attach(source, 0))
+        .collect();
+
+    for lexeme in Source::starting_at(source, resume_from) {
+        if lexeme.span_start >= edit.edited_end() {
+            let previous_start = (lexeme.span_start as isize - delta) as usize;
+            if let Ok(index) =
+                previous.binary_search_by_key(&previous_start, |token| token.span.start)
+            {
+                lexemes.extend(
+                    previous[index..]
+                        .iter()
+                        .map(|token| token.attach(source, delta)),
+                );
+                return lexemes;
+            }
+        }
+        lexemes.push(lexeme);
+    }
+
+    lexemes
+}
+
+fn shift_span(span: &mut Span, delta: isize) {
+    span.start = (span.start as isize + delta) as usize;
+    span.end = (span.end as isize + delta) as usize;
+}
+
+fn shift_node<T>(node: &mut Node<T>, delta: isize) {
+    shift_span(&mut node.span, delta);
+}
+
+fn shift_stmt(stmt: &mut Stmt, delta: isize) {
+    use StmtKind::*;
+
+    shift_node(stmt, delta);
+    match &mut *stmt.kind {
+        Expression { expr } | VariableDeclaration { expr, .. } => shift_expr(expr, delta),
+        FunctionDeclaration { params, body, .. } => {
+            shift_node(params, delta);
+            params
+                .kind
+                .iter_mut()
+                .for_each(|param| shift_node(param, delta));
+            shift_expr(body, delta);
+        }
+        ClassDeclaration { methods, .. } => {
+            methods
+                .iter_mut()
+                .for_each(|method| shift_stmt(method, delta));
+        }
+    }
+}
+
+fn shift_expr(expr: &mut Expr, delta: isize) {
+    use ExprKind::*;
+
+    shift_node(expr, delta);
+    match &mut *expr.kind {
+        Atom(_) | Continue => {}
+        Binary { lhs, op, rhs } => {
+            shift_expr(lhs, delta);
+            shift_node(op, delta);
+            shift_expr(rhs, delta);
+        }
+        Unary { op, rhs } => {
+            shift_node(op, delta);
+            shift_expr(rhs, delta);
+        }
+        Block { stmts, return_expr } => {
+            stmts.iter_mut().for_each(|stmt| shift_stmt(stmt, delta));
+            if let Some(return_expr) = return_expr {
+                shift_expr(return_expr, delta);
+            }
+        }
+        If {
+            condition,
+            body,
+            else_expr,
+        } => {
+            shift_expr(condition, delta);
+            shift_expr(body, delta);
+            if let Some(else_expr) = else_expr {
+                shift_expr(else_expr, delta);
+            }
+        }
+        While { condition, body } => {
+            shift_expr(condition, delta);
+            shift_expr(body, delta);
+        }
+        Break { return_expr: value } | Return { value } => {
+            if let Some(value) = value {
+                shift_expr(value, delta);
+            }
+        }
+        Call { callee, args } => {
+            shift_expr(callee, delta);
+            args.iter_mut().for_each(|arg| shift_expr(arg, delta));
+        }
+        Array { values } => {
+            values.iter_mut().for_each(|value| shift_expr(value, delta));
+        }
+        Index { target, position } => {
+            shift_expr(target, delta);
+            shift_expr(position, delta);
+        }
+        GetProperty {
+            target, identifier, ..
+        } => {
+            shift_expr(target, delta);
+            shift_node(identifier, delta);
+        }
+        SetProperty {
+            target,
+            value,
+            identifier,
+        } => {
+            shift_expr(target, delta);
+            shift_expr(value, delta);
+            shift_node(identifier, delta);
+        }
+        ObjectLiteral { properties } => {
+            properties
+                .iter_mut()
+                .for_each(|(_, value)| shift_expr(value, delta));
+        }
+        Assignment { target, value } => {
+            shift_expr(target, delta);
+            shift_expr(value, delta);
+        }
+        Closure { params, body } => {
+            shift_node(params, delta);
+            params
+                .kind
+                .iter_mut()
+                .for_each(|param| shift_node(param, delta));
+            shift_expr(body, delta);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use quickcheck::{Arbitrary, Gen};
+    use quickcheck_macros::quickcheck;
+
+    use super::*;
+    use crate::{json::to_json, parse};
+
+    const STATEMENTS: &[&str] = &[
+        "let a = 10;",
+        "a = a + 1;",
+        "fn foo(a, b) { return a + b; }",
+        "fn bar() => 1 + 2",
+        "class Foo: Bar { fn baz() { this.x } }",
+        "while a < 10 { a = a + 1; };",
+        "if a { 1 } else { 2 };",
+        "let c = |x| => x * 2;",
+        "print(new { b: [1, 2][0] });",
+        "\"text\";",
+        "// comment",
+    ];
+
+    const FRAGMENTS: &[&str] = &[
+        "",
+        " ",
+        "\n",
+        ";",
+        "{",
+        "}",
+        "(",
+        ")",
+        "1",
+        "+ 2",
+        "a",
+        "fn",
+        "let",
+        "=",
+        "\"",
+        "//",
+        "- 1;",
+        "foo();",
+        "let z = 3;",
+        "fn q() => z",
+    ];
+
+    #[derive(Debug, Clone)]
+    struct EditedProgram {
+        source: String,
+        edit: TextEdit,
+    }
+
+    impl Arbitrary for EditedProgram {
+        fn arbitrary(g: &mut Gen) -> Self {
+            let len = usize::arbitrary(g) % 8;
+            let source = (0..len)
+                .map(|_| *g.choose(STATEMENTS).unwrap())
+                .collect::<Vec<_>>()
+                .join(*g.choose(&[" ", "\n", "\n\n"]).unwrap());
+
+            // Sources are ascii only, so every offset is a char boundary
+            let start = usize::arbitrary(g) % (source.len() + 1);
+            let end = start + usize::arbitrary(g) % (source.len() - start + 1).min(12);
+            let edit = TextEdit::new(start..end, *g.choose(FRAGMENTS).unwrap());
+
+            Self { source, edit }
+        }
+    }
+
+    // Compares against a full parse of `source`, the text after the edit
+    fn assert_same_output(incremental: Result<ParsedSource, ProgramErrors>, source: &str) {
+        match (incremental, parse(source)) {
+            (Ok(incremental), Ok(full)) => {
+                // Node equality ignores spans, so compare the serialized trees instead
+                assert_eq!(to_json(&incremental.ast), to_json(&full));
+
+                let tokens: Vec<_> = incremental
+                    .tokens
+                    .iter()
+                    .map(|token| token.attach(source, 0))
+                    .collect();
+                let lexed: Vec<_> = Source::starting_at(source, 0).collect();
+                assert_eq!(tokens, lexed);
+            }
+            (incremental, full) => assert_eq!(incremental.map(|parsed| parsed.ast), full),
+        }
+    }
+
+    #[quickcheck]
+    fn reparse_matches_full_parse(program: EditedProgram) {
+        let previous = match ParsedSource::parse(&program.source) {
+            Ok(parsed) => parsed,
+            Err(_) => return,
+        };
+
+        let source = program.edit.apply(&program.source).unwrap();
+        assert_same_output(reparse(&previous, &source, &program.edit), &source);
+    }
+
+    #[test]
+    fn reparses_edits() {
+        let assert_reparse = |old: &str, edit: TextEdit| {
+            let previous = ParsedSource::parse(old).unwrap();
+            let source = edit.apply(old).unwrap();
+            assert_same_output(reparse(&previous, &source, &edit), &source);
+        };
+
+        // edit inside a statement in the middle
+        assert_reparse(
+            "let a = 1; let b = 2; let c = 3;",
+            TextEdit::new(19..20, "20"),
+        );
+        // new statement in front of the others
+        assert_reparse("let a = 1; a;", TextEdit::new(0..0, "let b = 2; "));
+        // function body gets extended by the edit behind it
+        assert_reparse("fn foo() => 1 foo();", TextEdit::new(13..13, " + 2"));
+        // statement removed at the end
+        assert_reparse("let a = 1; a;", TextEdit::new(10..13, ""));
+        // identifier grows into the token behind the edit
+        assert_reparse("let a = b + c;", TextEdit::new(9..12, "c"));
+        // edit opens a string that swallows the following tokens
+        assert_reparse("let a = 1; \"b\"; let c = 2;", TextEdit::new(8..9, "\""));
+        // broken program falls back to full parse
+        assert_reparse("let a = 1; a;", TextEdit::new(4..5, ""));
+    }
+
+    #[test]
+    fn reuses_tokens_behind_the_edit() {
+        let old = "let a = 1; let b = \"text\"; let c = 2;";
+        let mut previous = ParsedSource::parse(old).unwrap();
+        // Tokens that are reused keep whatever they were, even when they don't match the text
+        let two = previous.tokens.len() - 2;
+        previous.tokens[two] = Lexeme {
+            token: Token::Number(3.0),
+            slice: "2",
+            span_start: 35,
+            span_end: 36,
+        }
+        .detach();
+
+        let edit = TextEdit::new(8..9, "10");
+        let source = edit.apply(old).unwrap();
+        let reparsed = reparse(&previous, &source, &edit).unwrap();
+        let tokens: Vec<_> = reparsed
+            .tokens
+            .iter()
+            .map(|token| token.attach(&source, 0))
+            .collect();
+
+        assert_eq!(tokens[3].token, Token::Number(10.0));
+        assert_eq!(tokens[8].token, Token::String("text"));
+        assert_eq!(tokens[8].slice, "\"text\"");
+        assert_eq!(tokens[13].token, Token::Number(3.0));
+        assert_eq!(tokens[13].span(), 36..37);
+    }
+
+    #[test]
+    fn rejects_edits_outside_of_the_source() {
+        let source = "let a = \"ä\";";
+
+        assert_eq!(
+            TextEdit::new(10..20, "").apply(source),
+            Err(TextEditError::OutOfBounds {
+                range: 10..20,
+                len: 13
+            })
+        );
+        let reversed = Range { start: 5, end: 4 };
+        assert_eq!(
+            TextEdit::new(reversed.clone(), "").apply(source),
+            Err(TextEditError::OutOfBounds {
+                range: reversed,
+                len: 13
+            })
+        );
+        assert_eq!(
+            TextEdit::new(10..11, "a").apply(source),
+            Err(TextEditError::NotCharBoundary(10))
+        );
+        assert_eq!(
+            TextEdit::new(9..11, "a").apply(source),
+            Ok("let a = \"a\";".to_owned())
+        );
+    }
+
+    #[test]
+    fn reparses_edits_that_dont_match_the_previous_source() {
+        let previous = ParsedSource::parse("let a = 1;").unwrap();
+        let source = "let b = 2; b;";
+
+        assert_same_output(
+            reparse(&previous, source, &TextEdit::new(20..30, "")),
+            source,
+        );
+        assert_same_output(reparse(&previous, source, &TextEdit::new(0..1, "")), source);
+    }
+}
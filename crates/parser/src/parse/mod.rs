@@ -5,9 +5,11 @@ use crate::{
     token::{constants::IDENTIFIER, Lexeme, Lexer, Token},
     utils::error::{Expect, ParseError, ParseErrorCause},
 };
+use serde::{Deserialize, Serialize};
 use std::{fmt, mem::discriminant, ops::Range};
 
 pub mod expr;
+pub mod incremental;
 pub mod operator;
 pub(crate) mod pieces;
 pub mod stmt;
@@ -32,7 +34,7 @@ pub(crate) type StmtResult<'t> = ParseResult<'t, Stmt>;
 
 pub type Span = Range<usize>;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Node<T> {
     pub kind: T,
     pub span: Span,
@@ -79,26 +81,24 @@ impl<'t> Parser<'t> {
         }
     }
 
-    fn peek(&mut self) -> Token {
+    pub(crate) fn from_lexemes(lexemes: Vec<Lexeme<'t>>) -> Self {
+        Self {
+            lexer: Lexer::from_lexemes(lexemes),
+        }
+    }
+
+    fn peek(&mut self) -> Token<'_> {
         self.lexer
             .peek_nth(0)
             .map(|l| l.token)
             .unwrap_or(Token::Eof)
     }
 
-    fn peek_eq_consume(&mut self, expected: Token) -> Option<ParseResult<Lexeme>> {
-        if let expected = self.peek() {
-            Some(self.advance())
-        } else {
-            None
-        }
-    }
-
-    fn advance(&mut self) -> ParseResult<Lexeme> {
+    fn advance(&mut self) -> ParseResult<'_, Lexeme<'_>> {
         self.lexer.next().ok_or(ParseErrorCause::EndOfInput)
     }
 
-    fn expect(&mut self, expected: Token<'static>) -> ParseResult<Lexeme> {
+    fn expect(&mut self, expected: Token<'static>) -> ParseResult<'_, Lexeme<'_>> {
         if let Ok(next) = self.advance() {
             if next.token == expected {
                 return Ok(next);
@@ -108,7 +108,7 @@ impl<'t> Parser<'t> {
         Err(ParseErrorCause::Expected(Expect::Token(expected)))
     }
 
-    fn expect_identifier(&mut self) -> ParseResult<Lexeme> {
+    fn expect_identifier(&mut self) -> ParseResult<'_, Lexeme<'_>> {
         if let Ok(next) = self.advance() {
             if discriminant(&next.token) == discriminant(&IDENTIFIER) {
                 return Ok(next);
@@ -130,13 +130,21 @@ impl<'t> Parser<'t> {
                     ast.push(stmt);
                 }
                 Err(cause) => {
+                    let span_end = self.lexer.current_span();
+                    // make sure we move forward even if the statement failed on its first token
+                    let stalled = span_end == span_start;
+
                     let parse_error = ParseError {
                         cause,
                         span_start,
-                        span_end: self.lexer.current_span(),
+                        span_end,
                     };
                     errors.push(parse_error);
 
+                    if stalled {
+                        self.advance().unwrap();
+                    }
+
                     // discard every expression until we encounter a new statement
                     loop {
                         let next = self.peek();
@@ -156,7 +164,7 @@ impl<'t> Parser<'t> {
         }
     }
 
-    fn construct_node<T>(&mut self, val: T) -> ParseResult<Node<T>> {
+    fn construct_node<T>(&mut self, val: T) -> ParseResult<'_, Node<T>> {
         let lexeme = self.advance()?;
         Ok(Node {
             kind: val,
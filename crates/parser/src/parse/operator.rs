@@ -1,4 +1,5 @@
 use crate::{token::operator::Operator, utils::error::ParseErrorCause};
+use serde::{Deserialize, Serialize};
 use std::fmt;
 
 macro_rules! impl_double_ended_conversion {
@@ -25,7 +26,7 @@ macro_rules! impl_double_ended_conversion {
     };
 }
 
-#[derive(Debug, PartialEq, Copy, Clone)]
+#[derive(Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
 pub enum BinaryOperator {
     // +
     Addition,
@@ -90,7 +91,7 @@ impl fmt::Display for BinaryOperator {
     }
 }
 
-#[derive(Debug, PartialEq, Copy, Clone)]
+#[derive(Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
 pub enum UnaryOperator {
     Negate,
     Not,
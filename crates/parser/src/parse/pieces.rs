@@ -11,7 +11,7 @@ use crate::{
 };
 
 impl<'t> Parser<'t> {
-    pub(super) fn parse_params(&mut self) -> ParseResult<Params> {
+    pub(super) fn parse_params(&mut self) -> ParseResult<'_, Params> {
         let (open_parenthesis, closing_token) = {
             // we encountered closure opening so we will have to expect closing bar
             // after the end of the parameters list
@@ -0,0 +1,76 @@
+use crate::{
+    parse::{
+        stmt::{Stmt, StmtKind},
+        Parser, StmtResult,
+    },
+    token::{
+        constants::{CLOSE_BRACKET, OPEN_BRACKET},
+        Token,
+    },
+    utils::combine,
+};
+
+impl<'t> Parser<'t> {
+    // class Foo {
+    //  fn method() { }
+    // }
+    // class Bar: Foo { }
+
+    pub(crate) fn parse_class_declaration(&mut self) -> StmtResult<'_> {
+        let class_keyword = self.expect(Token::Class)?.span();
+        let name = self.expect_identifier()?.slice.to_owned();
+        let super_class = if self.peek() == Token::Colon {
+            self.advance()?;
+            Some(self.expect_identifier()?.slice.to_owned())
+        } else {
+            None
+        };
+
+        self.expect(OPEN_BRACKET)?;
+        let mut methods: Vec<Stmt> = Vec::new();
+
+        while matches!(self.peek(), Token::Function | Token::At) {
+            methods.push(self.parse_fun_declaration()?);
+        }
+
+        let close_bracket = self.expect(CLOSE_BRACKET)?.span();
+        let span = combine(&class_keyword, &close_bracket);
+
+        Ok(Stmt::boxed(
+            StmtKind::ClassDeclaration {
+                name,
+                super_class,
+                methods,
+            },
+            span,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        token::constants::CLOSE_BRACKET,
+        utils::{
+            error::{Expect, ParseErrorCause},
+            test::parser::{assert_stmt, assert_stmt_error},
+        },
+    };
+
+    #[test]
+    fn parser_parses_class_declarations() {
+        assert_stmt("class Foo {}", "class Foo { }");
+        assert_stmt("class Foo: Bar {}", "class Foo: Bar { }");
+        assert_stmt(
+            "class Foo { fn bar() => 10 fn baz(a) { a } }",
+            "class Foo { fn bar(empty) 10 fn baz(args) { a } }",
+        );
+
+        assert_stmt_error("class", ParseErrorCause::Expected(Expect::Identifier));
+        assert_stmt_error("class Foo:", ParseErrorCause::Expected(Expect::Identifier));
+        assert_stmt_error(
+            "class Foo { let x = 10; }",
+            ParseErrorCause::Expected(Expect::Token(CLOSE_BRACKET)),
+        );
+    }
+}
@@ -1,10 +1,10 @@
 use crate::{
     parse::{
-        stmt::{Stmt, StmtKind},
-        Parser, StmtResult,
+        stmt::{Annotation, Stmt, StmtKind},
+        Node, ParseResult, Parser, StmtResult,
     },
     token::{constants::OPEN_BRACKET, Token},
-    utils::combine,
+    utils::{combine, error::ParseErrorCause},
 };
 
 impl<'t> Parser<'t> {
@@ -13,7 +13,8 @@ impl<'t> Parser<'t> {
     //  return a + b + c;
     // }
 
-    pub(crate) fn parse_fun_declaration(&mut self) -> StmtResult {
+    pub(crate) fn parse_fun_declaration(&mut self) -> StmtResult<'_> {
+        let annotations = self.parse_annotations()?;
         let fn_keyword = self.expect(Token::Function)?.span();
         let name = self.expect_identifier()?.slice.to_owned();
         let params = self.parse_params()?;
@@ -21,12 +22,36 @@ impl<'t> Parser<'t> {
             self.expect(Token::Arrow)?;
         }
         let body = self.parse_expression()?;
-        let span = combine(&fn_keyword, &body.span);
+        let start = annotations
+            .first()
+            .map_or(fn_keyword, |annotation| annotation.span.clone());
+        let span = combine(&start, &body.span);
         Ok(Stmt::boxed(
-            StmtKind::FunctionDeclaration { name, params, body },
+            StmtKind::FunctionDeclaration {
+                name,
+                params,
+                body,
+                annotations,
+            },
             span,
         ))
     }
+
+    // @pure @another
+    fn parse_annotations(&mut self) -> ParseResult<'_, Vec<Node<Annotation>>> {
+        let mut annotations = Vec::new();
+
+        while self.peek() == Token::At {
+            let at = self.advance()?.span();
+            let identifier = self.expect_identifier()?;
+            let span = combine(&at, &identifier.span());
+            let annotation = Annotation::from_name(identifier.slice)
+                .ok_or_else(|| ParseErrorCause::UnknownAnnotation(identifier.slice.to_owned()))?;
+            annotations.push(Node::new(annotation, span));
+        }
+
+        Ok(annotations)
+    }
 }
 
 #[cfg(test)]
@@ -34,8 +59,8 @@ mod test {
     use crate::{
         parse::{
             expr::{atom::AtomicValue, Expr, ExprKind},
-            stmt::{Stmt, StmtKind},
-            Param, Params, Parser,
+            stmt::{Annotation, Stmt, StmtKind},
+            Node, Param, Params, Parser,
         },
         token::constants::OPEN_PARENTHESIS,
         token::Token,
@@ -85,7 +110,8 @@ mod test {
                 StmtKind::FunctionDeclaration {
                     name: "foo".to_owned(),
                     params: Params::new(vec![], 6..8),
-                    body: Expr::boxed(ExprKind::Atom(AtomicValue::Number(2.0)), 12..13)
+                    body: Expr::boxed(ExprKind::Atom(AtomicValue::Number(2.0)), 12..13),
+                    annotations: vec![],
                 },
                 0..13
             )
@@ -113,6 +139,7 @@ mod test {
                     },
                     11..16,
                 ),
+                annotations: vec![],
             },
             0..16,
         );
@@ -127,4 +154,30 @@ mod test {
             fun_node
         )
     }
+
+    #[test]
+    fn parser_parses_annotations() {
+        let mut parser = Parser::new("@pure fn foo() => 2");
+        let declaration = parser.parse_stmt().unwrap();
+        assert_eq!(declaration.span, 0..19);
+        match &*declaration.kind {
+            StmtKind::FunctionDeclaration { annotations, .. } => {
+                assert_eq!(annotations, &vec![Node::new(Annotation::Pure, 0..5)]);
+                assert_eq!(annotations[0].span, 0..5);
+            }
+            _ => unreachable!(),
+        }
+
+        let mut parser = Parser::new("@memoize fn foo() => 2");
+        assert_eq!(
+            parser.parse_stmt().unwrap_err(),
+            ParseErrorCause::UnknownAnnotation("memoize".to_owned())
+        );
+
+        let mut parser = Parser::new("@pure let a = 2;");
+        assert_eq!(
+            parser.parse_stmt().unwrap_err(),
+            ParseErrorCause::Expected(Expect::Token(Token::Function))
+        );
+    }
 }
@@ -4,15 +4,17 @@ use crate::{
     utils::combine,
 };
 use common::ProgramText;
+use serde::{Deserialize, Serialize};
 use std::fmt;
 
 use super::FunctionBody;
 
 pub type Stmt = Node<Box<StmtKind>>;
 
+pub(crate) mod class;
 pub(crate) mod fun;
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum StmtKind {
     Expression {
         expr: Expr,
@@ -25,7 +27,38 @@ pub enum StmtKind {
         name: ProgramText,
         params: Params,
         body: FunctionBody,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        annotations: Vec<Node<Annotation>>,
     },
+    ClassDeclaration {
+        name: ProgramText,
+        super_class: Option<ProgramText>,
+        methods: Vec<Stmt>,
+    },
+}
+
+// @pure fn foo(a) => a + 1
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Annotation {
+    // Function can't have any side effects, which the analyzer checks
+    Pure,
+}
+
+impl Annotation {
+    pub(crate) fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "pure" => Some(Annotation::Pure),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Annotation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Annotation::Pure => write!(f, "@pure"),
+        }
+    }
 }
 
 impl fmt::Display for StmtKind {
@@ -39,7 +72,15 @@ impl fmt::Display for StmtKind {
             VariableDeclaration { expr, name } => {
                 write!(f, "let {} = {};", name, expr)?;
             }
-            FunctionDeclaration { params, body, name } => {
+            FunctionDeclaration {
+                params,
+                body,
+                name,
+                annotations,
+            } => {
+                for annotation in annotations {
+                    write!(f, "{} ", annotation)?;
+                }
                 write!(
                     f,
                     "fn {}({}) {}",
@@ -52,6 +93,21 @@ impl fmt::Display for StmtKind {
                     body
                 )?;
             }
+            ClassDeclaration {
+                name,
+                super_class,
+                methods,
+            } => {
+                write!(f, "class {}", name)?;
+                if let Some(super_class) = super_class {
+                    write!(f, ": {}", super_class)?;
+                }
+                write!(f, " {{ ")?;
+                for method in methods {
+                    write!(f, "{} ", method)?;
+                }
+                write!(f, "}}")?;
+            }
         }
 
         Ok(())
@@ -59,15 +115,16 @@ impl fmt::Display for StmtKind {
 }
 
 impl<'t> Parser<'t> {
-    pub(crate) fn parse_stmt(&mut self) -> StmtResult {
+    pub(crate) fn parse_stmt(&mut self) -> StmtResult<'_> {
         match self.peek() {
             Token::Let => self.parse_variable_declaration(),
-            Token::Function => self.parse_fun_declaration(),
+            Token::Function | Token::At => self.parse_fun_declaration(),
+            Token::Class => self.parse_class_declaration(),
             _ => self.parse_expression_stmt(),
         }
     }
 
-    pub(super) fn parse_expression_stmt(&mut self) -> StmtResult {
+    pub(super) fn parse_expression_stmt(&mut self) -> StmtResult<'_> {
         let expr = self.parse_expression()?;
         let semicolon = self.expect(Token::Semicolon)?.span();
         let span = combine(&expr.span, &semicolon);
@@ -75,7 +132,7 @@ impl<'t> Parser<'t> {
         Ok(Stmt::boxed(StmtKind::Expression { expr }, span))
     }
 
-    pub(super) fn parse_variable_declaration(&mut self) -> StmtResult {
+    pub(super) fn parse_variable_declaration(&mut self) -> StmtResult<'_> {
         let let_keyword = {
             let lexeme = self.expect(Token::Let)?;
             lexeme.span()
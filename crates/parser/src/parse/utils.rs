@@ -14,7 +14,7 @@ pub(crate) enum ExprOrStmt {
 }
 
 impl<'t> Parser<'t> {
-    pub(super) fn parse_expr_or_stmt(&mut self) -> ParseResult<ExprOrStmt> {
+    pub(super) fn parse_expr_or_stmt(&mut self) -> ParseResult<'_, ExprOrStmt> {
         if self.peek().is_stmt() {
             return Ok(ExprOrStmt::Stmt(self.parse_stmt()?));
         }
@@ -27,7 +27,7 @@ fn lex_number<'t>(lex: &mut logos::Lexer<'t, Token<'t>>) -> Result<f64, Token<'t
         return Ok(f64::NAN);
     }
 
-    if MULTIPLE_DOTS_IN_NUMBER.is_match(&slice) {
+    if MULTIPLE_DOTS_IN_NUMBER.is_match(slice) {
         Err(Token::Error)
     } else {
         slice.parse::<f64>().map_err(|_| Token::Error)
@@ -91,6 +91,9 @@ pub enum Token<'t> {
     #[token("|")]
     #[display(fmt = "|")]
     Bar,
+    #[token("@")]
+    #[display(fmt = "@")]
+    At,
     // EXPRESSION KEYWORDS
     #[token("if")]
     If,
@@ -132,7 +135,7 @@ impl<'t> Token<'t> {
     pub(crate) fn is_stmt(&self) -> bool {
         use Token::*;
 
-        matches!(self, Class | Function | Let)
+        matches!(self, Class | Function | Let | At)
     }
 
     pub(crate) fn is_expr(&self) -> bool {
@@ -162,16 +165,51 @@ impl<'t> Token<'t> {
     pub(crate) fn is_identifier(&self) -> bool {
         matches!(self, Token::Identifier(_))
     }
+
+    // Same token with its text taken from `slice`, the whole slice of the token in a source
+    pub(crate) fn with_slice<'s>(self, slice: &'s str) -> Token<'s> {
+        use Token::*;
+
+        match self {
+            String(_) => String(slice.get(1..slice.len().saturating_sub(1)).unwrap_or("")),
+            Identifier(_) => Identifier(slice),
+            Function => Function,
+            Class => Class,
+            Let => Let,
+            Semicolon => Semicolon,
+            Arrow => Arrow,
+            Comma => Comma,
+            New => New,
+            Colon => Colon,
+            Bar => Bar,
+            At => At,
+            If => If,
+            Else => Else,
+            While => While,
+            Return => Return,
+            For => For,
+            Break => Break,
+            Continue => Continue,
+            Operator(op) => Operator(op),
+            Bool(bool) => Bool(bool),
+            Number(number) => Number(number),
+            Eof => Eof,
+            Error => Error,
+        }
+    }
 }
 
-struct Source<'t> {
+pub(crate) struct Source<'t> {
     inner: logos::Lexer<'t, Token<'t>>,
+    // Position of the lexed input inside of the whole source
+    offset: usize,
 }
 
 impl<'t> Source<'t> {
-    pub fn new(input: &'t str) -> Self {
+    pub fn starting_at(input: &'t str, offset: usize) -> Self {
         Self {
-            inner: Token::lexer(input),
+            inner: Token::lexer(&input[offset..]),
+            offset,
         }
     }
 }
@@ -190,6 +228,38 @@ impl<'t> Lexeme<'t> {
     }
 }
 
+// Lexeme that outlives its source, its text is sliced again from the source it gets attached to
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct DetachedLexeme {
+    token: Token<'static>,
+    pub(crate) span: Span,
+}
+
+impl<'t> Lexeme<'t> {
+    pub(crate) fn detach(&self) -> DetachedLexeme {
+        DetachedLexeme {
+            token: self.token.with_slice(""),
+            span: self.span(),
+        }
+    }
+}
+
+impl DetachedLexeme {
+    // Attaches the lexeme to a source in which it has moved by `delta` bytes
+    pub(crate) fn attach<'t>(&self, source: &'t str, delta: isize) -> Lexeme<'t> {
+        let span_start = (self.span.start as isize + delta) as usize;
+        let span_end = (self.span.end as isize + delta) as usize;
+        let slice = &source[span_start..span_end];
+
+        Lexeme {
+            token: self.token.with_slice(slice),
+            slice,
+            span_start,
+            span_end,
+        }
+    }
+}
+
 impl<'t> Iterator for Source<'t> {
     type Item = Lexeme<'t>;
 
@@ -201,27 +271,51 @@ impl<'t> Iterator for Source<'t> {
         Some(Lexeme {
             token,
             slice,
-            span_start: span.start,
-            span_end: span.end,
+            span_start: self.offset + span.start,
+            span_end: self.offset + span.end,
         })
     }
 }
 
+// Lexemes are either lexed on demand or were lexed before, e.g. while reparsing an edited source
+enum Lexemes<'t> {
+    Lexed(Source<'t>),
+    Cached(std::vec::IntoIter<Lexeme<'t>>),
+}
+
+impl<'t> Iterator for Lexemes<'t> {
+    type Item = Lexeme<'t>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            Lexemes::Lexed(source) => source.next(),
+            Lexemes::Cached(lexemes) => lexemes.next(),
+        }
+    }
+}
+
 pub(crate) struct Lexer<'t> {
     // Logos lexer that lexes our source input
-    inner: PeekNth<Source<'t>>,
+    inner: PeekNth<Lexemes<'t>>,
     current_span: Option<Span>,
 }
 
 impl<'t> Lexer<'t> {
     pub(crate) fn new(input: &'t str) -> Self {
         Self {
-            inner: peek_nth(Source::new(input)),
+            inner: peek_nth(Lexemes::Lexed(Source::starting_at(input, 0))),
+            current_span: None,
+        }
+    }
+
+    pub(crate) fn from_lexemes(lexemes: Vec<Lexeme<'t>>) -> Self {
+        Self {
+            inner: peek_nth(Lexemes::Cached(lexemes.into_iter())),
             current_span: None,
         }
     }
 
-    pub(crate) fn peek_nth(&mut self, nth: usize) -> Option<Lexeme> {
+    pub(crate) fn peek_nth(&mut self, nth: usize) -> Option<Lexeme<'_>> {
         self.inner.peek_nth(nth).copied()
     }
 
@@ -244,12 +338,21 @@ impl<'t> Iterator for Lexer<'t> {
     }
 }
 
+// Whether the text can be used as a name, keywords and anything with more than one token can't
+pub fn is_identifier(text: &str) -> bool {
+    let mut lexer = Lexer::new(text);
+    match (lexer.next(), lexer.next()) {
+        (Some(lexeme), None) => lexeme.token.is_identifier() && lexeme.slice == text,
+        _ => false,
+    }
+}
+
 #[cfg(test)]
 mod test {
     use quickcheck_macros::quickcheck;
 
     use crate::{
-        token::{operator::Operator, Lexeme, Lexer, Token},
+        token::{is_identifier, operator::Operator, Lexeme, Lexer, Token},
         utils::test::lexer::{
             assert_empty, assert_error, assert_token, assert_tokens, first_token, op,
         },
@@ -405,7 +508,10 @@ mod test {
         assert_token(
             "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ",
             Identifier("abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ"),
-        )
+        );
+        // this and super aren't keywords, the analyzer checks where they are used
+        assert_token("this", Identifier("this"));
+        assert_token("super", Identifier("super"));
     }
 
     #[test]
@@ -430,8 +536,6 @@ mod test {
         assert_token("for", For);
         assert_token("break", Break);
         assert_token("continue", Continue);
-        assert_token("this", This);
-        assert_token("super", Super);
     }
 
     #[test]
@@ -450,6 +554,17 @@ mod test {
         assert_token("cONTInue", Identifier("cONTInue"));
     }
 
+    #[test]
+    fn recognizes_identifiers() {
+        assert!(is_identifier("foo_bar2"));
+        assert!(is_identifier("lEt"));
+        assert!(!is_identifier("let"));
+        assert!(!is_identifier("foo bar"));
+        assert!(!is_identifier(" foo"));
+        assert!(!is_identifier("2foo"));
+        assert!(!is_identifier(""));
+    }
+
     #[test]
     fn lexer_skips_unnecessary_tokens() {
         // Skips comments
@@ -507,6 +622,6 @@ mod test {
         assert_token(";;;", Token::Semicolon);
         assert_token(",", Token::Comma);
         assert_token("=>", Token::Arrow);
-        assert_token(":", Token::Inherit);
+        assert_token(":", Token::Colon);
     }
 }
@@ -1,6 +1,6 @@
 use crate::{token::Token, utils::combine};
 use codespan_reporting::diagnostic::{Diagnostic, Label};
-use common::CompilerDiagnostic;
+use common::{CompilerDiagnostic, ProgramText};
 use logos::Span;
 use std::fmt::{self, Formatter};
 
@@ -26,6 +26,36 @@ impl fmt::Display for Expect {
     }
 }
 
+// Reasons why a function isn't pure
+#[derive(Debug, Clone, PartialEq)]
+pub enum SideEffect {
+    CallsNative(ProgramText),
+    MutatesCapture(ProgramText),
+    SetsProperty,
+    // Assigns to an index of an array or another value that isn't a variable
+    MutatesValue,
+    CallsImpure(ProgramText),
+    // Callee isn't a function declared in the program, e.g. a parameter or a method
+    CallsUnknown,
+}
+
+impl fmt::Display for SideEffect {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            SideEffect::CallsNative(name) => write!(f, "calls native function '{}'", name),
+            SideEffect::MutatesCapture(name) => {
+                write!(f, "assigns to captured variable '{}'", name)
+            }
+            SideEffect::SetsProperty => write!(f, "sets a property"),
+            SideEffect::MutatesValue => write!(f, "modifies a value it doesn't own"),
+            SideEffect::CallsImpure(name) => write!(f, "calls function '{}' that isn't pure", name),
+            SideEffect::CallsUnknown => {
+                write!(f, "calls a function that isn't known at compile time")
+            }
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum Forbidden {
     TrailingComma,
@@ -49,9 +79,33 @@ pub enum ParseErrorCause {
     UsedOutsideClass,
     CantInheritFromItself,
     SuperclassDoesntExist,
+    SuperWithoutSuperclass,
+    // Every class of the cycle, starting with the one where it was found
+    InheritanceCycle(Vec<ProgramText>),
     NotDefined,
     ReturnExprMustBeLast,
     ReturnUsedOutsideFunction,
+    DuplicateParameter {
+        name: ProgramText,
+        previous: Span,
+    },
+    FunctionRedeclared {
+        name: ProgramText,
+        previous: Span,
+    },
+    UnknownAnnotation(ProgramText),
+    // Side effect inside of a function annotated with @pure
+    PurityViolation {
+        function: ProgramText,
+        effect: SideEffect,
+        annotation: Span,
+    },
+    // Definition span is missing for native functions
+    WrongArity {
+        expected: usize,
+        found: usize,
+        definition: Option<Span>,
+    },
 }
 
 impl CompilerDiagnostic for ParseError {
@@ -63,7 +117,7 @@ impl CompilerDiagnostic for ParseError {
         // TODO: It's all repetetive
         match &self.cause {
             EndOfInput => Diagnostic::error().with_message("unexpected end of input"),
-            UnexpectedToken { .. } => Diagnostic::error()
+            UnexpectedToken => Diagnostic::error()
                 .with_message("Encountered unexpected token")
                 .with_labels(vec![
                     Label::primary(file_id, span).with_message("wasn't expected")
@@ -89,6 +143,17 @@ impl CompilerDiagnostic for ParseError {
             SuperclassDoesntExist => Diagnostic::error()
                 .with_message("Tried to inherit from a superclass that doesn't exist")
                 .with_labels(vec![Label::primary(file_id, span)]),
+            SuperWithoutSuperclass => Diagnostic::error()
+                .with_message("Use of 'super' in a class that doesn't inherit from anything")
+                .with_labels(vec![Label::primary(file_id, span)]),
+            InheritanceCycle(classes) => Diagnostic::error()
+                .with_message(format!(
+                    "Classes {} inherit from each other",
+                    classes.join(", ")
+                ))
+                .with_labels(vec![
+                    Label::primary(file_id, span).with_message("...the cycle starts here")
+                ]),
             UsedOutsideClass => Diagnostic::error()
                 .with_message("Use of 'super' or 'this' is forbidden outside class methods")
                 .with_labels(vec![Label::primary(file_id, span)]),
@@ -101,6 +166,56 @@ impl CompilerDiagnostic for ParseError {
             ReturnUsedOutsideFunction => Diagnostic::error()
                 .with_message("Return expression can only be used inside functions!")
                 .with_labels(vec![Label::primary(file_id, span)]),
+            DuplicateParameter { name, previous } => Diagnostic::error()
+                .with_message(format!("Parameter '{}' is declared more than once", name))
+                .with_labels(vec![
+                    Label::primary(file_id, span).with_message("...and again here"),
+                    Label::secondary(file_id, previous.clone())
+                        .with_message("Parameter declared here..."),
+                ]),
+            FunctionRedeclared { name, previous } => Diagnostic::error()
+                .with_message(format!("'{}' is already declared in this scope", name))
+                .with_labels(vec![
+                    Label::primary(file_id, span).with_message("...and declared again here"),
+                    Label::secondary(file_id, previous.clone())
+                        .with_message("First declared here..."),
+                ]),
+            UnknownAnnotation(name) => Diagnostic::error()
+                .with_message(format!("Unknown annotation '@{}'", name))
+                .with_labels(vec![Label::primary(file_id, span)])
+                .with_notes(vec!["the only supported annotation is '@pure'".to_owned()]),
+            PurityViolation {
+                function,
+                effect,
+                annotation,
+            } => Diagnostic::error()
+                .with_message(format!("Function '{}' isn't pure", function))
+                .with_labels(vec![
+                    Label::primary(file_id, span).with_message(format!("...but it {}", effect)),
+                    Label::secondary(file_id, annotation.clone())
+                        .with_message("Function is declared pure here..."),
+                ]),
+            WrongArity {
+                expected,
+                found,
+                definition,
+            } => {
+                let mut labels = vec![Label::primary(file_id, span)
+                    .with_message(format!("...but got {} here", found))];
+                if let Some(definition) = definition {
+                    labels.push(
+                        Label::secondary(file_id, definition.clone())
+                            .with_message(format!("Function takes {} arguments...", expected)),
+                    );
+                }
+
+                Diagnostic::error()
+                    .with_message(format!(
+                        "Function expected {} arguments but got {}",
+                        expected, found
+                    ))
+                    .with_labels(labels)
+            }
             _ => Diagnostic::error().with_message("TODO"),
         }
     }
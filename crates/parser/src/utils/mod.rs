@@ -2,6 +2,12 @@ use crate::parse::Span;
 
 pub mod error;
 
+pub fn combine(a: &Span, b: &Span) -> Span {
+    assert!(a.start <= b.end);
+
+    a.start..b.end
+}
+
 #[cfg(test)]
 pub(crate) mod test {
     pub(crate) mod lexer {
@@ -13,11 +19,11 @@ pub(crate) mod test {
             Token::Operator(operator)
         }
 
-        fn tokens(code: &str) -> Vec<Token> {
+        fn tokens(code: &str) -> Vec<Token<'_>> {
             Token::lexer(code).collect()
         }
 
-        pub(crate) fn first_token(code: &str) -> Token {
+        pub(crate) fn first_token(code: &str) -> Token<'_> {
             tokens(code)[0]
         }
 
@@ -76,9 +82,3 @@ pub(crate) mod test {
         }
     }
 }
-
-pub(crate) fn combine(a: &Span, b: &Span) -> Span {
-    assert!(a.start <= b.end);
-
-    a.start..b.end
-}
@@ -0,0 +1,240 @@
+use crate::{
+    gc::{HeapObject, HeapPointer},
+    runtime_error::RuntimeErrorCause,
+    runtime_value::RuntimeValue,
+    MachineResult, OperationResult, VM,
+};
+
+impl VM {
+    fn pop_array(&mut self) -> MachineResult<HeapPointer> {
+        match self.pop_operand()? {
+            RuntimeValue::HeapPointer(ptr)
+                if matches!(self.gc.deref(ptr), HeapObject::Array(_)) =>
+            {
+                Ok(ptr)
+            }
+            _ => self.error(RuntimeErrorCause::MismatchedTypes),
+        }
+    }
+
+    fn index(&mut self, array_ptr: HeapPointer, position: RuntimeValue) -> MachineResult<usize> {
+        let index = match position {
+            RuntimeValue::Number(index) if index.fract() == 0.0 => index,
+            _ => return self.error(RuntimeErrorCause::InvalidIndex),
+        };
+        let length = self.gc.deref(array_ptr).as_array().len();
+
+        if index < 0.0 || index >= length as f64 {
+            return self.error(RuntimeErrorCause::IndexOutOfBounds { index, length });
+        }
+
+        Ok(index as usize)
+    }
+
+    pub(crate) fn op_create_array(&mut self, amount: usize) -> OperationResult {
+        let mut elements = Vec::with_capacity(amount);
+        for _ in 0..amount {
+            elements.push(self.pop_operand()?);
+        }
+        // The first element was pushed first, so it was popped last
+        elements.reverse();
+
+        let array_ptr = self.gc.allocate(HeapObject::Array(elements));
+        self.push_operand(RuntimeValue::HeapPointer(array_ptr));
+        Ok(())
+    }
+
+    pub(crate) fn op_get_index(&mut self) -> OperationResult {
+        let position = self.pop_operand()?;
+        let array_ptr = self.pop_array()?;
+        let index = self.index(array_ptr, position)?;
+
+        let element = self.gc.deref(array_ptr).as_array()[index].clone();
+        self.push_operand(element);
+        Ok(())
+    }
+
+    pub(crate) fn op_set_index(&mut self) -> OperationResult {
+        let value = self.pop_operand()?;
+        let position = self.pop_operand()?;
+        let array_ptr = self.pop_array()?;
+        let index = self.index(array_ptr, position)?;
+
+        self.gc.deref_mut(array_ptr).as_array_mut()[index] = value.clone();
+        self.push_operand(value);
+        Ok(())
+    }
+
+    // Arrays are shown together with their elements, the ones containing themselves only once
+    pub(crate) fn format_value(&self, value: &RuntimeValue) -> String {
+        self.format_nested(value, &mut Vec::new())
+    }
+
+    fn format_nested(&self, value: &RuntimeValue, arrays: &mut Vec<HeapPointer>) -> String {
+        let array_ptr = match value {
+            RuntimeValue::HeapPointer(ptr)
+                if matches!(self.gc.deref(*ptr), HeapObject::Array(_)) =>
+            {
+                *ptr
+            }
+            value => return value.to_string(),
+        };
+        if arrays.contains(&array_ptr) {
+            return "[...]".to_owned();
+        }
+
+        arrays.push(array_ptr);
+        let elements: Vec<String> = self
+            .gc
+            .deref(array_ptr)
+            .as_array()
+            .iter()
+            .map(|element| self.format_nested(element, arrays))
+            .collect();
+        arrays.pop();
+
+        format!("[{}]", elements.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bytecode::{
+        chunk::{Chunk, Constant},
+        MemoryAddress, Opcode,
+    };
+
+    use crate::{
+        runtime_error::RuntimeErrorCause,
+        runtime_value::RuntimeValue,
+        test::{assert_program, assert_source, new_vm, source_error},
+    };
+
+    fn numbers(numbers: &[f64]) -> Vec<Constant> {
+        numbers.iter().copied().map(Constant::Number).collect()
+    }
+
+    #[test]
+    fn op_create_array() {
+        let mut vm = new_vm(Chunk::new(
+            vec![
+                Opcode::Constant(0),
+                Opcode::Constant(1),
+                Opcode::Constant(2),
+                Opcode::CreateArray(3),
+            ],
+            numbers(&[1.0, 2.0, 3.0]),
+        ));
+
+        let array = vm.execute().unwrap();
+        assert_eq!(vm.format_value(&array), "[1, 2, 3]");
+        assert!(vm.operands.is_empty());
+    }
+
+    #[test]
+    fn op_get_index() {
+        assert_program(
+            Chunk::new(
+                vec![
+                    Opcode::Constant(0),
+                    Opcode::Constant(1),
+                    Opcode::CreateArray(2),
+                    Opcode::Constant(2),
+                    Opcode::GetIndex,
+                ],
+                numbers(&[5.0, 7.0, 1.0]),
+            ),
+            RuntimeValue::Number(7.0),
+        );
+
+        let assert_fails = |index: f64, cause: RuntimeErrorCause| {
+            let mut vm = new_vm(Chunk::new(
+                vec![
+                    Opcode::Constant(0),
+                    Opcode::CreateArray(1),
+                    Opcode::Constant(1),
+                    Opcode::GetIndex,
+                ],
+                numbers(&[5.0, index]),
+            ));
+            assert_eq!(vm.execute().unwrap_err().cause, cause);
+        };
+        let out_of_bounds = |index| RuntimeErrorCause::IndexOutOfBounds { index, length: 1 };
+
+        assert_fails(1.0, out_of_bounds(1.0));
+        assert_fails(-1.0, out_of_bounds(-1.0));
+        assert_fails(0.5, RuntimeErrorCause::InvalidIndex);
+
+        // Only arrays can be indexed
+        let mut vm = new_vm(Chunk::new(
+            vec![Opcode::Constant(0), Opcode::Constant(1), Opcode::GetIndex],
+            numbers(&[5.0, 0.0]),
+        ));
+        assert_eq!(
+            vm.execute().unwrap_err().cause,
+            RuntimeErrorCause::MismatchedTypes
+        );
+    }
+
+    #[test]
+    fn op_set_index() {
+        let mut vm = new_vm(Chunk::new(
+            vec![
+                Opcode::Constant(0),
+                Opcode::Constant(1),
+                Opcode::CreateArray(2),
+                // the array stays in its slot, like a variable would
+                Opcode::Constant(2),
+                Opcode::Get,
+                Opcode::Constant(3),
+                Opcode::Constant(4),
+                Opcode::SetIndex,
+                // SetIndex results in the assigned value
+                Opcode::Pop(1),
+            ],
+            vec![
+                Constant::Number(1.0),
+                Constant::Number(2.0),
+                Constant::MemoryAddress(MemoryAddress::Local(0)),
+                Constant::Number(1.0),
+                Constant::String("foo".to_owned()),
+            ],
+        ));
+
+        let array = vm.execute().unwrap();
+        assert_eq!(vm.format_value(&array), "[1, foo]");
+    }
+
+    #[test]
+    fn runs_programs_with_arrays() {
+        assert_source("let a = [3, 5, 1]; a[1];", RuntimeValue::Number(5.0));
+        assert_source(
+            "let a = [3, 5, 1]; let i = 0; a[i + 2] = a[0] * 2; a[2];",
+            RuntimeValue::Number(6.0),
+        );
+        assert_source("[[1, 2], [3, 4]][1][0];", RuntimeValue::Number(3.0));
+        assert_eq!(
+            source_error("let a = [1, 2]; a[2];"),
+            RuntimeErrorCause::IndexOutOfBounds {
+                index: 2.0,
+                length: 2
+            }
+        );
+    }
+
+    #[test]
+    fn rejects_indexes_that_arent_whole_numbers() {
+        assert_eq!(
+            source_error("let a = [1, 2]; a[0.5];"),
+            RuntimeErrorCause::InvalidIndex
+        );
+        assert_eq!(
+            source_error("let a = [1, 2]; a[\"0\"] = 3;"),
+            RuntimeErrorCause::InvalidIndex
+        );
+        assert_eq!(
+            source_error("let a = [1, 2]; a[true];"),
+            RuntimeErrorCause::InvalidIndex
+        );
+    }
+}
@@ -11,6 +11,7 @@ impl RuntimeValue {
     pub(crate) fn add(self, other: RuntimeValue, vm: &mut VM) -> MachineResult<RuntimeValue> {
         match (self, other) {
             (RuntimeValue::Number(a), RuntimeValue::Number(b)) => Ok(RuntimeValue::Number(a + b)),
+            (RuntimeValue::String(a), RuntimeValue::String(b)) => Ok(RuntimeValue::String(a + &b)),
             _ => vm.error(RuntimeErrorCause::MismatchedTypes),
         }
     }
@@ -183,7 +184,10 @@ mod test {
     use crate::{
         runtime_error::RuntimeErrorCause,
         runtime_value::RuntimeValue,
-        test::{assert_program, create_two_operand_assertion},
+        test::{
+            assert_program, create_failable_two_operand_assertion, create_two_operand_assertion,
+            new_vm,
+        },
     };
 
     // Start of stuff that doesn't belong to any particular group
@@ -200,8 +204,8 @@ mod test {
         assert_constant(Constant::Bool(false));
         assert_constant(Constant::Bool(true));
         assert_constant(Constant::String("foo".to_owned()));
-        assert_constant(Constant::Number(std::f64::MAX));
-        assert_constant(Constant::Number(std::f64::MIN));
+        assert_constant(Constant::Number(f64::MAX));
+        assert_constant(Constant::Number(f64::MIN));
     }
 
     // End of stuff that doesn't belong to any particular group
@@ -211,13 +215,13 @@ mod test {
     #[test]
     fn op_neg() {
         // Accept only booleans
-        let mut code = new_vm(Chunk::new(
+        let mut vm = new_vm(Chunk::new(
             vec![Opcode::Constant(0), Opcode::Neg],
             vec![Constant::Bool(true)],
         ));
 
         assert_eq!(
-            vm.run().unwrap_err().cause,
+            vm.execute().unwrap_err().cause,
             RuntimeErrorCause::MismatchedTypes
         );
 
@@ -228,7 +232,7 @@ mod test {
             ));
 
             assert!(vm
-                .run()
+                .execute()
                 .unwrap()
                 .eq(&RuntimeValue::Number(e), &mut vm)
                 .unwrap())
@@ -237,8 +241,8 @@ mod test {
         assert_neg(1.0, -1.0);
         assert_neg(-1.0, 1.0);
         assert_neg(0.0, 0.0);
-        assert_neg(std::f64::MAX, std::f64::MIN);
-        assert_neg(std::f64::MIN, std::f64::MAX);
+        assert_neg(f64::MAX, f64::MIN);
+        assert_neg(f64::MIN, f64::MAX);
     }
 
     #[test]
@@ -250,7 +254,7 @@ mod test {
         ));
 
         assert_eq!(
-            vm.run().unwrap_err().cause,
+            vm.execute().unwrap_err().cause,
             RuntimeErrorCause::MismatchedTypes
         );
 
@@ -261,7 +265,7 @@ mod test {
             ));
 
             assert!(vm
-                .run()
+                .execute()
                 .unwrap()
                 .eq(&RuntimeValue::Bool(e), &mut vm)
                 .unwrap())
@@ -293,8 +297,22 @@ mod test {
         assert_add(-10.0, 10.0, 0.0);
         assert_add(10.0, 20.0, 30.0);
         assert_add(0.0, 0.0, 0.0);
-        assert_add(std::f64::MAX, std::f64::MAX, std::f64::INFINITY);
-        assert_add(std::f64::MIN, std::f64::MIN, std::f64::NEG_INFINITY);
+        assert_add(f64::MAX, f64::MAX, f64::INFINITY);
+        assert_add(f64::MIN, f64::MIN, f64::NEG_INFINITY);
+
+        // strings are concatenated
+        let assert_concat = create_two_operand_assertion(Opcode::Add);
+        assert_concat(
+            Constant::String("foo".to_owned()),
+            Constant::String("bar".to_owned()),
+            RuntimeValue::String("foobar".to_owned()),
+        );
+        let assert_fails = create_failable_two_operand_assertion(Opcode::Add);
+        assert_fails(
+            Constant::String("foo".to_owned()),
+            Constant::Number(1.0),
+            RuntimeErrorCause::MismatchedTypes,
+        );
     }
 
     #[test]
@@ -305,7 +323,7 @@ mod test {
                 vec![Constant::Bool(false), Constant::Bool(true)],
             ));
             assert_eq!(
-                vm.run().unwrap_err().cause,
+                vm.execute().unwrap_err().cause,
                 RuntimeErrorCause::MismatchedTypes
             );
         };
@@ -325,9 +343,9 @@ mod test {
         // Expect 10.0 to be on top of the stack
         assert_sub(10.0, 0.0, 10.0);
         assert_sub(0.0, 10.0, -10.0);
-        assert_sub(std::f64::MIN, std::f64::MIN, 0.0);
-        assert_sub(std::f64::MAX, std::f64::MAX, 0.0);
-        assert_sub(std::f64::MIN, -std::f64::MAX, 0.0);
+        assert_sub(f64::MIN, f64::MIN, 0.0);
+        assert_sub(f64::MAX, f64::MAX, 0.0);
+        assert_sub(f64::MIN, -f64::MAX, 0.0);
     }
 
     #[test]
@@ -338,9 +356,9 @@ mod test {
         assert_mul(10.0, 10.0, 100.0);
         assert_mul(0.0, 0.0, 0.0);
         assert_mul(-1.0, -1.0, 1.0);
-        assert_mul(std::f64::MAX, std::f64::MIN, std::f64::NEG_INFINITY);
-        assert_mul(std::f64::MAX, std::f64::MAX, std::f64::INFINITY);
-        assert_mul(std::f64::MIN, std::f64::MIN, std::f64::INFINITY);
+        assert_mul(f64::MAX, f64::MIN, f64::NEG_INFINITY);
+        assert_mul(f64::MAX, f64::MAX, f64::INFINITY);
+        assert_mul(f64::MIN, f64::MIN, f64::INFINITY);
     }
 
     #[test]
@@ -350,15 +368,15 @@ mod test {
             vec![Constant::Number(0.0), Constant::Number(0.0)],
         ));
 
-        if let RuntimeValue::Number(nan) = vm.run().unwrap() {
+        if let RuntimeValue::Number(nan) = vm.execute().unwrap() {
             assert!(nan.is_nan());
         } else {
             panic!("Expected NaN");
         }
 
         let assert_div = assert_arithmetic_op(Opcode::Div);
-        assert_div(std::f64::MAX, std::f64::MAX, 1.0);
-        assert_div(std::f64::MIN, std::f64::MIN, 1.0);
+        assert_div(f64::MAX, f64::MAX, 1.0);
+        assert_div(f64::MIN, f64::MIN, 1.0);
         assert_div(10.0, 1.0, 10.0);
         assert_div(-1.0, -1.0, 1.0);
     }
@@ -370,8 +388,8 @@ mod test {
         assert_mod(5.0, 3.0, 2.0);
         assert_mod(-1.0, 1.0, 0.0);
         assert_mod(1.0, -1.0, 0.0);
-        assert_mod(std::f64::MAX, std::f64::MAX, 0.0);
-        assert_mod(std::f64::MIN, std::f64::MIN, 0.0);
+        assert_mod(f64::MAX, f64::MAX, 0.0);
+        assert_mod(f64::MIN, f64::MIN, 0.0);
     }
 
     #[test]
@@ -381,8 +399,8 @@ mod test {
         assert_pow(-1.0, -1.0, -1.0);
         assert_pow(3.0, 2.0, 9.0);
         assert_pow(0.0, 0.0, 1.0);
-        assert_pow(std::f64::MAX, std::f64::MAX, std::f64::INFINITY);
-        assert_pow(std::f64::MIN, std::f64::MIN, 0.0);
+        assert_pow(f64::MAX, f64::MAX, f64::INFINITY);
+        assert_pow(f64::MIN, f64::MIN, 0.0);
     }
 
     #[test]
@@ -30,6 +30,17 @@ impl VM {
         Ok(args)
     }
 
+    // Bytecode isn't guaranteed to come from a program that passed the analysis
+    fn check_arity(&mut self, arity: usize, args_count: usize) -> MachineResult<()> {
+        if arity != args_count {
+            return self.error(RuntimeErrorCause::WrongArity {
+                expected: arity,
+                found: args_count,
+            });
+        }
+        Ok(())
+    }
+
     pub(crate) fn add_call_frame(&mut self, call_frame: CallFrame) {
         self.debug(format!(
             "[CALL_STACK][NEW FRAME][NAME={}][RETURN_IP={}][STACK_START={}]",
@@ -51,10 +62,11 @@ impl VM {
         ));
 
         self.ip = call_frame.return_ip;
+        self.close_upvalues(call_frame.stack_start);
         self.operands.truncate(call_frame.stack_start);
     }
 
-    fn closure_call(&mut self, closure_ptr: HeapPointer) -> CallOperation {
+    fn closure_call(&mut self, closure_ptr: HeapPointer, args_count: usize) -> CallOperation {
         let closure = self.gc.deref(closure_ptr).as_closure();
         let function_ptr = closure.function_ptr;
 
@@ -63,6 +75,7 @@ impl VM {
 
             (function.arity, function.name.clone())
         };
+        self.check_arity(arity, args_count)?;
 
         self.debug(format!("[VM][CALL][FUNCTION][NAME={}]", &name));
 
@@ -85,7 +98,7 @@ impl VM {
         Ok(CallType::EnterFnBody)
     }
 
-    fn bound_method_call(&mut self, method_ptr: HeapPointer) -> CallOperation {
+    fn bound_method_call(&mut self, method_ptr: HeapPointer, args_count: usize) -> CallOperation {
         let bound_method = self.gc.deref(method_ptr).as_bound_method();
         let recursion_handler = RuntimeValue::HeapPointer(bound_method.method_ptr);
         let this_handler = RuntimeValue::HeapPointer(bound_method.receiver);
@@ -93,6 +106,7 @@ impl VM {
             let function = self.deref_global(bound_method.method_ptr).as_function();
             (function.arity, function.name.clone())
         };
+        self.check_arity(arity, args_count)?;
 
         self.push_operand(recursion_handler);
         self.push_operand(this_handler);
@@ -123,14 +137,19 @@ impl VM {
     //     instance_ptr
     // }
 
-    fn native_function_call(&mut self, native_function: &NativeFunction) -> CallOperation {
+    fn native_function_call(
+        &mut self,
+        native_function: &NativeFunction,
+        args_count: usize,
+    ) -> CallOperation {
         let NativeFunction {
             arity,
             fn_body,
-            name,
+            name: _,
         } = native_function;
 
-        self.debug(format!("[VM][CALL][BUILT IN]"));
+        self.debug("[VM][CALL][BUILT IN]".to_string());
+        self.check_arity(*arity, args_count)?;
 
         let args = self.get_args(*arity)?;
         let result = fn_body(args, self);
@@ -138,14 +157,14 @@ impl VM {
         Ok(CallType::InlineFn)
     }
 
-    pub(crate) fn op_call(&mut self) -> CallOperation {
+    pub(crate) fn op_call(&mut self, args_count: usize) -> CallOperation {
         let callee = self.pop_operand()?;
         match callee {
             // RuntimeValue::GlobalPointer(global_ptr) => self.class_call(global_ptr),
             RuntimeValue::HeapPointer(heap_ptr) => {
                 let result = match self.gc.deref(heap_ptr) {
-                    HeapObject::Closure(_) => self.closure_call(heap_ptr),
-                    HeapObject::BoundMethod(_) => self.bound_method_call(heap_ptr),
+                    HeapObject::Closure(_) => self.closure_call(heap_ptr, args_count),
+                    HeapObject::BoundMethod(_) => self.bound_method_call(heap_ptr, args_count),
                     d => {
                         dbg!(d);
                         unreachable!()
@@ -158,7 +177,7 @@ impl VM {
                 let fun = NATIVE_FUNCTIONS
                     .get(&built_in_function)
                     .expect("We ensured during compilation that this exists.");
-                self.native_function_call(fun)
+                self.native_function_call(fun, args_count)
             }
             _ => self.error(RuntimeErrorCause::NotCallable),
         }
@@ -167,44 +186,66 @@ impl VM {
 
 #[cfg(test)]
 mod test {
-    use bytecode::{callables::Function, chunk::Constant, Opcode};
+    use bytecode::{
+        callables::Function,
+        chunk::{Chunk, Constant},
+        Opcode,
+    };
     use common::MAIN_FUNCTION_NAME;
 
-    use crate::{test::main_fn, Chunk, OperationResult, VM};
+    use crate::{
+        runtime_error::RuntimeErrorCause,
+        test::{source_error, with_globals},
+        OperationResult, VM,
+    };
 
-    #[test]
-    fn grow_callstack() {
+    fn call_function(name: &str) -> VM {
         let function = Function {
             arity: 0,
             chunk: Chunk::default(),
-            name: "foo".to_owned(),
+            name: name.to_owned(),
+            pure: false,
         };
 
-        let code = main_fn(Chunk::new(
-            vec![Opcode::Constant(0), Opcode::Call],
-            vec![Constant::Function(function)],
-        ));
+        let code = with_globals(
+            Chunk::new(
+                vec![
+                    Opcode::Constant(0),
+                    Opcode::CreateClosure(0),
+                    Opcode::Call(0),
+                ],
+                vec![Constant::GlobalPointer(0)],
+            ),
+            vec![function.into()],
+        );
+
+        let mut vm = VM::new();
+        vm.load(code);
+        vm
     }
 
     #[test]
-    fn change_callframe() -> OperationResult {
-        let function = Function {
-            arity: 0,
-            chunk: Chunk::default(),
-            name: "my_func".to_owned(),
-        };
+    fn grow_callstack() -> OperationResult {
+        let mut vm = call_function("foo");
+        assert_eq!(vm.call_stack.len(), 1);
+        // push the pointer, create closure and call it
+        vm.tick()?;
+        vm.tick()?;
+        vm.tick()?;
+        assert_eq!(vm.call_stack.len(), 2);
 
-        let mut code = main_fn(Chunk::new(
-            vec![Opcode::Constant(0), Opcode::Call],
-            vec![Constant::Function(function)],
-        ));
+        Ok(())
+    }
 
-        let mut vm = VM::new();
+    #[test]
+    fn change_callframe() -> OperationResult {
+        let mut vm = call_function("my_func");
 
         // we start with the global callframe which name is "main"
         let main_fn = vm.current_frame().name.clone();
         assert_eq!(&main_fn, MAIN_FUNCTION_NAME);
-        // push the constant onto the stack
+        // push the constant onto the stack and make a closure out of it
+        vm.tick()?;
         vm.tick()?;
         // call the function
         vm.tick()?;
@@ -214,4 +255,15 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    fn checks_arity_of_unknown_callees() {
+        assert_eq!(
+            source_error("let call = |f| => f(1, 2); call(|a| => a);"),
+            RuntimeErrorCause::WrongArity {
+                expected: 1,
+                found: 2
+            }
+        );
+    }
 }
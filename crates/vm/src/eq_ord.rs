@@ -4,7 +4,7 @@ use crate::{
 };
 
 impl RuntimeValue {
-    pub(crate) fn eq(&self, other: &RuntimeValue, vm: &mut VM) -> MachineResult<bool> {
+    pub(crate) fn eq(&self, other: &RuntimeValue, _vm: &mut VM) -> MachineResult<bool> {
         Ok(match (self, other) {
             (RuntimeValue::Number(a), RuntimeValue::Number(b)) => a == b,
             (RuntimeValue::String(a), RuntimeValue::String(b)) => a == b,
@@ -46,13 +46,12 @@ impl RuntimeValue {
         })
     }
 
-    pub(crate) fn to_bool(self, vm: &mut VM) -> MachineResult<bool> {
+    pub(crate) fn to_bool(&self, _vm: &mut VM) -> MachineResult<bool> {
         Ok(match self {
-            RuntimeValue::Number(Number) => true,
-            RuntimeValue::String(ProgramText) => true,
-            RuntimeValue::Bool(bool) => bool,
+            RuntimeValue::Bool(bool) => *bool,
             RuntimeValue::Null => false,
-            _ => unreachable!(),
+            // Numbers, strings, arrays and functions are all values that exist
+            _ => true,
         })
     }
 }
@@ -108,20 +107,35 @@ impl VM {
 
 #[cfg(test)]
 mod test {
-    use bytecode::{
-        chunk::{Chunk, Constant},
-        Opcode,
-    };
+    use bytecode::{chunk::Constant, Opcode};
 
     use crate::{
         runtime_error::RuntimeErrorCause,
         runtime_value::RuntimeValue,
         test::{
-            assert_program, create_failable_two_operand_assertion, create_two_operand_assertion,
-            dummy_class,
+            assert_source, create_failable_two_operand_assertion, create_two_operand_assertion,
         },
     };
 
+    #[test]
+    fn truthiness() {
+        let assert_truthy = |value: &str, truthy: bool| {
+            assert_source(
+                &format!("let a = {}; if a {{ 1 }} else {{ 0 }};", value),
+                RuntimeValue::Number(if truthy { 1.0 } else { 0.0 }),
+            );
+        };
+
+        assert_truthy("0", true);
+        assert_truthy("\"\"", true);
+        assert_truthy("false", false);
+        assert_truthy("if false { 1 }", false);
+        assert_truthy("[1]", true);
+        assert_truthy("[]", true);
+        assert_truthy("|| => 1", true);
+        assert_truthy("clock", true);
+    }
+
     #[test]
     fn op_eq() {
         let assert = create_two_operand_assertion(Opcode::Eq);
@@ -137,8 +151,8 @@ mod test {
         assert_numbers(0.0, 0.0);
         assert_numbers(10.0, 10.0);
         assert_numbers(-10.0, -10.0);
-        assert_numbers(std::f64::MIN, std::f64::MIN);
-        assert_numbers(std::f64::MAX, std::f64::MAX);
+        assert_numbers(f64::MIN, f64::MIN);
+        assert_numbers(f64::MAX, f64::MAX);
     }
 
     #[test]
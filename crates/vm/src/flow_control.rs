@@ -6,7 +6,9 @@ mod test {
     };
 
     use crate::{
-        runtime_error::RuntimeErrorCause, runtime_value::RuntimeValue, test::main_fn,
+        runtime_error::RuntimeErrorCause,
+        runtime_value::RuntimeValue,
+        test::{assert_source, main_fn},
         OperationResult, VM,
     };
 
@@ -20,8 +22,9 @@ mod test {
         let mut vm = VM::new();
         assert_eq!(vm.ip, 0);
         // opcodes advance the pointer to 0, and 1 and then we have a jump that advances by another 10 so 11
+        // and the pointer is moved past the jump itself, so we end up at 12
         assert!(vm.run(code)?.eq(&RuntimeValue::Number(127.0), &mut vm)?);
-        assert_eq!(vm.ip, 11);
+        assert_eq!(vm.ip, 12);
 
         Ok(())
     }
@@ -46,4 +49,24 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    fn loops_drop_their_variables_when_jumping() {
+        assert_source(
+            "
+            let total = 0;
+            let i = 0;
+            while i < 10 {
+                let next = i + 1;
+                i = next;
+                if next % 2 == 0 { continue; };
+                if next > 7 { break; };
+                total = total + next;
+            };
+            let last = i;
+            total * 100 + last;
+            ",
+            RuntimeValue::Number(1609.0),
+        );
+    }
 }
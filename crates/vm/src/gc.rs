@@ -1,5 +1,5 @@
 use core::panic;
-use std::{collections::HashMap, hash::Hash};
+use std::collections::HashMap;
 
 use bytecode::stmt::GlobalPointer;
 use common::ProgramText;
@@ -21,10 +21,15 @@ impl Closure {
             upvalues: Vec::new(),
         }
     }
+}
 
-    pub fn close_upvalue(&mut self, upvalue_ptr: HeapPointer) {
-        self.upvalues.push(upvalue_ptr);
-    }
+// Variable captured by closures, shared by all of them
+#[derive(Debug)]
+pub(crate) enum Upvalue {
+    // Variable is still on the stack, at the given index
+    Open(usize),
+    // Variable left the stack and its value moved here
+    Closed(RuntimeValue),
 }
 
 #[derive(Debug)]
@@ -45,10 +50,6 @@ impl Object {
         self.properties.get(name)
     }
 
-    pub fn get_method(&self, name: &str) -> Option<&RuntimeValue> {
-        self.properties.get(name)
-    }
-
     pub fn set(&mut self, name: ProgramText, value: RuntimeValue) {
         self.properties.insert(name, value);
     }
@@ -58,8 +59,9 @@ impl Object {
 pub(crate) enum HeapObject {
     Closure(Closure),
     BoundMethod(BoundMethod),
-    Value(RuntimeValue),
+    Upvalue(Upvalue),
     Object(Object),
+    Array(Vec<RuntimeValue>),
 }
 
 impl HeapObject {
@@ -70,10 +72,10 @@ impl HeapObject {
         }
     }
 
-    pub fn as_value(&self) -> &RuntimeValue {
+    pub fn as_upvalue(&self) -> &Upvalue {
         match self {
-            Self::Value(value) => value,
-            _ => panic!("Expected value"),
+            Self::Upvalue(upvalue) => upvalue,
+            _ => panic!("Expected upvalue"),
         }
     }
 
@@ -97,6 +99,20 @@ impl HeapObject {
             _ => panic!("Expected object"),
         }
     }
+
+    pub fn as_array(&self) -> &Vec<RuntimeValue> {
+        match self {
+            Self::Array(elements) => elements,
+            _ => panic!("Expected array"),
+        }
+    }
+
+    pub fn as_array_mut(&mut self) -> &mut Vec<RuntimeValue> {
+        match self {
+            Self::Array(elements) => elements,
+            _ => panic!("Expected array"),
+        }
+    }
 }
 
 impl From<Closure> for HeapObject {
@@ -105,9 +121,9 @@ impl From<Closure> for HeapObject {
     }
 }
 
-impl From<RuntimeValue> for HeapObject {
-    fn from(value: RuntimeValue) -> Self {
-        Self::Value(value)
+impl From<Upvalue> for HeapObject {
+    fn from(upvalue: Upvalue) -> Self {
+        Self::Upvalue(upvalue)
     }
 }
 
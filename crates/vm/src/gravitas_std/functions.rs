@@ -11,9 +11,9 @@ pub fn clock(_: FnArgs, _: &mut VM) -> RuntimeValue {
     )
 }
 
-pub fn print(args: FnArgs, _: &mut VM) -> RuntimeValue {
+pub fn print(args: FnArgs, vm: &mut VM) -> RuntimeValue {
     for arg in args {
-        println!("{}", arg);
+        println!("{}", vm.format_value(&arg));
     }
     RuntimeValue::Null
 }
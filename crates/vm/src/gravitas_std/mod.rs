@@ -38,7 +38,7 @@ macro_rules! hashmap {
 
 lazy_static! {
     pub static ref NATIVE_FUNCTIONS: HashMap<BuiltInFunction, NativeFunction> = hashmap! (
-        BuiltInFunction::Clock => NativeFunction { arity: 0, fn_body: clock, name: BuiltInFunction::Clock },
-        BuiltInFunction::Print => NativeFunction  { arity: 1, fn_body: print, name: BuiltInFunction::Print }
+        BuiltInFunction::Clock => NativeFunction { arity: BuiltInFunction::Clock.arity(), fn_body: clock, name: BuiltInFunction::Clock },
+        BuiltInFunction::Print => NativeFunction  { arity: BuiltInFunction::Print.arity(), fn_body: print, name: BuiltInFunction::Print }
     );
 }
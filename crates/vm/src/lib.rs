@@ -1,6 +1,7 @@
 use std::collections::HashMap;
 use std::fs::{File, OpenOptions};
 use std::io::prelude::*;
+use std::ops::Range;
 use std::path::Path;
 
 use crate::call::CallType;
@@ -17,6 +18,7 @@ use runtime_value::RuntimeValue;
 #[macro_use]
 extern crate prettytable;
 
+pub(crate) mod array;
 pub(crate) mod basic_expr;
 pub(crate) mod call;
 pub(crate) mod eq_ord;
@@ -24,9 +26,10 @@ pub(crate) mod flow_control;
 pub(crate) mod gc;
 pub mod gravitas_std;
 pub(crate) mod memory;
-pub(crate) mod runtime_error;
+pub mod runtime_error;
 pub mod runtime_value;
 pub(crate) mod stack;
+pub(crate) mod upvalue;
 
 pub type ProgramOutput = Result<RuntimeValue, RuntimeError>;
 pub type MachineResult<T> = Result<T, RuntimeError>;
@@ -71,16 +74,24 @@ pub struct VM {
 
     pub(crate) globals: Vec<GlobalItem>,
     pub(crate) gc: GC,
+    // Stack addresses of variables captured by closures, with their upvalues, lowest address first
+    pub(crate) open_upvalues: Vec<(usize, HeapPointer)>,
 }
 
-pub fn run(bytecode: ProgramBytecode, debug: bool) -> RuntimeValue {
+pub fn run(bytecode: ProgramBytecode, debug: bool) -> ProgramOutput {
     let mut vm = VM::new();
 
     if debug {
         vm = vm.with_debug();
     }
 
-    vm.run(bytecode).expect("VM went kaboom")
+    vm.run(bytecode)
+}
+
+impl Default for VM {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl VM {
@@ -92,6 +103,7 @@ impl VM {
             debug: None,
             globals: vec![],
             gc: GC::new(),
+            open_upvalues: vec![],
         }
     }
 
@@ -101,7 +113,29 @@ impl VM {
     }
 
     fn error<T>(&mut self, cause: RuntimeErrorCause) -> MachineResult<T> {
-        Err(RuntimeError { cause })
+        let span = self.current_span();
+        let location = self.current_location();
+        Err(RuntimeError {
+            cause,
+            span,
+            location,
+        })
+    }
+
+    // Source of the opcode under the instruction pointer
+    pub(crate) fn current_span(&self) -> Option<Range<usize>> {
+        if self.call_stack.is_empty() {
+            return None;
+        }
+        self.current_code().chunk.spans.range_at(self.ip).cloned()
+    }
+
+    // `file:line:column` of the opcode under the instruction pointer
+    pub(crate) fn current_location(&self) -> Option<String> {
+        if self.call_stack.is_empty() {
+            return None;
+        }
+        self.current_code().chunk.spans.location_at(self.ip)
     }
 
     // TODO: This probably could be hidden behind a feature flag to not
@@ -145,7 +179,14 @@ impl VM {
         let next = self.current_code().chunk.read_opcode(self.ip);
         use Opcode::*;
 
-        self.debug(format!("[OPCODE][NEXT]: {}", &next));
+        if self.debug.is_some() {
+            let source = match (self.current_location(), self.current_span()) {
+                (Some(location), _) => format!(" [AT={}]", location),
+                (None, Some(span)) => format!(" [SPAN={}..{}]", span.start, span.end),
+                (None, None) => String::new(),
+            };
+            self.debug(format!("[OPCODE][NEXT]: {}{}", &next, source));
+        }
 
         match next {
             Constant(index) => self.op_constant(index),
@@ -173,9 +214,10 @@ impl VM {
                 Ok(())
             }
             Jp(distance) => {
-                self.move_pointer(distance)?;
-                // So we don't increment the IP after jumping
-                Ok(())
+                // Generator counts on the pointer being advanced by one after the jump,
+                // both moves are done at once so loops starting at the first opcode don't underflow
+                self.move_pointer(distance + 1)?;
+                return Ok(TickOutcome::ContinueExecution);
             }
             Pop(amount) => self.op_pop(amount),
             Block(amount) => {
@@ -190,7 +232,7 @@ impl VM {
             }
             Get => self.op_get(),
             Asg => self.op_asg(),
-            Call => match self.op_call()? {
+            Call(args_count) => match self.op_call(args_count)? {
                 CallType::EnterFnBody => {
                     self.ip = 0;
                     return Ok(TickOutcome::ContinueExecution);
@@ -207,32 +249,10 @@ impl VM {
                 self.push_operand(RuntimeValue::Null);
                 Ok(())
             }
-            CreateClosure(upvalues_count) => {
-                let mut upvalues = vec![];
-
-                for _ in 0..upvalues_count {
-                    let upvalue_address = self.pop_operand()?.as_address();
-                    let upvalue = self.get_variable(upvalue_address.clone())?;
-                    let upvalue_ptr = self.gc.allocate(HeapObject::Value(upvalue));
-                    upvalues.push(upvalue_ptr);
-                }
-
-                let fn_ptr = self.pop_operand()?.as_global_pointer();
-                let closure_ptr = self.make_closure(fn_ptr);
-                let closure_mut = self.gc.deref_mut(closure_ptr);
-                for upvalue in upvalues {
-                    match closure_mut {
-                        HeapObject::Closure(closure) => {
-                            closure.close_upvalue(upvalue);
-                        }
-                        _ => {
-                            todo!();
-                        }
-                    }
-                }
-                self.push_operand(RuntimeValue::HeapPointer(closure_ptr));
-                Ok(())
-            }
+            CreateClosure(upvalues_count) => self.op_create_closure(upvalues_count),
+            CreateArray(amount) => self.op_create_array(amount),
+            GetIndex => self.op_get_index(),
+            SetIndex => self.op_set_index(),
             CreateObject(amount) => {
                 let mut properties: Properties = HashMap::new();
 
@@ -243,7 +263,7 @@ impl VM {
                     let mut value = self.pop_operand()?;
 
                     if let RuntimeValue::HeapPointer(method_ptr) = value {
-                        if let HeapObject::Closure(closure) = self.gc.deref(method_ptr) {
+                        if let HeapObject::Closure(_) = self.gc.deref(method_ptr) {
                             let bound_method_ptr =
                                 self.gc.allocate(HeapObject::BoundMethod(BoundMethod {
                                     receiver: obj_ptr,
@@ -268,7 +288,8 @@ impl VM {
                 let name = self.pop_operand()?.as_string().clone();
                 let obj_ptr = self.pop_operand()?.as_heap_pointer();
                 let obj = self.gc.deref_mut(obj_ptr).as_object_mut();
-                obj.set(name, value);
+                obj.set(name, value.clone());
+                self.push_operand(value);
                 Ok(())
             }
             GetProperty { .. } => {
@@ -292,15 +313,17 @@ impl VM {
     }
 
     pub(crate) fn make_closure(&mut self, function_ptr: GlobalPointer) -> HeapPointer {
-        let closure = Closure {
-            function_ptr,
-            upvalues: vec![],
-        };
+        let closure = Closure::new(function_ptr);
 
         self.gc.allocate(closure.into())
     }
 
     pub(crate) fn run(&mut self, program: ProgramBytecode) -> ProgramOutput {
+        self.load(program);
+        self.execute()
+    }
+
+    pub(crate) fn load(&mut self, program: ProgramBytecode) {
         for global in &program.globals {
             self.debug(format!("[GLOBAL][NAME={}]", global.name()));
             self.debug(format!("{}", global));
@@ -316,7 +339,9 @@ impl VM {
         };
 
         self.add_call_frame(initial_frame);
+    }
 
+    pub(crate) fn execute(&mut self) -> ProgramOutput {
         self.debug(format!(
             "[VM][START OF EXECUTION][NAME={}]",
             self.current_frame().name
@@ -363,19 +388,36 @@ impl VM {
 mod test {
     use super::*;
     use bytecode::{
-        callables::{Class, Function},
-        chunk::Constant,
+        callables::Function,
+        chunk::{Chunk, Constant},
     };
-    use common::CONSTRUCTOR_NAME;
 
-    pub(crate) fn main_fn(chunk: Chunk) -> Function {
-        Function {
+    pub(crate) fn main_fn(chunk: Chunk) -> ProgramBytecode {
+        with_globals(chunk, vec![])
+    }
+
+    // Main function is always put after the rest of globals, the same way generator does it
+    pub(crate) fn with_globals(chunk: Chunk, mut globals: Vec<GlobalItem>) -> ProgramBytecode {
+        let main = Function {
             arity: 0,
             chunk,
             name: MAIN_FUNCTION_NAME.to_owned(),
+            pure: false,
+        };
+        globals.push(main.into());
+
+        ProgramBytecode {
+            global_fn_ptr: globals.len() - 1,
+            globals,
         }
     }
 
+    pub(crate) fn new_vm(chunk: Chunk) -> VM {
+        let mut vm = VM::new();
+        vm.load(main_fn(chunk));
+        vm
+    }
+
     pub fn assert_program(code: Chunk, expected_outcome: RuntimeValue) {
         let mut vm = VM::new();
         assert!(vm
@@ -385,6 +427,33 @@ mod test {
             .unwrap());
     }
 
+    pub(crate) fn run_source(vm: &mut VM, code: &str) -> ProgramOutput {
+        let ast = parser::parse(code).expect("Parsing failed");
+        let mut analyzer = analyzer::Analyzer::new();
+        analyzer.analyze(&ast).expect("Analysis failed");
+        let bytecode =
+            bytecode::generate_bytecode(ast, analyzer.resolutions()).expect("Generation failed");
+        bytecode::verify::verify(&bytecode).expect("Generated bytecode is invalid");
+
+        vm.run(bytecode)
+    }
+
+    // Compiles and runs the whole program, the same way the CLI does it
+    pub(crate) fn assert_source(code: &str, expected_outcome: RuntimeValue) {
+        let mut vm = VM::new();
+        let outcome = run_source(&mut vm, code).unwrap();
+        assert!(
+            outcome.eq(&expected_outcome, &mut vm).unwrap(),
+            "Expected {}, got {}",
+            expected_outcome,
+            outcome
+        );
+    }
+
+    pub(crate) fn source_error(code: &str) -> RuntimeErrorCause {
+        run_source(&mut VM::new(), code).unwrap_err().cause
+    }
+
     pub(crate) fn create_failable_two_operand_assertion(
         opcode: Opcode,
     ) -> impl Fn(Constant, Constant, RuntimeErrorCause) {
@@ -415,17 +484,4 @@ mod test {
             assert!(result.eq(&expected, &mut vm).unwrap());
         }
     }
-
-    pub(crate) fn dummy_class() -> Class {
-        Class {
-            name: "dummy".to_owned(),
-            constructor: Function {
-                arity: 0,
-                chunk: Chunk::default(),
-                name: CONSTRUCTOR_NAME.to_owned(),
-            },
-            super_class: None,
-            methods: vec![],
-        }
-    }
 }
@@ -1,13 +1,15 @@
 use bytecode::MemoryAddress;
 
 use crate::{
-    gravitas_std::NATIVE_FUNCTIONS, runtime_error::RuntimeErrorCause, runtime_value::RuntimeValue,
-    MachineResult, OperationResult, VM,
+    runtime_error::RuntimeErrorCause, runtime_value::RuntimeValue, MachineResult, OperationResult,
+    VM,
 };
 
 impl VM {
     pub(crate) fn op_pop(&mut self, amount: usize) -> OperationResult {
-        for _ in 0..amount as usize {
+        // Popped variables might be captured by closures
+        self.close_upvalues(self.operands.len().saturating_sub(amount));
+        for _ in 0..amount {
             self.pop_operand()?;
         }
 
@@ -28,29 +30,9 @@ impl VM {
 
         match address {
             MemoryAddress::Local(local_address) => {
-                self.operands[stack_start + local_address as usize] = value;
-            }
-            MemoryAddress::Upvalue { index, is_ref } => {
-                let current_closure_ptr = self
-                    .call_stack
-                    .last()
-                    .map(|frame| frame.closure_ptr)
-                    .unwrap();
-
-                let closure = self.gc.deref(current_closure_ptr).as_closure();
-
-                let mut upvalue_ptr = closure.upvalues.get(index).cloned().unwrap();
-
-                if is_ref {
-                    while let RuntimeValue::HeapPointer(new_upvalue_ptr) =
-                        self.gc.deref(upvalue_ptr).as_value()
-                    {
-                        upvalue_ptr = *new_upvalue_ptr;
-                    }
-                }
-
-                *self.gc.deref_mut(upvalue_ptr) = value.into();
+                self.operands[stack_start + local_address] = value;
             }
+            MemoryAddress::Upvalue { index, .. } => self.set_upvalue(index, value),
             _ => unreachable!(),
         }
         Ok(())
@@ -59,7 +41,9 @@ impl VM {
     pub(crate) fn op_asg(&mut self) -> OperationResult {
         let to_assign = self.pop_operand()?;
         let address = self.pop_address()?;
-        self.assign_value(to_assign, address)?;
+        self.assign_value(to_assign.clone(), address)?;
+        // Assignment is an expression, its value is the assigned one
+        self.push_operand(to_assign);
 
         Ok(())
     }
@@ -69,7 +53,7 @@ impl VM {
         local_address: usize,
     ) -> MachineResult<RuntimeValue> {
         let stack_start = self.current_frame().stack_start;
-        let stack_address = stack_start + local_address as usize;
+        let stack_address = stack_start + local_address;
 
         match self.operands.get(stack_address).cloned() {
             Some(value) => {
@@ -83,34 +67,10 @@ impl VM {
         }
     }
 
-    pub(crate) fn get_upvalue(
-        &mut self,
-        upvalue_index: usize,
-        is_ref: bool,
-    ) -> MachineResult<RuntimeValue> {
-        let current_closure_ptr = self
-            .call_stack
-            .last()
-            .map(|frame| frame.closure_ptr)
-            .unwrap();
-
-        let closure = self.gc.deref(current_closure_ptr).as_closure();
-        let upvalue_ptr = closure.upvalues.get(upvalue_index).cloned().unwrap();
-        let mut upvalue = self.gc.deref(upvalue_ptr).as_value();
-
-        if is_ref {
-            while let RuntimeValue::HeapPointer(upvalue_ptr) = upvalue {
-                upvalue = self.gc.deref(*upvalue_ptr).as_value();
-            }
-        }
-
-        Ok(upvalue.clone())
-    }
-
     pub(crate) fn get_variable(&mut self, address: MemoryAddress) -> MachineResult<RuntimeValue> {
         match address {
             MemoryAddress::Local(stack_address) => self.get_local_variable(stack_address),
-            MemoryAddress::Upvalue { index, is_ref } => self.get_upvalue(index, is_ref),
+            MemoryAddress::Upvalue { index, .. } => Ok(self.get_upvalue(index)),
             MemoryAddress::BuiltInFunction(built_in_function) => {
                 Ok(RuntimeValue::NativeFunction(built_in_function))
             }
@@ -127,7 +87,11 @@ impl VM {
 
 #[cfg(test)]
 mod test {
-    use crate::{runtime_value::RuntimeValue, test::main_fn, OperationResult, VM};
+    use crate::{
+        runtime_value::RuntimeValue,
+        test::{assert_source, new_vm},
+        OperationResult,
+    };
     use bytecode::{
         chunk::{Chunk, Constant},
         MemoryAddress, Opcode,
@@ -135,7 +99,7 @@ mod test {
 
     #[test]
     fn op_pop() -> OperationResult {
-        let mut code = main_fn(Chunk::new(
+        let mut vm = new_vm(Chunk::new(
             vec![
                 Opcode::Constant(0),
                 Opcode::Constant(1),
@@ -149,8 +113,6 @@ mod test {
             ],
         ));
 
-        let mut vm = VM::new();
-
         // let's push the constants onto the stack
         vm.tick()?;
         vm.tick()?;
@@ -169,8 +131,7 @@ mod test {
 
     #[test]
     fn op_get() -> OperationResult {
-        let mut vm = VM::new();
-        let mut code = main_fn(Chunk::new(
+        let mut vm = new_vm(Chunk::new(
             vec![Opcode::Constant(0), Opcode::Constant(1), Opcode::Get],
             vec![
                 Constant::Bool(true),
@@ -195,8 +156,7 @@ mod test {
 
     #[test]
     fn op_asg() -> OperationResult {
-        let mut vm = VM::new();
-        let mut code = main_fn(Chunk::new(
+        let mut vm = new_vm(Chunk::new(
             vec![
                 Opcode::Constant(0),
                 Opcode::Constant(1),
@@ -232,4 +192,16 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    fn declarations_keep_their_slots_after_expression_statements() {
+        assert_source(
+            "let a = 1; print(a); let b = [2]; print(b[0]); a + b[0];",
+            RuntimeValue::Number(3.0),
+        );
+        assert_source(
+            "let a = 0; if a == 0 { a = 1; }; while a < 3 { a = a + 1; }; let b = a; b;",
+            RuntimeValue::Number(3.0),
+        );
+    }
 }
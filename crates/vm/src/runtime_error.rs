@@ -1,6 +1,15 @@
-#[derive(Clone, Copy, Debug, PartialEq)]
+use std::ops::Range;
+
+use codespan_reporting::diagnostic::{Diagnostic, Label};
+use common::{CompilerDiagnostic, Number};
+
+#[derive(Clone, Debug, PartialEq)]
 pub struct RuntimeError {
     pub cause: RuntimeErrorCause,
+    // Source of the opcode that failed, missing when the bytecode doesn't know it
+    pub span: Option<Range<usize>>,
+    // `file:line:column` of the same source, kept by bytecode files that come without it
+    pub location: Option<String>,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq)]
@@ -11,4 +20,95 @@ pub enum RuntimeErrorCause {
     ExpectedNumber,
     ExpectedAddress,
     NotCallable,
+    WrongArity { expected: usize, found: usize },
+    // Index that isn't a whole number, or isn't a number at all
+    InvalidIndex,
+    // Whole number that isn't between zero and the length of the array
+    IndexOutOfBounds { index: Number, length: usize },
+}
+
+impl CompilerDiagnostic for RuntimeError {
+    fn report(&self, file_id: usize) -> Diagnostic<usize> {
+        use RuntimeErrorCause::*;
+
+        let message = match self.cause {
+            PoppedFromEmptyStack => "Tried to pop a value from an empty stack".to_owned(),
+            MismatchedTypes => "Operation was applied to values of mismatched types".to_owned(),
+            StackOverflow => "Stack overflow".to_owned(),
+            ExpectedNumber => "Expected a number".to_owned(),
+            ExpectedAddress => "Expected a memory address".to_owned(),
+            NotCallable => "Tried to call a value that is not callable".to_owned(),
+            WrongArity { expected, found } => format!(
+                "Function takes {} arguments, but was called with {}",
+                expected, found
+            ),
+            InvalidIndex => "Arrays can only be indexed with whole numbers".to_owned(),
+            IndexOutOfBounds { index, length } => format!(
+                "Index {} is out of bounds for an array of length {}",
+                index, length
+            ),
+        };
+
+        let labels = match &self.span {
+            Some(span) => vec![Label::primary(file_id, span.clone())],
+            None => vec![],
+        };
+        // Labels already show where they point at
+        let notes = match (&self.span, &self.location) {
+            (None, Some(location)) => vec![format!("at {}", location)],
+            _ => vec![],
+        };
+
+        Diagnostic::error()
+            .with_message(message)
+            .with_labels(labels)
+            .with_notes(notes)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bytecode::gvc;
+    use common::{source::SourceDatabase, CompilerDiagnostic};
+
+    use crate::{test::run_source, VM};
+
+    fn failing_source(code: &str) -> &str {
+        let error = run_source(&mut VM::new(), code).unwrap_err();
+        &code[error.span.expect("Generated bytecode knows its spans")]
+    }
+
+    #[test]
+    fn points_at_failing_source() {
+        assert_eq!(failing_source("let a = true; let b = -a;"), "-a");
+        assert_eq!(
+            failing_source("fn at(list, i) { list[i] } at([1, 2], 1); at([1], 3);"),
+            "[i]"
+        );
+        assert_eq!(
+            failing_source("let call = |f| => f(1, 2); call(|a| => a);"),
+            "(1, 2)"
+        );
+    }
+
+    #[test]
+    fn points_at_lines_and_columns_without_source() {
+        let code = "let a = [1, 2];\nlet b = a[5];";
+        let mut db = SourceDatabase::new();
+        let file_id = db.add("main.vt", code);
+
+        let ast = parser::parse(code).unwrap();
+        let mut analyzer = analyzer::Analyzer::new();
+        analyzer.analyze(&ast).unwrap();
+        let mut bytecode = bytecode::generate_bytecode(ast, analyzer.resolutions()).unwrap();
+        bytecode.locate_spans(db.name(file_id), |offset| db.location(file_id, offset));
+
+        // Program compiled ahead of time comes without its source
+        let loaded = gvc::load(&gvc::write(&bytecode)).unwrap();
+        let error = VM::new().run(loaded).unwrap_err();
+
+        assert_eq!(error.span, None);
+        assert_eq!(error.location, Some("main.vt:2:10".to_owned()));
+        assert_eq!(error.report(0).notes, vec!["at main.vt:2:10".to_owned()]);
+    }
 }
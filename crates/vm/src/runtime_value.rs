@@ -54,7 +54,7 @@ impl fmt::Display for RuntimeValue {
             Number(num) => write!(f, "{}", num),
             String(text) => write!(f, "{}", text),
             Bool(bool) => write!(f, "{}", bool),
-            MemoryAddress(address) => write!(f, "{}", address.to_string()),
+            MemoryAddress(address) => write!(f, "{}", address),
             Null => write!(f, "null"),
             GlobalPointer(ptr) => write!(f, "global ptr: {}", ptr),
             HeapPointer(ptr) => write!(f, "heap ptr: {}", ptr),
@@ -1,6 +1,5 @@
 use bytecode::MemoryAddress;
-use common::Number;
-use prettytable::{Cell, Row, Table};
+use prettytable::Table;
 
 use crate::{runtime_error::RuntimeErrorCause, MachineResult, RuntimeValue, VM};
 
@@ -18,17 +17,10 @@ impl VM {
         self.debug(table.to_string());
     }
 
-    pub(crate) fn pop_number(&mut self) -> MachineResult<Number> {
-        match self.pop_operand()? {
-            RuntimeValue::Number(num) => Ok(num),
-            _ => return self.error(RuntimeErrorCause::ExpectedNumber),
-        }
-    }
-
     pub(crate) fn pop_address(&mut self) -> MachineResult<MemoryAddress> {
         match self.pop_operand()? {
             RuntimeValue::MemoryAddress(address) => Ok(address),
-            _ => return self.error(RuntimeErrorCause::ExpectedAddress),
+            _ => self.error(RuntimeErrorCause::ExpectedAddress),
         }
     }
 
@@ -60,8 +52,6 @@ impl VM {
 #[cfg(test)]
 mod test {
 
-    use bytecode::chunk::Chunk;
-
     use crate::{runtime_value::RuntimeValue, VM};
 
     #[test]
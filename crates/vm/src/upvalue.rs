@@ -0,0 +1,209 @@
+use bytecode::MemoryAddress;
+
+use crate::{
+    gc::{Closure, HeapObject, HeapPointer, Upvalue},
+    runtime_error::RuntimeErrorCause,
+    runtime_value::RuntimeValue,
+    MachineResult, OperationResult, VM,
+};
+
+impl VM {
+    fn current_closure(&self) -> &Closure {
+        match self.gc.deref(self.current_frame().closure_ptr) {
+            HeapObject::BoundMethod(bound_method) => {
+                self.gc.deref(bound_method.method_ptr).as_closure()
+            }
+            closure => closure.as_closure(),
+        }
+    }
+
+    fn upvalue_ptr(&self, index: usize) -> HeapPointer {
+        self.current_closure().upvalues[index]
+    }
+
+    // Closures capturing the same variable share its upvalue, so they all see its changes
+    fn capture(&mut self, address: MemoryAddress) -> MachineResult<HeapPointer> {
+        match address {
+            MemoryAddress::Local(local_address) => {
+                let stack_address = self.current_frame().stack_start + local_address;
+                let position = self
+                    .open_upvalues
+                    .binary_search_by_key(&stack_address, |(open_address, _)| *open_address);
+
+                match position {
+                    Ok(position) => Ok(self.open_upvalues[position].1),
+                    Err(position) => {
+                        let upvalue_ptr = self.gc.allocate(Upvalue::Open(stack_address).into());
+                        self.open_upvalues
+                            .insert(position, (stack_address, upvalue_ptr));
+                        Ok(upvalue_ptr)
+                    }
+                }
+            }
+            MemoryAddress::Upvalue { index, .. } => Ok(self.upvalue_ptr(index)),
+            MemoryAddress::BuiltInFunction(_) => self.error(RuntimeErrorCause::ExpectedAddress),
+        }
+    }
+
+    pub(crate) fn op_create_closure(&mut self, upvalues_count: usize) -> OperationResult {
+        let mut upvalues = Vec::with_capacity(upvalues_count);
+        for _ in 0..upvalues_count {
+            let address = self.pop_address()?;
+            upvalues.push(self.capture(address)?);
+        }
+
+        let function_ptr = self.pop_operand()?.as_global_pointer();
+        let closure_ptr = self.gc.allocate(
+            Closure {
+                function_ptr,
+                upvalues,
+            }
+            .into(),
+        );
+        self.push_operand(RuntimeValue::HeapPointer(closure_ptr));
+        Ok(())
+    }
+
+    pub(crate) fn get_upvalue(&self, index: usize) -> RuntimeValue {
+        match self.gc.deref(self.upvalue_ptr(index)).as_upvalue() {
+            Upvalue::Open(stack_address) => self.operands[*stack_address].clone(),
+            Upvalue::Closed(value) => value.clone(),
+        }
+    }
+
+    pub(crate) fn set_upvalue(&mut self, index: usize, value: RuntimeValue) {
+        let upvalue_ptr = self.upvalue_ptr(index);
+
+        match *self.gc.deref(upvalue_ptr).as_upvalue() {
+            Upvalue::Open(stack_address) => self.operands[stack_address] = value,
+            Upvalue::Closed(_) => *self.gc.deref_mut(upvalue_ptr) = Upvalue::Closed(value).into(),
+        }
+    }
+
+    // Variables from the given stack address upwards are about to leave the stack,
+    // their upvalues keep the last values. Only the captured ones are visited.
+    pub(crate) fn close_upvalues(&mut self, stack_address: usize) {
+        while let Some(&(open_address, upvalue_ptr)) = self.open_upvalues.last() {
+            if open_address < stack_address {
+                break;
+            }
+
+            let value = self.operands[open_address].clone();
+            *self.gc.deref_mut(upvalue_ptr) = Upvalue::Closed(value).into();
+            self.open_upvalues.pop();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{runtime_value::RuntimeValue, test::assert_source};
+
+    #[test]
+    fn counts_with_closures() {
+        assert_source(
+            "
+            fn make_counter() {
+                let count = 0;
+                |_step| => {
+                    count = count + 1;
+                    count
+                }
+            }
+            let counter = make_counter();
+            let other = make_counter();
+            counter(1);
+            counter(1);
+            other(1);
+            counter(1);
+            ",
+            RuntimeValue::Number(3.0),
+        );
+    }
+
+    #[test]
+    fn closures_capture_closures() {
+        assert_source(
+            "
+            fn adder(a) {
+                |b| => |c| => a + b + c
+            }
+            let add_one = adder(1);
+            let add_three = add_one(2);
+            add_three(10);
+            ",
+            RuntimeValue::Number(13.0),
+        );
+
+        // Nested closures share the variable with the function that declared it
+        assert_source(
+            "
+            fn outer() {
+                let value = 1;
+                let set = |x| => {
+                    let inner = |y| => {
+                        value = y;
+                    };
+                    inner(x);
+                };
+                set(5);
+                value
+            }
+            outer();
+            ",
+            RuntimeValue::Number(5.0),
+        );
+    }
+
+    #[test]
+    fn closures_outlive_their_scope() {
+        assert_source(
+            "
+            let get = 0;
+            let set = 0;
+            {
+                let hidden = 1;
+                get = |_x| => hidden;
+                set = |x| => {
+                    hidden = x;
+                };
+            };
+            set(42);
+            get(0);
+            ",
+            RuntimeValue::Number(42.0),
+        );
+
+        // Every call creates its own variable
+        assert_source(
+            "
+            fn make(value) {
+                |_x| => value
+            }
+            let first = make(1);
+            let second = make(2);
+            first(0) * 10 + second(0);
+            ",
+            RuntimeValue::Number(12.0),
+        );
+    }
+
+    #[test]
+    fn blocks_close_every_captured_variable() {
+        // Variables are captured in a different order than they were declared in
+        assert_source(
+            "
+            let closures = {
+                let a = 1;
+                let b = 2;
+                let both = |_x| => b * 100 + a;
+                let first = |_x| => a;
+                a = 3;
+                [both, first]
+            };
+            closures[0](0) + closures[1](0) * 1000;
+            ",
+            RuntimeValue::Number(3203.0),
+        );
+    }
+}
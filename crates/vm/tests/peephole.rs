@@ -0,0 +1,157 @@
+use analyzer::Analyzer;
+use bytecode::{
+    generate_unoptimized_bytecode, peephole::optimize, verify::verify, ProgramBytecode,
+};
+use quickcheck::{Arbitrary, Gen};
+use quickcheck_macros::quickcheck;
+use vm::{run, runtime_error::RuntimeErrorCause};
+
+// Every generated program can use these, so its bytecode has calls and upvalues as well
+const PRELUDE: &str = "
+    let a = 0;
+    let b = 1;
+    let c = 2;
+    fn twice(x) { if x > 5 { x } else { x * 2 } }
+    let bump = |x| => { a = a + x; a };
+";
+
+// Nesting of generated statements and expressions
+const DEPTH: usize = 3;
+
+fn variable(g: &mut Gen) -> String {
+    g.choose(&["a", "b", "c"]).unwrap().to_string()
+}
+
+fn expr(g: &mut Gen, depth: usize) -> String {
+    if depth == 0 || bool::arbitrary(g) {
+        return match bool::arbitrary(g) {
+            true => (u8::arbitrary(g) % 10).to_string(),
+            false => variable(g),
+        };
+    }
+
+    let variant = usize::arbitrary(g) % 6;
+    let mut inner = || expr(g, depth - 1);
+    match variant {
+        0 => format!("({} + {})", inner(), inner()),
+        1 => format!("({} * {})", inner(), inner()),
+        2 => format!(
+            "if {} > 3 {{ {} }} else {{ {} }}",
+            inner(),
+            inner(),
+            inner()
+        ),
+        3 => format!("twice({})", inner()),
+        4 => format!("bump({})", inner()),
+        _ => format!("{{ let v = {}; v - {} }}", inner(), inner()),
+    }
+}
+
+fn condition(g: &mut Gen, depth: usize) -> String {
+    let lhs = expr(g, depth);
+    let operator = *g.choose(&["<", "==", ">="]).unwrap();
+    format!("{} {} {}", lhs, operator, expr(g, depth))
+}
+
+fn body(g: &mut Gen, depth: usize) -> String {
+    let len = usize::arbitrary(g) % 4;
+    (0..len)
+        .map(|_| stmt(g, depth))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// Loops stop after a few iterations, whatever else breaks or continues them
+fn loop_body(g: &mut Gen, depth: usize) -> String {
+    let len = usize::arbitrary(g) % 4;
+    (0..len)
+        .map(|_| match usize::arbitrary(g) % 3 {
+            0 => format!("if {} {{ break; }};", condition(g, depth)),
+            1 => format!("if {} {{ continue; }};", condition(g, depth)),
+            _ => stmt(g, depth),
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn stmt(g: &mut Gen, depth: usize) -> String {
+    if depth == 0 || bool::arbitrary(g) {
+        return match bool::arbitrary(g) {
+            true => format!("{} = {};", variable(g), expr(g, DEPTH)),
+            false => format!("{};", expr(g, DEPTH)),
+        };
+    }
+
+    let depth = depth - 1;
+    match usize::arbitrary(g) % 4 {
+        0 => format!("if {} {{ {} }};", condition(g, DEPTH), body(g, depth)),
+        1 => format!(
+            "if {} {{ {} }} else {{ {} }};",
+            condition(g, DEPTH),
+            body(g, depth),
+            body(g, depth)
+        ),
+        2 => format!(
+            "{{ let v = {}; {} a = a + v; }};",
+            expr(g, DEPTH),
+            body(g, depth)
+        ),
+        _ => format!(
+            "{{ let i = 0; while i < 3 {{ i = i + 1; {} }}; }};",
+            loop_body(g, depth)
+        ),
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Program {
+    code: String,
+}
+
+impl Arbitrary for Program {
+    fn arbitrary(g: &mut Gen) -> Self {
+        let len = 1 + usize::arbitrary(g) % 5;
+        let stmts = (0..len).map(|_| stmt(g, DEPTH)).collect::<Vec<_>>();
+        let code = format!(
+            "{} {} a + b * 1000 + c * 1000000;",
+            PRELUDE,
+            stmts.join(" ")
+        );
+
+        Self { code }
+    }
+}
+
+fn compile(code: &str) -> ProgramBytecode {
+    let ast = parser::parse(code).expect("Parsing failed");
+    let mut analyzer = Analyzer::new();
+    analyzer.analyze(&ast).expect("Analysis failed");
+    generate_unoptimized_bytecode(ast, analyzer.resolutions()).expect("Generation failed")
+}
+
+// Spans of errors point at the same code either way, only their causes are compared
+fn outcome(bytecode: ProgramBytecode) -> Result<String, RuntimeErrorCause> {
+    run(bytecode, false)
+        .map(|value| value.to_string())
+        .map_err(|error| error.cause)
+}
+
+fn opcodes_count(bytecode: &ProgramBytecode) -> usize {
+    bytecode
+        .globals
+        .iter()
+        .map(|global| global.as_function().chunk.opcodes_len())
+        .sum()
+}
+
+// Programs run twice in a debug build, QUICKCHECK_TESTS can ask for more of them
+#[quickcheck]
+fn optimized_programs_give_the_same_results(program: Program) {
+    let bytecode = compile(&program.code);
+    let mut optimized = bytecode.clone();
+    optimize(&mut optimized);
+
+    assert_eq!(verify(&optimized), Ok(()));
+    assert!(opcodes_count(&optimized) <= opcodes_count(&bytecode));
+    assert_eq!(outcome(optimized), outcome(bytecode));
+}